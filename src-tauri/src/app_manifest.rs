@@ -0,0 +1,111 @@
+use std::{
+  fmt, io,
+  path::{Path, PathBuf},
+};
+
+use crate::keyvalues::{Document, KeyValuesError};
+
+/// `StateFlags` value of an app that is installed with no update pending or running.
+const STATE_FULLY_INSTALLED: u32 = 4;
+
+#[derive(Debug)]
+pub enum AppManifestError {
+  Io(io::Error),
+  Parse(KeyValuesError),
+  MissingField(&'static str),
+}
+
+impl fmt::Display for AppManifestError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AppManifestError::Io(e) => write!(f, "{}", e),
+      AppManifestError::Parse(e) => write!(f, "{}", e),
+      AppManifestError::MissingField(field) => write!(f, "Missing field {}", field),
+    }
+  }
+}
+
+impl From<io::Error> for AppManifestError {
+  fn from(e: io::Error) -> Self {
+    AppManifestError::Io(e)
+  }
+}
+
+/// The parts of Steam's `appmanifest_<id>.acf` the manager cares about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppManifest {
+  pub build_id: String,
+  pub state_flags: u32,
+}
+
+impl AppManifest {
+  /// Manifests live in the `steamapps` folder, two levels above the game
+  /// folder (`steamapps/common/<game>`).
+  pub fn path(game_path: &Path, app_id: u32) -> Option<PathBuf> {
+    let steamapps_path = game_path.parent()?.parent()?;
+
+    Some(steamapps_path.join(format!("appmanifest_{}.acf", app_id)))
+  }
+
+  pub fn open(path: &Path) -> Result<Self, AppManifestError> {
+    Self::parse(&std::fs::read_to_string(path)?)
+  }
+
+  pub fn parse(source: &str) -> Result<Self, AppManifestError> {
+    let document = Document::parse(source).map_err(AppManifestError::Parse)?;
+
+    let app_state = document
+      .root()
+      .find_block(&["AppState"])
+      .ok_or(AppManifestError::MissingField("AppState"))?;
+
+    let build_id = app_state
+      .find("buildid")
+      .and_then(|node| node.value())
+      .ok_or(AppManifestError::MissingField("buildid"))?;
+
+    let state_flags = app_state
+      .find("StateFlags")
+      .and_then(|node| node.value())
+      .and_then(|value| value.parse().ok())
+      .ok_or(AppManifestError::MissingField("StateFlags"))?;
+
+    Ok(AppManifest {
+      build_id: build_id.to_string(),
+      state_flags,
+    })
+  }
+
+  /// Whether Steam is done with the game, i.e. it is not downloading, staging
+  /// or verifying files that could still overwrite gameinfo.gi.
+  pub fn is_fully_installed(&self) -> bool {
+    self.state_flags == STATE_FULLY_INSTALLED
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MANIFEST: &str = "\"AppState\"\n{\n\t\"appid\"\t\t\"1422450\"\n\t\"StateFlags\"\t\t\"4\"\n\t\"installdir\"\t\t\"Deadlock\"\n\t\"buildid\"\t\t\"16013374\"\n\t\"InstalledDepots\"\n\t{\n\t\t\"1422456\"\n\t\t{\n\t\t\t\"manifest\"\t\t\"5371438520131738829\"\n\t\t}\n\t}\n}\n";
+
+  #[test]
+  fn reads_build_id_and_state() {
+    let manifest = AppManifest::parse(MANIFEST).unwrap();
+
+    assert_eq!(manifest.build_id, "16013374");
+    assert!(manifest.is_fully_installed());
+
+    let updating = AppManifest::parse(&MANIFEST.replace("\"4\"", "\"1026\"")).unwrap();
+
+    assert!(!updating.is_fully_installed());
+  }
+
+  #[test]
+  fn finds_manifest_next_to_common() {
+    assert_eq!(
+      AppManifest::path(Path::new("/steam/steamapps/common/Deadlock"), 1422450),
+      Some(PathBuf::from("/steam/steamapps/appmanifest_1422450.acf"))
+    );
+  }
+}
@@ -0,0 +1,380 @@
+use std::{
+  fmt,
+  fs::File,
+  io::{self, Read},
+  path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::copy_job::{CopyError, CopyJob};
+use crate::vpk;
+
+const ZIP_SIGNATURES: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+const SEVEN_ZIP_SIGNATURE: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const RAR_SIGNATURE: &[u8] = b"Rar!\x1a\x07";
+
+#[derive(Debug)]
+pub enum ArchiveError {
+  Io(io::Error),
+  Zip(zip::result::ZipError),
+  SevenZip(sevenz_rust::Error),
+  UnsupportedFormat,
+  Rar,
+  Cancelled,
+}
+
+impl fmt::Display for ArchiveError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ArchiveError::Io(e) => write!(f, "{}", e),
+      ArchiveError::Zip(e) => write!(f, "{}", e),
+      ArchiveError::SevenZip(e) => write!(f, "{}", e),
+      ArchiveError::UnsupportedFormat => write!(f, "Unsupported archive format"),
+      ArchiveError::Rar => write!(f, "RAR archives are not supported, extract it manually"),
+      ArchiveError::Cancelled => write!(f, "Extraction was cancelled"),
+    }
+  }
+}
+
+impl From<io::Error> for ArchiveError {
+  fn from(e: io::Error) -> Self {
+    ArchiveError::Io(e)
+  }
+}
+
+impl From<CopyError> for ArchiveError {
+  fn from(e: CopyError) -> Self {
+    match e {
+      CopyError::Cancelled => ArchiveError::Cancelled,
+      CopyError::Io(_, e) => ArchiveError::Io(e),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+  Zip,
+  SevenZip,
+}
+
+/// An addon found inside an archive.
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveAddon {
+  /// Path of the `.vpk` file inside the archive.
+  pub path: String,
+  #[serde(rename = "fileName")]
+  pub file_name: String,
+  /// Paths of the data chunks of a `_dir.vpk` file, in index order.
+  pub chunks: Vec<String>,
+  /// Size of the addon including its chunks.
+  pub size: u64,
+  #[serde(rename = "displayName")]
+  pub display_name: String,
+}
+
+fn detect_format(path: &Path) -> Result<ArchiveFormat, ArchiveError> {
+  let mut signature = Vec::with_capacity(8);
+
+  File::open(path)?.take(8).read_to_end(&mut signature)?;
+
+  if ZIP_SIGNATURES
+    .iter()
+    .any(|zip_signature| signature.starts_with(zip_signature))
+  {
+    Ok(ArchiveFormat::Zip)
+  } else if signature.starts_with(SEVEN_ZIP_SIGNATURE) {
+    Ok(ArchiveFormat::SevenZip)
+  } else if signature.starts_with(RAR_SIGNATURE) {
+    Err(ArchiveError::Rar)
+  } else {
+    Err(ArchiveError::UnsupportedFormat)
+  }
+}
+
+/// Paths and sizes of all files in the archive.
+fn read_entries(path: &Path) -> Result<Vec<(String, u64)>, ArchiveError> {
+  match detect_format(path)? {
+    ArchiveFormat::Zip => {
+      let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(ArchiveError::Zip)?;
+
+      let mut entries = vec![];
+
+      for index in 0..archive.len() {
+        let file = archive.by_index_raw(index).map_err(ArchiveError::Zip)?;
+
+        if file.is_file() {
+          entries.push((file.name().to_string(), file.size()));
+        }
+      }
+
+      Ok(entries)
+    }
+    ArchiveFormat::SevenZip => {
+      let archive = sevenz_rust::Archive::open(path).map_err(ArchiveError::SevenZip)?;
+
+      Ok(
+        archive
+          .files
+          .iter()
+          .filter(|file| !file.is_directory())
+          .map(|file| (file.name().to_string(), file.size()))
+          .collect(),
+      )
+    }
+  }
+}
+
+fn split_entry_path(entry_path: &str) -> (&str, &str) {
+  match entry_path.rfind(['/', '\\']) {
+    Some(index) => (&entry_path[..index], &entry_path[index + 1..]),
+    None => ("", entry_path),
+  }
+}
+
+fn file_stem(file_name: &str) -> &str {
+  file_name
+    .rsplit_once('.')
+    .map_or(file_name, |(stem, _)| stem)
+}
+
+/// Lists the addons contained in an archive. Data chunks (`*_NNN.vpk`) are
+/// grouped with the `*_dir.vpk` file next to them instead of being listed on
+/// their own; chunks without a `_dir.vpk` file are skipped.
+pub fn list_addons(path: &Path) -> Result<Vec<ArchiveAddon>, ArchiveError> {
+  let entries = read_entries(path)?
+    .into_iter()
+    .filter(|(entry_path, _)| entry_path.to_lowercase().ends_with(".vpk"))
+    .collect::<Vec<_>>();
+
+  let mut addons: Vec<ArchiveAddon> = vec![];
+
+  for (entry_path, size) in &entries {
+    let (folder, file_name) = split_entry_path(entry_path);
+
+    let is_chunk = vpk::chunk_dir_file_name(file_name).is_some_and(|dir_file_name| {
+      entries
+        .iter()
+        .any(|(other_path, _)| split_entry_path(other_path) == (folder, dir_file_name.as_str()))
+    });
+
+    if is_chunk {
+      continue;
+    }
+
+    let chunks = (0..vpk::DIR_ARCHIVE_INDEX)
+      .map_while(|index| vpk::chunk_file_name(file_name, index))
+      .map_while(|chunk_file_name| {
+        entries.iter().find(|(other_path, _)| {
+          split_entry_path(other_path) == (folder, chunk_file_name.as_str())
+        })
+      })
+      .collect::<Vec<_>>();
+
+    addons.push(ArchiveAddon {
+      path: entry_path.clone(),
+      // Stored under this name, which is only listed with a lowercase `.vpk`.
+      file_name: format!("{}.vpk", file_stem(file_name)),
+      size: size + chunks.iter().map(|(_, size)| size).sum::<u64>(),
+      chunks: chunks
+        .into_iter()
+        .map(|(chunk_path, _)| chunk_path.clone())
+        .collect(),
+      display_name: String::new(),
+    });
+  }
+
+  let archive_name = path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  let addon_names = addons
+    .iter()
+    .map(|addon| {
+      let addon_name = file_stem(&addon.file_name);
+
+      addon_name
+        .strip_suffix("_dir")
+        .unwrap_or(addon_name)
+        .to_string()
+    })
+    .collect::<Vec<_>>();
+
+  for (addon, addon_name) in addons.iter_mut().zip(&addon_names) {
+    let (folder, _) = split_entry_path(&addon.path);
+
+    // Variants of the same addon often only differ by folder.
+    let addon_name = match split_entry_path(folder) {
+      (_, folder_name)
+        if !folder_name.is_empty()
+          && addon_names
+            .iter()
+            .filter(|name| *name == addon_name)
+            .count()
+            > 1 =>
+      {
+        folder_name
+      }
+      _ => addon_name,
+    };
+
+    addon.display_name = if addon_names.len() == 1 {
+      archive_name.clone()
+    } else {
+      format!("{} - {}", archive_name, addon_name)
+    };
+  }
+
+  Ok(addons)
+}
+
+/// Extracts the given archive entries, each to its own destination path, as
+/// part of `copy_job`. Files already extracted are left behind when it fails.
+pub fn extract(
+  path: &Path,
+  files: &[(String, PathBuf)],
+  copy_job: &CopyJob,
+) -> Result<(), ArchiveError> {
+  let destination = |entry_path: &str| {
+    files
+      .iter()
+      .find(|(file_path, _)| file_path == entry_path)
+      .map(|(_, destination)| destination)
+  };
+
+  copy_job.add_total_bytes(
+    read_entries(path)?
+      .iter()
+      .filter(|(entry_path, _)| destination(entry_path).is_some())
+      .map(|(_, size)| size)
+      .sum(),
+  );
+
+  match detect_format(path)? {
+    ArchiveFormat::Zip => {
+      let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(ArchiveError::Zip)?;
+
+      for (entry_path, destination) in files {
+        let mut file = archive.by_name(entry_path).map_err(ArchiveError::Zip)?;
+
+        copy_job.write_file(&mut file, destination)?;
+      }
+    }
+    ArchiveFormat::SevenZip => {
+      let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+        .map_err(ArchiveError::SevenZip)?;
+
+      let mut copy_error = None;
+
+      let result = reader.for_each_entries(|entry, entry_reader| {
+        // Entries in solid blocks have to be read in order, so skipped ones
+        // are still consumed.
+        match destination(entry.name()) {
+          Some(destination) => {
+            if let Err(e) = copy_job.write_file(entry_reader, destination) {
+              copy_error = Some(e);
+
+              return Ok(false);
+            }
+          }
+          None => {
+            io::copy(entry_reader, &mut io::sink())?;
+          }
+        };
+
+        Ok(true)
+      });
+
+      if let Some(e) = copy_error {
+        return Err(e.into());
+      }
+
+      result.map_err(ArchiveError::SevenZip)?;
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+  use crate::copy_job::CancelToken;
+
+  const FIXTURE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/archive/variants.zip"
+  );
+
+  #[test]
+  fn groups_chunks_and_names_variants() {
+    let addons = list_addons(Path::new(FIXTURE)).unwrap();
+
+    assert_eq!(addons.len(), 2);
+
+    assert_eq!(addons[0].path, "Variant A/pak01_dir.vpk");
+    assert_eq!(
+      addons[0].chunks,
+      vec!["Variant A/pak01_000.vpk", "Variant A/pak01_001.vpk"]
+    );
+    assert_eq!(addons[0].size, 23);
+    assert_eq!(addons[0].display_name, "variants - Variant A");
+
+    assert_eq!(addons[1].path, "Variant B/pak01_dir.vpk");
+    assert!(addons[1].chunks.is_empty());
+    assert_eq!(addons[1].display_name, "variants - Variant B");
+  }
+
+  #[test]
+  fn extracts_selected_entries() {
+    let destination =
+      std::env::temp_dir().join(format!("citadel-cm-archive-test-{}", std::process::id()));
+
+    let files = [("Variant A/pak01_000.vpk".to_string(), destination.clone())];
+    let progress = Arc::new(Mutex::new(None));
+
+    let copy_job = CopyJob::new("job", CancelToken::default(), {
+      let progress = progress.clone();
+
+      move |p| *progress.lock().unwrap() = Some(p)
+    });
+
+    extract(Path::new(FIXTURE), &files, &copy_job).unwrap();
+
+    assert_eq!(std::fs::read(&destination).unwrap(), b"chunk a 0");
+
+    let progress = progress.lock().unwrap().clone().unwrap();
+
+    assert_eq!((progress.copied_bytes, progress.total_bytes), (9, 9));
+
+    std::fs::remove_file(&destination).unwrap();
+
+    let cancel = CancelToken::default();
+
+    cancel.cancel();
+
+    assert!(matches!(
+      extract(
+        Path::new(FIXTURE),
+        &files,
+        &CopyJob::new("job", cancel, |_| {})
+      ),
+      Err(ArchiveError::Cancelled)
+    ));
+    assert!(!destination.exists());
+  }
+
+  #[test]
+  fn rejects_rar() {
+    let path = std::env::temp_dir().join(format!("citadel-cm-rar-test-{}.rar", std::process::id()));
+
+    std::fs::write(&path, b"Rar!\x1a\x07\x01\x00").unwrap();
+
+    assert!(matches!(list_addons(&path), Err(ArchiveError::Rar)));
+
+    std::fs::remove_file(path).unwrap();
+  }
+}
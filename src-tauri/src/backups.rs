@@ -0,0 +1,293 @@
+use std::{
+  fs::File,
+  io::{self, Write},
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+const BACKUP_PREFIX: &str = "gameinfo-";
+const BACKUP_EXTENSION: &str = "gi";
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GameInfoBackup {
+  id: String,
+  /// Milliseconds since the Unix epoch.
+  #[serde(rename = "createdAt")]
+  created_at: u64,
+  hash: String,
+  size: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+  Unchanged,
+  Added,
+  Removed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffLine {
+  kind: DiffLineKind,
+  text: String,
+}
+
+pub fn hash(content: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(content))
+}
+
+fn parse_backup_file_name(file_name: &str) -> Option<(u64, String)> {
+  let id = file_name
+    .strip_prefix(BACKUP_PREFIX)?
+    .strip_suffix(BACKUP_EXTENSION)?
+    .strip_suffix('.')?;
+
+  let (created_at, hash) = id.split_once('-')?;
+
+  Some((created_at.parse().ok()?, hash.to_string()))
+}
+
+/// Lists snapshots in `backups_path`, newest first.
+pub fn list(backups_path: &Path) -> io::Result<Vec<GameInfoBackup>> {
+  if !backups_path.exists() {
+    return Ok(vec![]);
+  }
+
+  let mut backups = vec![];
+
+  for entry in std::fs::read_dir(backups_path)? {
+    let entry = entry?;
+
+    let Ok(file_name) = entry.file_name().into_string() else {
+      continue;
+    };
+
+    if let Some((created_at, hash)) = parse_backup_file_name(&file_name) {
+      backups.push(GameInfoBackup {
+        id: format!("{}-{}", created_at, hash),
+        created_at,
+        hash,
+        size: entry.metadata()?.len(),
+      });
+    }
+  }
+
+  backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+
+  Ok(backups)
+}
+
+fn backup_file_path(backups_path: &Path, id: &str) -> PathBuf {
+  backups_path.join(format!("{}{}.{}", BACKUP_PREFIX, id, BACKUP_EXTENSION))
+}
+
+/// Saves `content` as a new snapshot, unless the newest snapshot already has
+/// the same content.
+pub fn snapshot(backups_path: &Path, content: &[u8]) -> io::Result<GameInfoBackup> {
+  let content_hash = hash(content);
+
+  if let Some(latest) = list(backups_path)?.into_iter().next() {
+    if latest.hash == content_hash {
+      return Ok(latest);
+    }
+  }
+
+  std::fs::create_dir_all(backups_path)?;
+
+  let created_at = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_millis() as u64)
+    .unwrap_or_default();
+
+  let id = format!("{}-{}", created_at, content_hash);
+
+  write_atomic(&backup_file_path(backups_path, &id), content)?;
+
+  Ok(GameInfoBackup {
+    id,
+    created_at,
+    hash: content_hash,
+    size: content.len() as u64,
+  })
+}
+
+pub fn read(backups_path: &Path, id: &str) -> io::Result<Vec<u8>> {
+  // Ids come from the frontend, make sure they can't point outside the backups folder.
+  if parse_backup_file_name(&format!("{}{}.{}", BACKUP_PREFIX, id, BACKUP_EXTENSION)).is_none()
+    || id.contains(['/', '\\'])
+  {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      "Backup does not exist",
+    ));
+  }
+
+  std::fs::read(backup_file_path(backups_path, id))
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it over
+/// `path`, so readers never observe a partially written file.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+  let file_name = path
+    .file_name()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+
+  let mut temp_file_name = file_name.to_os_string();
+  temp_file_name.push(".tmp");
+
+  let temp_path = path.with_file_name(temp_file_name);
+
+  let result = File::create(&temp_path).and_then(|mut file| {
+    file.write_all(content)?;
+    file.sync_all()
+  });
+
+  if let Err(e) = result.and_then(|_| std::fs::rename(&temp_path, path)) {
+    let _ = std::fs::remove_file(&temp_path);
+
+    return Err(e);
+  }
+
+  Ok(())
+}
+
+/// Line based diff between `old` and `new` (longest common subsequence).
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+  let old_lines = old.lines().collect::<Vec<_>>();
+  let new_lines = new.lines().collect::<Vec<_>>();
+
+  let (n, m) = (old_lines.len(), new_lines.len());
+
+  let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+
+  for i in (0..n).rev() {
+    for j in (0..m).rev() {
+      lengths[i][j] = if old_lines[i] == new_lines[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+
+  let line = |kind, text: &str| DiffLine {
+    kind,
+    text: text.to_string(),
+  };
+
+  let mut diff = vec![];
+  let (mut i, mut j) = (0, 0);
+
+  while i < n && j < m {
+    if old_lines[i] == new_lines[j] {
+      diff.push(line(DiffLineKind::Unchanged, old_lines[i]));
+      i += 1;
+      j += 1;
+    } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+      diff.push(line(DiffLineKind::Removed, old_lines[i]));
+      i += 1;
+    } else {
+      diff.push(line(DiffLineKind::Added, new_lines[j]));
+      j += 1;
+    }
+  }
+
+  diff.extend(
+    old_lines[i..]
+      .iter()
+      .map(|l| line(DiffLineKind::Removed, l)),
+  );
+  diff.extend(new_lines[j..].iter().map(|l| line(DiffLineKind::Added, l)));
+
+  diff
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn snapshots_only_changed_content() {
+    let backups_path =
+      std::env::temp_dir().join(format!("citadel-cm-backups-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&backups_path);
+
+    assert!(list(&backups_path).unwrap().is_empty());
+
+    let first = snapshot(&backups_path, b"first").unwrap();
+
+    assert_eq!(snapshot(&backups_path, b"first").unwrap().id, first.id);
+    assert_eq!(list(&backups_path).unwrap().len(), 1);
+
+    std::thread::sleep(std::time::Duration::from_millis(2));
+
+    let second = snapshot(&backups_path, b"second").unwrap();
+    let backups = list(&backups_path).unwrap();
+
+    assert_ne!(second.id, first.id);
+    assert_eq!(
+      backups.iter().map(|b| b.id.as_str()).collect::<Vec<_>>(),
+      vec![second.id.as_str(), first.id.as_str()]
+    );
+    assert_eq!(read(&backups_path, &first.id).unwrap(), b"first");
+
+    std::fs::remove_dir_all(&backups_path).unwrap();
+  }
+
+  #[test]
+  fn refuses_ids_outside_the_backups_folder() {
+    let root = std::env::temp_dir().join(format!("citadel-cm-backups-ids-{}", std::process::id()));
+    let backups_path = root.join("backups");
+
+    _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&backups_path).unwrap();
+    std::fs::write(root.join("gameinfo-1-secret.gi"), b"secret").unwrap();
+
+    for id in [
+      "1-../../gameinfo-1-secret",
+      "../gameinfo-1-secret",
+      "1-..\\gameinfo-1-secret",
+      "not-a-timestamp",
+      "",
+    ] {
+      assert_eq!(
+        read(&backups_path, id).unwrap_err().kind(),
+        io::ErrorKind::NotFound,
+        "{}",
+        id
+      );
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn diffs_insertions_and_deletions() {
+    let diff = diff_lines("a\nb\nc\nd", "a\nc\nx\nd\ne");
+
+    assert_eq!(
+      diff
+        .iter()
+        .map(|line| (line.kind, line.text.as_str()))
+        .collect::<Vec<_>>(),
+      vec![
+        (DiffLineKind::Unchanged, "a"),
+        (DiffLineKind::Removed, "b"),
+        (DiffLineKind::Unchanged, "c"),
+        (DiffLineKind::Added, "x"),
+        (DiffLineKind::Unchanged, "d"),
+        (DiffLineKind::Added, "e"),
+      ]
+    );
+
+    assert!(diff_lines("same\n", "same\n")
+      .iter()
+      .all(|line| line.kind == DiffLineKind::Unchanged));
+    assert!(diff_lines("", "new")
+      .iter()
+      .all(|line| line.kind == DiffLineKind::Added));
+  }
+}
@@ -0,0 +1,533 @@
+//! Command line interface to the addon storage, for scripting and for use
+//! without the app. It reads and writes the same settings as the app.
+
+use std::path::PathBuf;
+
+use app_lib::{
+  categories::{AddonCategory, AddonFilter},
+  config::{self, ConfigStore, FileConfigStore},
+  copy_job::CopyJob,
+  error::ErrorPayload,
+  game::{
+    self, AddonError, AddonLocation, Context, GameInstall, InstallAddonInfo, ProfileError,
+    SearchPathsError, SettingsError,
+  },
+  migration::{MigrationConflictPolicy, MigrationOptions},
+  storage::AddonStorage,
+};
+use serde_json::{json, Value};
+
+const USAGE: &str = "\
+Usage: citadel-cm <command> [arguments]
+
+Commands:
+  status                          Show game path, addon storage and gameinfo.gi state
+  list [--category <category>] [--hero <hero>]
+                                  List stored and installed addons
+  classify [--force]              Show the categories and heroes of stored addons
+  thumbnail <addon>               Extract a preview image of a stored addon as PNG
+  install <file> [--name <file name>] [--display-name <name>]
+                                  Copy an addon to the addon storage
+  uninstall <addon>               Delete an addon from the addon storage
+  mount <addon>                   Install a stored addon into the game
+  unmount <addon> [--unmanaged]   Remove an addon from the game, with --unmanaged one placed
+                                  by hand, by its name in the addons folder, and store it
+  patch                           Add the addon search paths to gameinfo.gi
+  reset                           Remove the addon search paths from gameinfo.gi
+  plan-storage <path>             Show what changing the addon storage folder would copy
+  set-storage <path> [--on-conflict <skip|overwrite|rename>] [--delete-old]
+                                  Copy stored addons to <path> and use it as addon storage
+  games                           List Deadlock installs found in the Steam libraries
+  set-game <path>                 Use the game folder at <path> instead of the detected one
+  detect-game                     Forget the game folder set by hand and look for the game
+  recover                         Roll back an interrupted operation
+  verify                          Compare installed addons with their stored copies
+  resync <addon> --from <stored|mounted>
+                                  Replace one copy of an installed addon with the other
+  repair-links                    Point broken addon symlinks to the addon storage
+  adopt [--redeploy]              Store and manage addons copied into the game by hand
+  profile list
+  profile create <name> [<addon>...]
+  profile update <name> [--rename <new name>] [<addon>...]
+  profile delete <name>
+  profile apply <name>
+
+Output is written as JSON to stdout.";
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+/// Error payload and process exit code of a command failure.
+trait CliError {
+  fn payload(&self) -> ErrorPayload;
+
+  fn exit_code(&self) -> i32;
+}
+
+impl CliError for SearchPathsError {
+  fn payload(&self) -> ErrorPayload {
+    SearchPathsError::payload(self)
+  }
+
+  fn exit_code(&self) -> i32 {
+    match self {
+      SearchPathsError::CouldNotReadGameInfo(..) => 10,
+      SearchPathsError::CouldNotWriteGameInfo(..) => 11,
+      SearchPathsError::CouldNotParseGameInfo(_) => 12,
+      SearchPathsError::NoSearchPathsBlock => 13,
+      SearchPathsError::CouldNotBackupGameInfo(..) => 14,
+      SearchPathsError::CouldNotReadBackup(..) => 15,
+      SearchPathsError::NoGamePath => 16,
+    }
+  }
+}
+
+impl CliError for AddonError {
+  fn payload(&self) -> ErrorPayload {
+    AddonError::payload(self)
+  }
+
+  fn exit_code(&self) -> i32 {
+    match self {
+      AddonError::NoInstallPath => 20,
+      AddonError::NoGamePath => 21,
+      AddonError::CouldNotCreateAddonFolder(..) => 22,
+      AddonError::CouldNotReadInstallFolder(..) => 23,
+      AddonError::CouldNotReadAddonFolder(..) => 24,
+      AddonError::CouldNotWriteAddonFolder(..) => 25,
+      AddonError::CouldNotWriteInstallFolder(..) => 26,
+      AddonError::InvalidAddonFile => 27,
+      AddonError::AddonIsNotInstalled(_) => 28,
+      AddonError::AddonAlreadyMounted(_) => 29,
+      AddonError::AddonIsNotMounted(_) => 30,
+      AddonError::CannotDeleteMountedAddon(_) => 31,
+      AddonError::CouldNotReadAddon(_) => 32,
+      AddonError::AddonWouldConflict(_) => 33,
+      AddonError::NoFreeLoadSlot => 34,
+      AddonError::InvalidLoadOrder => 35,
+      AddonError::CouldNotReadArchive(_) => 36,
+      AddonError::CouldNotAccessJournal(..) => 37,
+      AddonError::OperationInProgress => 38,
+      AddonError::DeployMethodUnavailable => 39,
+      // 40 to 59 are taken by profile and settings errors.
+      AddonError::CouldNotLinkAddon(..) => 60,
+      AddonError::OperationFailed(_) => 61,
+      // Same as a process stopped with Ctrl+C.
+      AddonError::Cancelled => 130,
+    }
+  }
+}
+
+impl CliError for ProfileError {
+  fn payload(&self) -> ErrorPayload {
+    ProfileError::payload(self)
+  }
+
+  fn exit_code(&self) -> i32 {
+    match self {
+      ProfileError::ProfileNotFound => 40,
+      ProfileError::ProfileAlreadyExists => 41,
+      ProfileError::InvalidProfileName => 42,
+      ProfileError::Addon(e) => e.exit_code(),
+    }
+  }
+}
+
+impl CliError for SettingsError {
+  fn payload(&self) -> ErrorPayload {
+    SettingsError::payload(self)
+  }
+
+  fn exit_code(&self) -> i32 {
+    match self {
+      SettingsError::NoGamePath => 50,
+      SettingsError::InvalidGamePath(_) => 51,
+      SettingsError::StorageInsideGamePath { .. } => 52,
+      SettingsError::StoragePathNotFound(_) => 53,
+      SettingsError::NotEnoughSpace { .. } => 54,
+      SettingsError::CouldNotSaveSettings(_) => 55,
+      SettingsError::Migration(e) => e.exit_code(),
+    }
+  }
+}
+
+impl CliError for String {
+  fn payload(&self) -> ErrorPayload {
+    ErrorPayload::new("Error", self.as_str())
+  }
+
+  fn exit_code(&self) -> i32 {
+    EXIT_ERROR
+  }
+}
+
+struct Failure {
+  error: ErrorPayload,
+  exit_code: i32,
+}
+
+impl<E: CliError> From<E> for Failure {
+  fn from(e: E) -> Self {
+    Failure {
+      error: e.payload(),
+      exit_code: e.exit_code(),
+    }
+  }
+}
+
+fn usage_error(message: &str) -> Failure {
+  Failure {
+    error: ErrorPayload::new("InvalidArguments", format!("{}\n\n{}", message, USAGE)),
+    exit_code: EXIT_USAGE,
+  }
+}
+
+/// Removes `--flag <value>` from `args` and returns the value.
+fn take_option(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, Failure> {
+  let Some(index) = args.iter().position(|arg| arg == flag) else {
+    return Ok(None);
+  };
+
+  if index + 1 >= args.len() {
+    return Err(usage_error(&format!("Missing value for {}", flag)));
+  }
+
+  let value = args.remove(index + 1);
+
+  args.remove(index);
+
+  Ok(Some(value))
+}
+
+/// Removes `--flag` from `args` and returns whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+  let Some(index) = args.iter().position(|arg| arg == flag) else {
+    return false;
+  };
+
+  args.remove(index);
+
+  true
+}
+
+fn single_argument(args: Vec<String>, name: &str) -> Result<String, Failure> {
+  match <[String; 1]>::try_from(args) {
+    Ok([arg]) => Ok(arg),
+    Err(_) => Err(usage_error(&format!("Expected exactly one {}", name))),
+  }
+}
+
+fn no_arguments(args: Vec<String>) -> Result<(), Failure> {
+  if args.is_empty() {
+    Ok(())
+  } else {
+    Err(usage_error(&format!("Unexpected argument {}", args[0])))
+  }
+}
+
+fn run_profile_command(context: &Context, mut args: Vec<String>) -> Result<Value, Failure> {
+  if args.is_empty() {
+    return Err(usage_error("Missing profile command"));
+  }
+
+  let command = args.remove(0);
+
+  match command.as_str() {
+    "list" => {
+      no_arguments(args)?;
+
+      Ok(json!(context.list_profiles()))
+    }
+    "create" => {
+      if args.is_empty() {
+        return Err(usage_error("Missing profile name"));
+      }
+
+      let name = args.remove(0);
+
+      context.create_profile(&name, args)?;
+
+      Ok(Value::Null)
+    }
+    "update" => {
+      let new_name = take_option(&mut args, "--rename")?;
+
+      if args.is_empty() {
+        return Err(usage_error("Missing profile name"));
+      }
+
+      let name = args.remove(0);
+
+      context.update_profile(&name, args, new_name.as_deref())?;
+
+      Ok(Value::Null)
+    }
+    "delete" => {
+      context.delete_profile(&single_argument(args, "profile name")?)?;
+
+      Ok(Value::Null)
+    }
+    "apply" => Ok(json!(
+      context.apply_profile(&single_argument(args, "profile name")?)?
+    )),
+    _ => Err(usage_error(&format!("Unknown profile command {}", command))),
+  }
+}
+
+fn run_command(context: &Context, mut args: Vec<String>) -> Result<Value, Failure> {
+  if args.is_empty() {
+    return Err(usage_error("Missing command"));
+  }
+
+  let command = args.remove(0);
+
+  match command.as_str() {
+    "status" => {
+      no_arguments(args)?;
+
+      let search_paths = context.search_paths_report();
+
+      Ok(json!({
+        "gamePath": context.game.as_ref().map(|game| &game.path),
+        "installPath": context.storage.as_ref().map(|storage| &storage.path),
+        "searchPathsState": search_paths.as_ref().ok().map(|report| report.state),
+        "searchPathsError": search_paths.err().map(|e| e.payload()),
+        "deployMethod": context.deploy_method(),
+        "gameUpdateStatus": context.game_update_status(),
+      }))
+    }
+    "list" => {
+      let category = match take_option(&mut args, "--category")? {
+        Some(category) => Some(
+          serde_json::from_value::<AddonCategory>(json!(category)).map_err(|_| {
+            usage_error(
+              "Expected --category HeroSkin, AbilityVfx, Sound, Hud, Map, Localization or Misc",
+            )
+          })?,
+        ),
+        None => None,
+      };
+
+      let hero = take_option(&mut args, "--hero")?;
+
+      no_arguments(args)?;
+
+      let stored = context.list_installed_addons()?;
+      let mut mounted = context.scan_mounted_addons()?;
+
+      if category.is_none() && hero.is_none() {
+        return Ok(json!({ "stored": stored, "mounted": mounted }));
+      }
+
+      let filter = AddonFilter { category, hero };
+
+      let mounted_names = context.filter_addons(
+        mounted
+          .iter()
+          .map(|addon| addon.file_name().to_string())
+          .collect(),
+        &filter,
+      )?;
+
+      mounted.retain(|addon| mounted_names.iter().any(|name| name == addon.file_name()));
+
+      Ok(json!({
+        "stored": context.filter_addons(stored, &filter)?,
+        "mounted": mounted,
+      }))
+    }
+    "classify" => {
+      let force = take_flag(&mut args, "--force");
+
+      no_arguments(args)?;
+
+      Ok(json!(context.classify_addons(force)?))
+    }
+    "thumbnail" => {
+      let thumbnail = context.addon_thumbnail(&single_argument(args, "addon")?)?;
+
+      Ok(json!({ "path": thumbnail.map(|thumbnail| thumbnail.path) }))
+    }
+    "install" => {
+      let file_name = take_option(&mut args, "--name")?;
+      let display_name = take_option(&mut args, "--display-name")?;
+
+      context.install_addon(InstallAddonInfo {
+        file_path: single_argument(args, "addon file")?,
+        file_name,
+        display_name,
+      })?;
+
+      Ok(Value::Null)
+    }
+    "uninstall" => {
+      context.uninstall_addon(&single_argument(args, "addon")?)?;
+
+      Ok(Value::Null)
+    }
+    "mount" => Ok(json!({
+      "conflicts": context.mount_addon(&single_argument(args, "addon")?)?
+    })),
+    "unmount" => {
+      if take_flag(&mut args, "--unmanaged") {
+        return Ok(json!(
+          context.unmount_unmanaged_addon(&single_argument(args, "addon")?)?
+        ));
+      }
+
+      context.unmount_addon(&single_argument(args, "addon")?)?;
+
+      Ok(Value::Null)
+    }
+    "patch" => {
+      no_arguments(args)?;
+
+      context.mod_search_paths()?;
+
+      Ok(Value::Null)
+    }
+    "reset" => {
+      no_arguments(args)?;
+
+      context.reset_search_paths()?;
+
+      Ok(Value::Null)
+    }
+    "plan-storage" => Ok(json!(
+      context.plan_storage_migration(&PathBuf::from(single_argument(args, "path")?))?
+    )),
+    "set-storage" => {
+      let on_conflict = match take_option(&mut args, "--on-conflict")?.as_deref() {
+        None | Some("skip") => MigrationConflictPolicy::Skip,
+        Some("overwrite") => MigrationConflictPolicy::Overwrite,
+        Some("rename") => MigrationConflictPolicy::Rename,
+        Some(_) => {
+          return Err(usage_error(
+            "Expected --on-conflict skip, overwrite or rename",
+          ))
+        }
+      };
+
+      let options = MigrationOptions {
+        on_conflict,
+        delete_old: take_flag(&mut args, "--delete-old"),
+      };
+
+      let report =
+        context.set_install_path(&PathBuf::from(single_argument(args, "path")?), options)?;
+
+      if !report.completed {
+        return Err(Failure {
+          error: ErrorPayload::new(
+            "MigrationFailed",
+            "Some addons could not be copied, the old storage folder is still used",
+          )
+          .param("failed", &report.failed),
+          exit_code: EXIT_ERROR,
+        });
+      }
+
+      Ok(json!(report))
+    }
+    "games" => {
+      no_arguments(args)?;
+
+      Ok(json!(game::find_game_installs()))
+    }
+    "set-game" => {
+      let game = context.set_game_path(&PathBuf::from(single_argument(args, "path")?))?;
+
+      Ok(json!({ "gamePath": game.path }))
+    }
+    "detect-game" => {
+      no_arguments(args)?;
+
+      let game = context.detect_game_path()?;
+
+      Ok(json!({ "gamePath": game.map(|game| game.path) }))
+    }
+    "recover" => {
+      no_arguments(args)?;
+
+      Ok(json!({ "rolledBack": context.recover()? }))
+    }
+    "verify" => {
+      no_arguments(args)?;
+
+      Ok(json!(context.verify_deployment()?))
+    }
+    "resync" => {
+      let from = match take_option(&mut args, "--from")?.as_deref() {
+        Some("stored") => AddonLocation::Stored,
+        Some("mounted") => AddonLocation::Mounted,
+        _ => return Err(usage_error("Expected --from stored or --from mounted")),
+      };
+
+      context.resync_addon(&single_argument(args, "addon")?, from)?;
+
+      Ok(Value::Null)
+    }
+    "repair-links" => {
+      no_arguments(args)?;
+
+      Ok(json!(context.repair_links()?))
+    }
+    "adopt" => {
+      let redeploy = take_flag(&mut args, "--redeploy");
+
+      no_arguments(args)?;
+
+      Ok(json!(context.adopt_unmanaged_addons(redeploy)?))
+    }
+    "profile" => run_profile_command(context, args),
+    "help" | "--help" | "-h" => {
+      println!("{}", USAGE);
+
+      std::process::exit(0);
+    }
+    _ => Err(usage_error(&format!("Unknown command {}", command))),
+  }
+}
+
+fn run() -> Result<Value, Failure> {
+  let data_path = config::app_data_path();
+
+  let config_store = data_path
+    .as_ref()
+    .map(|data_path| FileConfigStore::open(&data_path.join(config::CONFIG_FILE_NAME)))
+    .ok_or_else(|| "App data folder not found".to_string())?
+    .map_err(|e| format!("Could not read settings: {}", e))?;
+
+  let storage = config_store
+    .get("install_path")
+    .and_then(|value| value.as_str().map(AddonStorage::new));
+
+  let context = Context {
+    game: GameInstall::resolve(&config_store),
+    storage,
+    data_path,
+    config: &config_store,
+    copy_job: CopyJob::default(),
+  };
+
+  run_command(&context, std::env::args().skip(1).collect())
+}
+
+fn main() {
+  match run() {
+    Ok(result) => {
+      println!("{}", json!({ "ok": true, "result": result }));
+    }
+    Err(failure) => {
+      println!(
+        "{}",
+        json!({
+          "ok": false,
+          "error": failure.error,
+        })
+      );
+
+      std::process::exit(failure.exit_code);
+    }
+  }
+}
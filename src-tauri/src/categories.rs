@@ -0,0 +1,168 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Kind of content an addon changes, guessed from the paths in its VPK.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AddonCategory {
+  HeroSkin,
+  AbilityVfx,
+  Sound,
+  Hud,
+  Map,
+  Localization,
+  Misc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct AddonClassification {
+  /// Every kind of content found, most files first. `Misc` only when nothing
+  /// else was recognized.
+  pub categories: Vec<AddonCategory>,
+  /// Internal hero names as used in the game files, e.g. `haze` or `hornet`.
+  pub heroes: Vec<String>,
+}
+
+impl AddonClassification {
+  pub fn matches(&self, filter: &AddonFilter) -> bool {
+    filter
+      .category
+      .map_or(true, |category| self.categories.contains(&category))
+      && filter.hero.as_ref().map_or(true, |hero| {
+        self
+          .heroes
+          .iter()
+          .any(|addon_hero| addon_hero.eq_ignore_ascii_case(hero))
+      })
+  }
+}
+
+/// Narrows addon listings down, every given criterion has to match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AddonFilter {
+  pub category: Option<AddonCategory>,
+  pub hero: Option<String>,
+}
+
+/// Category of a single file inside an addon.
+fn file_category(path: &str) -> AddonCategory {
+  let (folder, _) = path.split_once('/').unwrap_or(("", path));
+  let extension = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+  match (folder, extension) {
+    ("resource", _) if path.starts_with("resource/localization/") => AddonCategory::Localization,
+    ("panorama", _) | ("resource", _) => AddonCategory::Hud,
+    ("maps", _) | (_, "vmap_c") => AddonCategory::Map,
+    ("sounds" | "soundevents", _) | (_, "vsnd_c" | "vsndevts_c") => AddonCategory::Sound,
+    ("particles", _) | (_, "vpcf_c") => AddonCategory::AbilityVfx,
+    ("models" | "materials", _) if hero(path).is_some() => AddonCategory::HeroSkin,
+    _ => AddonCategory::Misc,
+  }
+}
+
+/// Hero a file belongs to: the folder following a `heroes` or `heroes_*`
+/// folder, as in `models/heroes_staging/haze/haze.vmdl_c`.
+fn hero(path: &str) -> Option<&str> {
+  let mut components = path.split('/');
+
+  // The last component is the file name.
+  components.next_back();
+
+  components
+    .by_ref()
+    .find(|component| *component == "heroes" || component.starts_with("heroes_"))?;
+
+  components.next().filter(|hero| !hero.is_empty())
+}
+
+/// Classifies an addon from the paths of the files it contains.
+pub fn classify<'a>(paths: impl IntoIterator<Item = &'a str>) -> AddonClassification {
+  let mut counts = [0usize; 7];
+  let mut heroes = BTreeSet::new();
+
+  for path in paths {
+    let path = path.to_lowercase();
+
+    counts[file_category(&path) as usize] += 1;
+
+    if let Some(hero) = hero(&path) {
+      heroes.insert(hero.to_string());
+    }
+  }
+
+  let mut categories = [
+    AddonCategory::HeroSkin,
+    AddonCategory::AbilityVfx,
+    AddonCategory::Sound,
+    AddonCategory::Hud,
+    AddonCategory::Map,
+    AddonCategory::Localization,
+  ]
+  .into_iter()
+  .filter(|category| counts[*category as usize] > 0)
+  .collect::<Vec<_>>();
+
+  // Stable, so ties keep the declaration order.
+  categories.sort_by_key(|category| std::cmp::Reverse(counts[*category as usize]));
+
+  if categories.is_empty() {
+    categories.push(AddonCategory::Misc);
+  }
+
+  AddonClassification {
+    categories,
+    heroes: heroes.into_iter().collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn classifies_by_paths_and_finds_heroes() {
+    let skin = classify([
+      "models/heroes_staging/haze/haze.vmdl_c",
+      "materials/models/heroes_staging/haze/haze_color.vtex_c",
+      "materials/models/heroes_staging/haze/haze.vmat_c",
+      "particles/abilities/haze/dagger.vpcf_c",
+    ]);
+
+    assert_eq!(
+      skin.categories,
+      vec![AddonCategory::HeroSkin, AddonCategory::AbilityVfx]
+    );
+    assert_eq!(skin.heroes, vec!["haze"]);
+
+    let mixed = classify([
+      "panorama/layout/hud.vxml_c",
+      "resource/localization/citadel_gc/citadel_gc_english.txt",
+      "sounds/heroes/Inferno/ult.vsnd_c",
+      "maps/dl_midtown.vmap_c",
+    ]);
+
+    assert_eq!(
+      mixed.categories,
+      vec![
+        AddonCategory::Sound,
+        AddonCategory::Hud,
+        AddonCategory::Map,
+        AddonCategory::Localization,
+      ]
+    );
+    assert_eq!(mixed.heroes, vec!["inferno"]);
+    assert!(mixed.matches(&AddonFilter {
+      category: Some(AddonCategory::Map),
+      hero: Some("Inferno".to_string()),
+    }));
+    assert!(!mixed.matches(&AddonFilter {
+      category: Some(AddonCategory::HeroSkin),
+      hero: None,
+    }));
+
+    let misc = classify(["models/props/crate.vmdl_c", "heroes/readme.txt"]);
+
+    assert_eq!(misc.categories, vec![AddonCategory::Misc]);
+    assert!(misc.heroes.is_empty());
+  }
+}
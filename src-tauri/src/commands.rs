@@ -0,0 +1,719 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tauri_plugin_store::{Store, StoreExt};
+
+use crate::archive::{self, ArchiveAddon};
+use crate::backups::{DiffLine, GameInfoBackup};
+use crate::categories::{AddonClassification, AddonFilter};
+use crate::config::ConfigStore;
+use crate::conflicts::AddonConflict;
+use crate::copy_job::{CancelToken, CopyJob};
+use crate::game::{
+  self, AddonContents, AddonDrift, AddonError, AddonLocation, AddonThumbnail, AdoptedAddon,
+  ConflictPolicy, ConflictReport, Context, DeployCapabilities, DeployMethod, DeployedAddon,
+  GameInstall, GameUpdateError, GameUpdateStatus, InstallAddonInfo, LinkRepair, ProfileApplyReport,
+  ProfileError, SearchPathsError, SettingsError,
+};
+use crate::gameinfo::{SearchPathsReport, SearchPathsState};
+use crate::journal::Operation;
+use crate::migration::{MigrationOptions, MigrationPlan, MigrationReport};
+use crate::profiles::Profile;
+use crate::storage::AddonStorage;
+use crate::watcher::{WatchEvent, Watcher};
+use crate::AppState;
+
+const GAME_UPDATE_EVENT: &str = "game-updated";
+const GAME_UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const ADDON_ADDED_EVENT: &str = "addon-added";
+const ADDON_REMOVED_EVENT: &str = "addon-removed";
+const ADDON_CHANGED_EVENT: &str = "addon-changed";
+const SEARCH_PATHS_STATE_CHANGED_EVENT: &str = "search-paths-state-changed";
+
+const COPY_PROGRESS_EVENT: &str = "copy-progress";
+
+impl<R: Runtime> ConfigStore for Store<R> {
+  fn get(&self, key: &str) -> Option<serde_json::Value> {
+    Store::get(self, key)
+  }
+
+  fn set(&self, key: &str, value: serde_json::Value) {
+    Store::set(self, key, value)
+  }
+
+  fn delete(&self, key: &str) -> bool {
+    Store::delete(self, key)
+  }
+
+  fn save(&self) -> Result<(), String> {
+    Store::save(self).map_err(|e| e.to_string())
+  }
+}
+
+/// Runs `f` with a [`Context`] backed by the app's state and settings store.
+fn with_context<T>(
+  state: &State<AppState>,
+  app_handle: &AppHandle,
+  f: impl FnOnce(&Context) -> T,
+) -> T {
+  with_copy_job(state, app_handle, CopyJob::default(), f)
+}
+
+fn with_copy_job<T>(
+  state: &State<AppState>,
+  app_handle: &AppHandle,
+  copy_job: CopyJob,
+  f: impl FnOnce(&Context) -> T,
+) -> T {
+  let config_store = app_handle.store_builder(".config").build();
+
+  let context = Context {
+    game: state.path.lock().unwrap().clone().map(GameInstall::new),
+    storage: state
+      .install_path
+      .lock()
+      .unwrap()
+      .clone()
+      .map(AddonStorage::new),
+    data_path: app_handle.path().app_data_dir().ok(),
+    config: &config_store,
+    copy_job,
+  };
+
+  f(&context)
+}
+
+/// Runs `f` on a blocking thread. With a `job_id`, its file copies emit
+/// [`COPY_PROGRESS_EVENT`] and can be stopped with [`cancel_job`]. A panic in
+/// `f` is returned as [`AddonError::OperationFailed`].
+async fn run_copy_job<T: Send + 'static, E: From<AddonError> + Send + 'static>(
+  app_handle: AppHandle,
+  job_id: Option<String>,
+  f: impl FnOnce(&Context) -> Result<T, E> + Send + 'static,
+) -> Result<T, E> {
+  let copy_job = job_id.clone().map(|job_id| {
+    let cancel = CancelToken::default();
+
+    let state = app_handle.state::<AppState>();
+
+    state
+      .copy_jobs
+      .lock()
+      .unwrap()
+      .insert(job_id.clone(), cancel.clone());
+
+    let emitter = app_handle.clone();
+
+    CopyJob::new(job_id, cancel, move |progress| {
+      _ = emitter.emit(COPY_PROGRESS_EVENT, &progress);
+    })
+  });
+
+  let result = tauri::async_runtime::spawn_blocking({
+    let app_handle = app_handle.clone();
+
+    move || {
+      let state = app_handle.state::<AppState>();
+
+      with_copy_job(&state, &app_handle, copy_job.unwrap_or_default(), f)
+    }
+  })
+  .await;
+
+  if let Some(job_id) = job_id {
+    let state = app_handle.state::<AppState>();
+
+    state.copy_jobs.lock().unwrap().remove(&job_id);
+  }
+
+  result.map_err(|e| AddonError::OperationFailed(e.to_string()))?
+}
+
+/// Rolls back an operation the app was closed or crashed in the middle of.
+pub fn recover_interrupted_operation(app_handle: &AppHandle) {
+  let state = app_handle.state::<AppState>();
+
+  match with_context(&state, app_handle, |context| context.recover()) {
+    Ok(Some(operation)) => {
+      log::warn!("Rolled back interrupted operation: {:?}", operation);
+
+      if let Operation::MigrateStorage { from, .. } = operation {
+        *state.install_path.lock().unwrap() = Some(from);
+      }
+    }
+    Ok(None) => {}
+    Err(e) => log::error!("Could not roll back interrupted operation: {:?}", e),
+  }
+}
+
+/// (Re)starts watching the addon folders and gameinfo.gi for the current
+/// paths, emitting an event for every change found.
+pub fn restart_watcher(app_handle: &AppHandle) {
+  let state = app_handle.state::<AppState>();
+
+  let mut watcher = state.watcher.lock().unwrap();
+
+  // Stop the old watcher first so it doesn't report the new paths twice.
+  *watcher = None;
+
+  let game = state.path.lock().unwrap().clone().map(GameInstall::new);
+  let storage = state
+    .install_path
+    .lock()
+    .unwrap()
+    .clone()
+    .map(AddonStorage::new);
+
+  let emitter = app_handle.clone();
+
+  match Watcher::start(game.as_ref(), storage.as_ref(), move |events| {
+    for event in events {
+      let event_name = match event {
+        WatchEvent::AddonAdded { .. } => ADDON_ADDED_EVENT,
+        WatchEvent::AddonRemoved { .. } => ADDON_REMOVED_EVENT,
+        WatchEvent::AddonChanged { .. } => ADDON_CHANGED_EVENT,
+        WatchEvent::SearchPathsStateChanged { .. } => SEARCH_PATHS_STATE_CHANGED_EVENT,
+      };
+
+      _ = emitter.emit(event_name, &event);
+    }
+  }) {
+    Ok(new_watcher) => *watcher = Some(new_watcher),
+    Err(e) => log::warn!("Could not watch addon folders: {:?}", e),
+  }
+}
+
+/// Checks for game updates on startup and then periodically, emitting
+/// [`GAME_UPDATE_EVENT`] when one is detected.
+pub fn watch_game_updates(app_handle: AppHandle) {
+  std::thread::spawn(move || loop {
+    let state = app_handle.state::<AppState>();
+
+    match with_context(&state, &app_handle, |context| {
+      context.check_for_game_update()
+    }) {
+      Ok(Some(status)) => {
+        _ = app_handle.emit(GAME_UPDATE_EVENT, &status);
+      }
+      Ok(None) | Err(GameUpdateError::NoGamePath) => {}
+      Err(e) => log::warn!("Could not check for game updates: {:?}", e),
+    }
+
+    std::thread::sleep(GAME_UPDATE_CHECK_INTERVAL);
+  });
+}
+
+#[tauri::command]
+pub fn get_game_path(state: State<AppState>) -> Option<PathBuf> {
+  state.path.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn list_game_installs() -> Vec<PathBuf> {
+  game::find_game_installs()
+}
+
+#[tauri::command]
+pub fn set_game_path(
+  state: State<AppState>,
+  game_path: String,
+  app_handle: AppHandle,
+) -> Result<(), SettingsError> {
+  let game = with_context(&state, &app_handle, |context| {
+    context.set_game_path(Path::new(&game_path))
+  })?;
+
+  *state.path.lock().unwrap() = Some(game.path);
+
+  restart_watcher(&app_handle);
+
+  Ok(())
+}
+
+/// Drops a game path set by hand and looks for the game again, returning the
+/// path found.
+#[tauri::command]
+pub fn detect_game_path(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<Option<PathBuf>, SettingsError> {
+  let game = with_context(&state, &app_handle, |context| context.detect_game_path())?;
+
+  let game_path = game.map(|game| game.path);
+
+  *state.path.lock().unwrap() = game_path.clone();
+
+  restart_watcher(&app_handle);
+
+  Ok(game_path)
+}
+
+#[tauri::command]
+pub fn get_search_paths_state(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<SearchPathsState, SearchPathsError> {
+  with_context(&state, &app_handle, |context| {
+    Ok(context.search_paths_report()?.state)
+  })
+}
+
+#[tauri::command]
+pub fn get_search_paths_report(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<SearchPathsReport, SearchPathsError> {
+  with_context(&state, &app_handle, |context| context.search_paths_report())
+}
+
+#[tauri::command]
+pub fn mod_search_paths(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<(), SearchPathsError> {
+  with_context(&state, &app_handle, |context| context.mod_search_paths())
+}
+
+#[tauri::command]
+pub fn reset_search_paths(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<(), SearchPathsError> {
+  with_context(&state, &app_handle, |context| context.reset_search_paths())
+}
+
+#[tauri::command]
+pub fn check_game_update(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<Option<GameUpdateStatus>, GameUpdateError> {
+  let status = with_context(&state, &app_handle, |context| {
+    context.check_for_game_update()
+  })?;
+
+  if let Some(status) = status.as_ref() {
+    _ = app_handle.emit(GAME_UPDATE_EVENT, status);
+  }
+
+  Ok(status)
+}
+
+#[tauri::command]
+pub fn get_game_update_status(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Option<GameUpdateStatus> {
+  with_context(&state, &app_handle, |context| context.game_update_status())
+}
+
+#[tauri::command]
+pub fn set_auto_repatch(state: State<AppState>, app_handle: AppHandle, auto_repatch: bool) {
+  with_context(&state, &app_handle, |context| {
+    context.set_auto_repatch(auto_repatch)
+  })
+}
+
+#[tauri::command]
+pub fn get_auto_repatch(state: State<AppState>, app_handle: AppHandle) -> bool {
+  with_context(&state, &app_handle, |context| context.auto_repatch())
+}
+
+#[tauri::command]
+pub fn list_game_info_backups(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<Vec<GameInfoBackup>, SearchPathsError> {
+  with_context(&state, &app_handle, |context| {
+    context.list_game_info_backups()
+  })
+}
+
+#[tauri::command]
+pub fn diff_game_info_backup(
+  state: State<AppState>,
+  backup_id: String,
+  app_handle: AppHandle,
+) -> Result<Vec<DiffLine>, SearchPathsError> {
+  with_context(&state, &app_handle, |context| {
+    context.diff_game_info_backup(&backup_id)
+  })
+}
+
+#[tauri::command]
+pub fn restore_game_info_backup(
+  state: State<AppState>,
+  backup_id: String,
+  app_handle: AppHandle,
+) -> Result<(), SearchPathsError> {
+  with_context(&state, &app_handle, |context| {
+    context.restore_game_info_backup(&backup_id)
+  })
+}
+
+#[tauri::command]
+pub fn get_install_path(state: State<AppState>) -> Option<PathBuf> {
+  let state = state.install_path.lock().unwrap();
+
+  state.clone()
+}
+
+#[tauri::command]
+pub fn plan_storage_migration(
+  install_path: String,
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<MigrationPlan, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.plan_storage_migration(Path::new(&install_path))
+  })
+}
+
+#[tauri::command]
+pub async fn set_install_path(
+  install_path: String,
+  options: Option<MigrationOptions>,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<MigrationReport, SettingsError> {
+  let new_install_path = PathBuf::from(install_path);
+
+  let report = run_copy_job(app_handle.clone(), job_id, {
+    let new_install_path = new_install_path.clone();
+
+    move |context| context.set_install_path(&new_install_path, options.unwrap_or_default())
+  })
+  .await?;
+
+  // A rolled back migration keeps the old storage path.
+  if report.completed {
+    let state = app_handle.state::<AppState>();
+
+    *state.install_path.lock().unwrap() = Some(new_install_path);
+
+    restart_watcher(&app_handle);
+  }
+
+  Ok(report)
+}
+
+#[tauri::command]
+pub fn list_installed_addons(
+  state: State<AppState>,
+  filter: Option<AddonFilter>,
+  app_handle: AppHandle,
+) -> Result<Vec<String>, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    let addons = context.list_installed_addons()?;
+
+    match filter {
+      Some(filter) => context.filter_addons(addons, &filter),
+      None => Ok(addons),
+    }
+  })
+}
+
+#[tauri::command]
+pub fn list_mounted_addons(
+  state: State<AppState>,
+  filter: Option<AddonFilter>,
+  app_handle: AppHandle,
+) -> Result<Vec<String>, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    let addons = context.list_mounted_addons()?;
+
+    match filter {
+      Some(filter) => context.filter_addons(addons, &filter),
+      None => Ok(addons),
+    }
+  })
+}
+
+#[tauri::command]
+pub fn classify_addons(
+  state: State<AppState>,
+  force: bool,
+  app_handle: AppHandle,
+) -> Result<BTreeMap<String, AddonClassification>, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.classify_addons(force)
+  })
+}
+
+#[tauri::command]
+pub async fn get_addon_thumbnail(
+  addon_file_name: String,
+  app_handle: AppHandle,
+) -> Result<Option<AddonThumbnail>, AddonError> {
+  // Decoding textures takes a moment, keep it off the main thread.
+  run_copy_job(app_handle, None, move |context| {
+    context.addon_thumbnail(&addon_file_name)
+  })
+  .await
+}
+
+#[tauri::command]
+pub fn get_load_order(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<Vec<DeployedAddon>, AddonError> {
+  with_context(&state, &app_handle, |context| context.scan_mounted_addons())
+}
+
+#[tauri::command]
+pub async fn install_addon(
+  input: InstallAddonInfo,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  run_copy_job(app_handle, job_id, |context| context.install_addon(input)).await
+}
+
+#[tauri::command]
+pub fn list_archive_addons(archive_path: String) -> Result<Vec<ArchiveAddon>, AddonError> {
+  archive::list_addons(Path::new(&archive_path)).map_err(AddonError::CouldNotReadArchive)
+}
+
+#[tauri::command]
+pub async fn import_archive(
+  archive_path: String,
+  addons: Vec<String>,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<Vec<String>, AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.import_archive(Path::new(&archive_path), &addons)
+  })
+  .await
+}
+
+#[tauri::command]
+pub fn uninstall_addon(
+  state: State<AppState>,
+  addon_file_name: String,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.uninstall_addon(&addon_file_name)
+  })
+}
+
+#[tauri::command]
+pub async fn mount_addon(
+  addon_file_name: String,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<Vec<AddonConflict>, AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.mount_addon(&addon_file_name)
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn unmount_addon(
+  addon_file_name: String,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.unmount_addon(&addon_file_name)
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn unmount_unmanaged_addon(
+  deployed_file_name: String,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<Option<String>, AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.unmount_unmanaged_addon(&deployed_file_name)
+  })
+  .await
+}
+
+#[tauri::command]
+pub fn reorder_mounted_addons(
+  state: State<AppState>,
+  order: Vec<String>,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.reorder_mounted_addons(order)
+  })
+}
+
+#[tauri::command]
+pub async fn set_deploy_method(
+  deploy_method: DeployMethod,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.set_deploy_method(deploy_method)
+  })
+  .await
+}
+
+#[tauri::command]
+pub fn is_symlink_available(state: State<AppState>, app_handle: AppHandle) -> bool {
+  with_context(&state, &app_handle, |context| {
+    context.is_symlink_available()
+  })
+}
+
+#[tauri::command]
+pub fn get_deploy_capabilities(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> DeployCapabilities {
+  with_context(&state, &app_handle, |context| context.deploy_capabilities())
+}
+
+#[tauri::command]
+pub fn get_deploy_method(state: State<AppState>, app_handle: AppHandle) -> DeployMethod {
+  with_context(&state, &app_handle, |context| context.deploy_method())
+}
+
+#[tauri::command]
+pub fn get_addon_contents(
+  state: State<AppState>,
+  addon_file_name: String,
+  location: AddonLocation,
+  app_handle: AppHandle,
+) -> Result<AddonContents, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.addon_contents(&addon_file_name, location)
+  })
+}
+
+#[tauri::command]
+pub fn detect_conflicts(
+  state: State<AppState>,
+  preview_addon_file_name: Option<String>,
+  app_handle: AppHandle,
+) -> Result<ConflictReport, AddonError> {
+  with_context(&state, &app_handle, |context| {
+    context.detect_conflicts(preview_addon_file_name.as_deref())
+  })
+}
+
+#[tauri::command]
+pub fn set_conflict_policy(
+  state: State<AppState>,
+  app_handle: AppHandle,
+  conflict_policy: ConflictPolicy,
+) {
+  with_context(&state, &app_handle, |context| {
+    context.set_conflict_policy(conflict_policy)
+  })
+}
+
+#[tauri::command]
+pub fn get_conflict_policy(state: State<AppState>, app_handle: AppHandle) -> ConflictPolicy {
+  with_context(&state, &app_handle, |context| context.conflict_policy())
+}
+
+#[tauri::command]
+pub fn list_profiles(state: State<AppState>, app_handle: AppHandle) -> Vec<Profile> {
+  with_context(&state, &app_handle, |context| context.list_profiles())
+}
+
+#[tauri::command]
+pub fn create_profile(
+  state: State<AppState>,
+  name: String,
+  addons: Vec<String>,
+  app_handle: AppHandle,
+) -> Result<(), ProfileError> {
+  with_context(&state, &app_handle, |context| {
+    context.create_profile(&name, addons)
+  })
+}
+
+#[tauri::command]
+pub fn update_profile(
+  state: State<AppState>,
+  name: String,
+  addons: Vec<String>,
+  new_name: Option<String>,
+  app_handle: AppHandle,
+) -> Result<(), ProfileError> {
+  with_context(&state, &app_handle, |context| {
+    context.update_profile(&name, addons, new_name.as_deref())
+  })
+}
+
+#[tauri::command]
+pub fn delete_profile(
+  state: State<AppState>,
+  name: String,
+  app_handle: AppHandle,
+) -> Result<(), ProfileError> {
+  with_context(&state, &app_handle, |context| context.delete_profile(&name))
+}
+
+#[tauri::command]
+pub async fn apply_profile(
+  name: String,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<ProfileApplyReport, ProfileError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.apply_profile(&name)
+  })
+  .await
+}
+
+#[tauri::command]
+pub fn verify_deployment(
+  state: State<AppState>,
+  app_handle: AppHandle,
+) -> Result<Vec<AddonDrift>, AddonError> {
+  with_context(&state, &app_handle, |context| context.verify_deployment())
+}
+
+#[tauri::command]
+pub async fn resync_addon(
+  addon_file_name: String,
+  from: AddonLocation,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<(), AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.resync_addon(&addon_file_name, from)
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn repair_links(
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<Vec<LinkRepair>, AddonError> {
+  run_copy_job(app_handle, job_id, |context| context.repair_links()).await
+}
+
+#[tauri::command]
+pub async fn adopt_unmanaged_addons(
+  redeploy: bool,
+  job_id: Option<String>,
+  app_handle: AppHandle,
+) -> Result<Vec<AdoptedAddon>, AddonError> {
+  run_copy_job(app_handle, job_id, move |context| {
+    context.adopt_unmanaged_addons(redeploy)
+  })
+  .await
+}
+
+/// Stops a running copy job, whatever it already copied is removed again.
+#[tauri::command]
+pub fn cancel_job(state: State<AppState>, job_id: String) {
+  if let Some(cancel) = state.copy_jobs.lock().unwrap().get(&job_id) {
+    cancel.cancel();
+  }
+}
@@ -0,0 +1,79 @@
+use std::{
+  io,
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+
+use serde_json::{Map, Value};
+
+use crate::backups;
+
+/// Bundle identifier from `tauri.conf.json`, the app data folder is named after it.
+pub const APP_IDENTIFIER: &str = "com.citadel-content-manager";
+
+/// Name of the settings file inside the app data folder.
+pub const CONFIG_FILE_NAME: &str = ".config";
+
+/// Key-value settings storage shared by the app and the command line tool.
+pub trait ConfigStore {
+  fn get(&self, key: &str) -> Option<Value>;
+
+  fn set(&self, key: &str, value: Value);
+
+  fn delete(&self, key: &str) -> bool;
+
+  fn save(&self) -> Result<(), String>;
+}
+
+/// Folder the app keeps its settings and gameinfo.gi backups in, same as
+/// Tauri's `app_data_dir`.
+pub fn app_data_path() -> Option<PathBuf> {
+  Some(dirs::data_dir()?.join(APP_IDENTIFIER))
+}
+
+/// Reads and writes the settings file directly, in the same format as the
+/// store plugin, for use outside of the app.
+pub struct FileConfigStore {
+  path: PathBuf,
+  values: Mutex<Map<String, Value>>,
+}
+
+impl FileConfigStore {
+  pub fn open(path: &Path) -> io::Result<Self> {
+    let values = match std::fs::read(path) {
+      Ok(content) => serde_json::from_slice(&content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Map::new(),
+      Err(e) => return Err(e),
+    };
+
+    Ok(FileConfigStore {
+      path: path.to_path_buf(),
+      values: Mutex::new(values),
+    })
+  }
+}
+
+impl ConfigStore for FileConfigStore {
+  fn get(&self, key: &str) -> Option<Value> {
+    self.values.lock().unwrap().get(key).cloned()
+  }
+
+  fn set(&self, key: &str, value: Value) {
+    self.values.lock().unwrap().insert(key.to_string(), value);
+  }
+
+  fn delete(&self, key: &str) -> bool {
+    self.values.lock().unwrap().remove(key).is_some()
+  }
+
+  fn save(&self) -> Result<(), String> {
+    let content = serde_json::to_vec(&*self.values.lock().unwrap()).map_err(|e| e.to_string())?;
+
+    if let Some(parent) = self.path.parent() {
+      std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    backups::write_atomic(&self.path, &content).map_err(|e| e.to_string())
+  }
+}
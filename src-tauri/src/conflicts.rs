@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Files provided by a single addon.
+pub struct AddonFiles {
+  pub addon: String,
+  /// `pakNN_dir.vpk` slot the addon is loaded from, if any.
+  pub slot: Option<u32>,
+  pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AddonConflict {
+  pub path: String,
+  /// Addons providing this file, highest priority first.
+  pub addons: Vec<String>,
+  pub winner: String,
+}
+
+/// Orders addons from highest to lowest priority. Addons without a slot go last.
+pub fn sort_by_priority(addons: &mut [AddonFiles]) {
+  addons.sort_by(|a, b| {
+    let a_key = (a.slot.unwrap_or(u32::MAX), &a.addon);
+    let b_key = (b.slot.unwrap_or(u32::MAX), &b.addon);
+
+    a_key.cmp(&b_key)
+  });
+}
+
+/// Finds every internal path provided by more than one addon.
+///
+/// `addons` must already be ordered by priority, see [`sort_by_priority`].
+pub fn find_conflicts(addons: &[AddonFiles]) -> Vec<AddonConflict> {
+  let mut providers: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+
+  for addon in addons {
+    for file in &addon.files {
+      let (_, file_addons) = providers
+        .entry(file.to_lowercase())
+        .or_insert_with(|| (file.clone(), vec![]));
+
+      if !file_addons.contains(&addon.addon) {
+        file_addons.push(addon.addon.clone());
+      }
+    }
+  }
+
+  providers
+    .into_values()
+    .filter(|(_, file_addons)| file_addons.len() > 1)
+    .map(|(path, file_addons)| AddonConflict {
+      path,
+      winner: file_addons[0].clone(),
+      addons: file_addons,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn addon(addon: &str, slot: Option<u32>, files: &[&str]) -> AddonFiles {
+    AddonFiles {
+      addon: addon.to_string(),
+      slot,
+      files: files.iter().map(|file| file.to_string()).collect(),
+    }
+  }
+
+  #[test]
+  fn sorts_by_slot_with_unslotted_addons_last() {
+    let mut addons = vec![
+      addon("unslotted_b.vpk", None, &[]),
+      addon("late.vpk", Some(7), &[]),
+      addon("unslotted_a.vpk", None, &[]),
+      addon("early.vpk", Some(2), &[]),
+    ];
+
+    sort_by_priority(&mut addons);
+
+    assert_eq!(
+      addons.iter().map(|a| a.addon.as_str()).collect::<Vec<_>>(),
+      vec![
+        "early.vpk",
+        "late.vpk",
+        "unslotted_a.vpk",
+        "unslotted_b.vpk"
+      ]
+    );
+  }
+
+  #[test]
+  fn finds_case_insensitive_collisions_in_priority_order() {
+    let mut addons = vec![
+      addon(
+        "low.vpk",
+        Some(5),
+        &["materials/HUD.vmat_c", "sounds/a.vsnd_c"],
+      ),
+      addon("high.vpk", Some(1), &["materials/hud.vmat_c"]),
+      addon("other.vpk", Some(3), &["models/crate.vmdl_c"]),
+    ];
+
+    sort_by_priority(&mut addons);
+
+    let conflicts = find_conflicts(&addons);
+
+    assert_eq!(conflicts.len(), 1);
+    // Reported with the spelling of the addon that wins.
+    assert_eq!(conflicts[0].path, "materials/hud.vmat_c");
+    assert_eq!(conflicts[0].addons, vec!["high.vpk", "low.vpk"]);
+    assert_eq!(conflicts[0].winner, "high.vpk");
+  }
+
+  #[test]
+  fn reports_nothing_without_shared_files() {
+    let addons = vec![
+      addon("hud.vpk", Some(1), &["panorama/hud.css"]),
+      // The same path twice in one addon is not a conflict.
+      addon(
+        "skin.vpk",
+        Some(2),
+        &["models/skin.vmdl_c", "MODELS/skin.vmdl_c"],
+      ),
+    ];
+
+    assert!(find_conflicts(&addons).is_empty());
+    assert!(find_conflicts(&[]).is_empty());
+  }
+}
@@ -0,0 +1,274 @@
+use std::{
+  cell::Cell,
+  fs::File,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
+
+use serde::Serialize;
+
+/// Files are copied in chunks of this size, cancellation is checked and
+/// progress is reported after each one.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Progress of a copy job, the total grows as the job finds more files to copy.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct CopyProgress {
+  #[serde(rename = "jobId")]
+  pub job_id: String,
+  #[serde(rename = "copiedBytes")]
+  pub copied_bytes: u64,
+  #[serde(rename = "totalBytes")]
+  pub total_bytes: u64,
+}
+
+#[derive(Debug)]
+pub enum CopyError {
+  Cancelled,
+  /// The path is the source or destination the error happened on.
+  Io(PathBuf, io::Error),
+}
+
+/// Shared flag that stops a running copy job.
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// Copies files chunk by chunk, reporting progress and stopping when its
+/// [`CancelToken`] is cancelled. The default job reports nothing and can't be
+/// cancelled.
+#[derive(Default)]
+pub struct CopyJob {
+  id: String,
+  cancel: CancelToken,
+  on_progress: Option<Box<dyn Fn(CopyProgress) + Send>>,
+  copied_bytes: Cell<u64>,
+  total_bytes: Cell<u64>,
+}
+
+impl CopyJob {
+  pub fn new(
+    id: impl Into<String>,
+    cancel: CancelToken,
+    on_progress: impl Fn(CopyProgress) + Send + 'static,
+  ) -> Self {
+    CopyJob {
+      id: id.into(),
+      cancel,
+      on_progress: Some(Box::new(on_progress)),
+      ..Default::default()
+    }
+  }
+
+  /// Copies every `(source, destination)` pair. When a copy fails or the job
+  /// is cancelled, the files this call has written are removed again.
+  pub fn copy_files(&self, files: &[(PathBuf, PathBuf)]) -> Result<(), CopyError> {
+    let total_bytes = files
+      .iter()
+      .map(|(source, _)| {
+        std::fs::metadata(source)
+          .map(|metadata| metadata.len())
+          .map_err(|e| CopyError::Io(source.clone(), e))
+      })
+      .sum::<Result<u64, CopyError>>()?;
+
+    self.add_total_bytes(total_bytes);
+
+    let mut created = vec![];
+
+    for (source, destination) in files {
+      if let Err(e) = self.copy_file(source, destination, &mut created) {
+        // The failed file is removed too if it was created, it may be
+        // partially written.
+        for destination in created {
+          let _ = std::fs::remove_file(destination);
+        }
+
+        return Err(e);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Counts `bytes` towards the total, for data written with
+  /// [`CopyJob::write_file`].
+  pub fn add_total_bytes(&self, bytes: u64) {
+    self.total_bytes.set(self.total_bytes.get() + bytes);
+
+    self.report_progress();
+  }
+
+  /// Writes everything `source` yields to `destination` the way files are
+  /// copied, for data that isn't a file, like archive entries. A partially
+  /// written destination is left for the caller to remove. `source` has no
+  /// path, errors reading it are reported on `destination`.
+  pub fn write_file(&self, source: &mut dyn Read, destination: &Path) -> Result<(), CopyError> {
+    let mut destination_file = self.create_destination(destination)?;
+
+    self.write_chunks((source, destination), (&mut destination_file, destination))
+  }
+
+  /// Copies `source` to `destination`, adding `destination` to `created` once
+  /// it has been created.
+  fn copy_file<'a>(
+    &self,
+    source: &Path,
+    destination: &'a Path,
+    created: &mut Vec<&'a Path>,
+  ) -> Result<(), CopyError> {
+    let mut source_file = File::open(source).map_err(|e| CopyError::Io(source.to_path_buf(), e))?;
+    let mut destination_file = self.create_destination(destination)?;
+
+    created.push(destination);
+
+    self.write_chunks(
+      (&mut source_file, source),
+      (&mut destination_file, destination),
+    )?;
+
+    if let Ok(permissions) = source_file
+      .metadata()
+      .map(|metadata| metadata.permissions())
+    {
+      let _ = destination_file.set_permissions(permissions);
+    }
+
+    Ok(())
+  }
+
+  fn create_destination(&self, destination: &Path) -> Result<File, CopyError> {
+    if self.cancel.is_cancelled() {
+      return Err(CopyError::Cancelled);
+    }
+
+    File::create(destination).map_err(|e| CopyError::Io(destination.to_path_buf(), e))
+  }
+
+  fn write_chunks(
+    &self,
+    (source, source_path): (&mut dyn Read, &Path),
+    (destination_file, destination): (&mut File, &Path),
+  ) -> Result<(), CopyError> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+
+    loop {
+      let read = match source.read(&mut buffer) {
+        Ok(0) => break,
+        Ok(read) => read,
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+        Err(e) => return Err(CopyError::Io(source_path.to_path_buf(), e)),
+      };
+
+      destination_file
+        .write_all(&buffer[..read])
+        .map_err(|e| CopyError::Io(destination.to_path_buf(), e))?;
+
+      self.copied_bytes.set(self.copied_bytes.get() + read as u64);
+
+      self.report_progress();
+
+      if self.cancel.is_cancelled() {
+        return Err(CopyError::Cancelled);
+      }
+    }
+
+    Ok(())
+  }
+
+  fn report_progress(&self) {
+    if let Some(on_progress) = &self.on_progress {
+      on_progress(CopyProgress {
+        job_id: self.id.clone(),
+        copied_bytes: self.copied_bytes.get(),
+        total_bytes: self.total_bytes.get(),
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use super::*;
+
+  #[test]
+  fn copies_and_cleans_up_when_cancelled() {
+    let folder =
+      std::env::temp_dir().join(format!("citadel-cm-copy-job-test-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    let content = vec![7u8; CHUNK_SIZE * 2 + 10];
+
+    std::fs::write(folder.join("a.vpk"), &content).unwrap();
+    std::fs::write(folder.join("b.vpk"), &content).unwrap();
+
+    let files = vec![
+      (folder.join("a.vpk"), folder.join("a_copy.vpk")),
+      (folder.join("b.vpk"), folder.join("b_copy.vpk")),
+    ];
+
+    let reports = Arc::new(Mutex::new(vec![]));
+
+    let job = CopyJob::new("job", CancelToken::default(), {
+      let reports = reports.clone();
+
+      move |progress| reports.lock().unwrap().push(progress)
+    });
+
+    job.copy_files(&files).unwrap();
+
+    assert_eq!(std::fs::read(folder.join("b_copy.vpk")).unwrap(), content);
+    assert_eq!(
+      reports.lock().unwrap().last(),
+      Some(&CopyProgress {
+        job_id: "job".to_string(),
+        copied_bytes: content.len() as u64 * 2,
+        total_bytes: content.len() as u64 * 2,
+      })
+    );
+
+    std::fs::remove_file(folder.join("a_copy.vpk")).unwrap();
+    std::fs::remove_file(folder.join("b_copy.vpk")).unwrap();
+
+    // Cancels as soon as progress is reported.
+    let cancel = CancelToken::default();
+
+    let job = CopyJob::new("job", cancel.clone(), move |_| cancel.cancel());
+
+    assert!(matches!(job.copy_files(&files), Err(CopyError::Cancelled)));
+    assert!(!folder.join("a_copy.vpk").exists());
+    assert!(!folder.join("b_copy.vpk").exists());
+
+    // Cancelled before the first destination is created, the file already
+    // there is kept.
+    std::fs::write(folder.join("a_copy.vpk"), b"kept").unwrap();
+
+    let cancel = CancelToken::default();
+
+    cancel.cancel();
+
+    let job = CopyJob::new("job", cancel, |_| {});
+
+    assert!(matches!(job.copy_files(&files), Err(CopyError::Cancelled)));
+    assert_eq!(std::fs::read(folder.join("a_copy.vpk")).unwrap(), b"kept");
+
+    std::fs::remove_dir_all(folder).unwrap();
+  }
+}
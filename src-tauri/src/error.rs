@@ -0,0 +1,107 @@
+use std::{collections::BTreeMap, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Error as sent to the frontend and printed by the command line. `code` is
+/// stable and names the error variant, `params` holds the values the message
+/// is built from, so the frontend can branch on the code or write its own
+/// message. Deserializable so payloads can be kept in the config.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ErrorPayload {
+  pub code: String,
+  pub params: BTreeMap<String, Value>,
+  /// English message.
+  pub message: String,
+}
+
+impl ErrorPayload {
+  pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+    ErrorPayload {
+      code: code.to_string(),
+      params: BTreeMap::new(),
+      message: message.into(),
+    }
+  }
+
+  pub fn param(mut self, key: &'static str, value: impl Serialize) -> Self {
+    self.params.insert(key.to_string(), json!(value));
+    self
+  }
+
+  /// Adds `ioKind`, the raw `osError` when there is one, and `detail`.
+  pub fn io(self, e: &io::Error) -> Self {
+    let payload = self
+      .param("ioKind", format!("{:?}", e.kind()))
+      .param("detail", e.to_string());
+
+    match e.raw_os_error() {
+      Some(os_error) => payload.param("osError", os_error),
+      None => payload,
+    }
+  }
+
+  /// Same as [`ErrorPayload::new`] with the error appended to the message and
+  /// as `detail`.
+  pub fn with_detail(code: &'static str, message: &str, detail: impl ToString) -> Self {
+    let detail = detail.to_string();
+
+    ErrorPayload::new(code, format!("{}: {}", message, detail)).param("detail", detail)
+  }
+
+  /// Same as [`ErrorPayload::with_detail`] for an io error, see
+  /// [`ErrorPayload::io`].
+  pub fn with_io(code: &'static str, message: &str, e: &io::Error) -> Self {
+    ErrorPayload::new(code, format!("{}: {}", message, e)).io(e)
+  }
+
+  /// Same as [`ErrorPayload::with_io`] for an error on `path`, which is added
+  /// to the message and as `path`.
+  pub fn with_path_io(code: &'static str, message: &str, path: &Path, e: &io::Error) -> Self {
+    ErrorPayload::new(code, format!("{} ({}): {}", message, path.display(), e))
+      .io(e)
+      .param("path", path)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn serializes_code_params_and_message() {
+    let e = io::Error::from_raw_os_error(2);
+
+    let payload = ErrorPayload::with_io(
+      "CouldNotReadAddonFolder",
+      "Could not read addons folder",
+      &e,
+    )
+    .param("addon", "hud.vpk");
+
+    let value = json!(payload);
+
+    assert_eq!(value["code"], "CouldNotReadAddonFolder");
+    assert_eq!(value["params"]["addon"], "hud.vpk");
+    assert_eq!(value["params"]["ioKind"], "NotFound");
+    assert_eq!(value["params"]["osError"], 2);
+    assert_eq!(
+      value["message"],
+      format!("Could not read addons folder: {}", e)
+    );
+
+    let path = Path::new("addons/pak01_dir.vpk");
+    let value = json!(ErrorPayload::with_path_io(
+      "CouldNotWriteAddonFolder",
+      "Could not write addons folder",
+      path,
+      &e,
+    ));
+
+    assert_eq!(value["params"]["path"], json!(path));
+    assert_eq!(
+      value["message"],
+      format!("Could not write addons folder ({}): {}", path.display(), e)
+    );
+  }
+}
@@ -1,52 +1,227 @@
-use serde::{ser, Deserialize, Serialize};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{de::DeserializeOwned, ser, Deserialize, Serialize};
 use serde_json::json;
-use std::os::windows::fs::FileTypeExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{io, path::Path};
 use steamlocate::SteamDir;
-use tauri::{AppHandle, State};
-use tauri_plugin_store::StoreExt;
 
-use crate::AppState;
+use crate::app_manifest::{AppManifest, AppManifestError};
+use crate::archive::{self, ArchiveAddon, ArchiveError};
+use crate::backups::{self, DiffLine, GameInfoBackup};
+use crate::categories::{self, AddonClassification, AddonFilter};
+use crate::config::ConfigStore;
+use crate::conflicts::{self, AddonConflict, AddonFiles};
+use crate::copy_job::{CopyError, CopyJob};
+use crate::error::ErrorPayload;
+use crate::gameinfo::{self, PatchedEntries, SearchPathsReport, SearchPathsState};
+use crate::hash_index::HashIndex;
+use crate::journal::{self, Operation, Transaction};
+use crate::load_order::{self, MountedAddon};
+use crate::migration::{
+  self, FailedAddon, MigratedAddon, MigrationConflict, MigrationConflictPolicy, MigrationOptions,
+  MigrationOutcome, MigrationPlan, MigrationReport,
+};
+use crate::profiles::{self, Profile};
+use crate::storage::AddonStorage;
+use crate::thumbnails;
+use crate::vpk::VpkError;
+use crate::{keyvalues, platform, vpk};
 
 static GAME_ID: u32 = 1422450;
+/// Folder Steam installs the game to inside a library's `steamapps/common`.
+const GAME_FOLDER_NAME: &str = "Deadlock";
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// Numbers the temporary folders of archive imports within this process.
+static IMPORT_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+const LAST_PATCHED_BUILD_ID_KEY: &str = "last_patched_build_id";
+const GAME_UPDATE_STATUS_KEY: &str = "game_update_status";
+/// Search path entries inserted by patching, removed again on reset.
+const PATCHED_SEARCH_PATHS_KEY: &str = "patched_search_paths";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum DeployMethod {
   Copy,
   Symlink,
+  /// Same volume only, doesn't need the privileges symlinks need on Windows.
+  Hardlink,
+  /// Copy-on-write clone, needs a filesystem with block cloning.
+  Reflink,
+}
+
+impl DeployMethod {
+  /// Function creating a deployed file linked to the stored one, `None` for
+  /// plain copies.
+  fn link_file(self) -> Option<fn(&Path, &Path) -> io::Result<()>> {
+    match self {
+      DeployMethod::Copy => None,
+      DeployMethod::Symlink => Some(platform::symlink_file),
+      DeployMethod::Hardlink => Some(platform::hard_link_file),
+      DeployMethod::Reflink => Some(platform::reflink_file),
+    }
+  }
+}
+
+/// Deploy methods that work between the addon storage and the game folder.
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeployCapabilities {
+  pub symlink: bool,
+  pub hardlink: bool,
+  pub reflink: bool,
+}
+
+impl DeployCapabilities {
+  pub fn supports(&self, deploy_method: DeployMethod) -> bool {
+    match deploy_method {
+      DeployMethod::Copy => true,
+      DeployMethod::Symlink => self.symlink,
+      DeployMethod::Hardlink => self.hardlink,
+      DeployMethod::Reflink => self.reflink,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum ConflictPolicy {
+  Ignore,
+  Warn,
+  Refuse,
 }
 
 #[derive(Debug)]
 pub enum SearchPathsError {
-  CouldNotReadGameInfo(io::Error),
-  CouldNotWriteGameInfo(io::Error),
-  DeserializationError(Box<keyvalues_serde::error::Error>),
-  SerializationError(Box<keyvalues_serde::error::Error>),
+  CouldNotReadGameInfo(PathBuf, io::Error),
+  CouldNotWriteGameInfo(PathBuf, io::Error),
+  CouldNotParseGameInfo(keyvalues::KeyValuesError),
+  NoSearchPathsBlock,
+  CouldNotBackupGameInfo(PathBuf, io::Error),
+  CouldNotReadBackup(PathBuf, io::Error),
   NoGamePath,
 }
 
-#[derive(Debug, Serialize)]
-pub enum SearchPathsState {
-  Vanilla,
-  Modded,
-  Custom,
+#[derive(Debug)]
+pub enum GameUpdateError {
+  NoGamePath,
+  CouldNotReadAppManifest(AppManifestError),
+  SearchPaths(SearchPathsError),
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+  ProfileNotFound,
+  ProfileAlreadyExists,
+  InvalidProfileName,
+  Addon(AddonError),
 }
 
 #[derive(Debug)]
 pub enum AddonError {
   NoInstallPath,
   NoGamePath,
-  CouldNotCreateAddonFolder(io::Error),
-  CouldNotReadInstallFolder(io::Error),
-  CouldNotReadAddonFolder(io::Error),
-  CouldNotWriteAddonFolder(io::Error),
-  CouldNotWriteInstallFolder(io::Error),
+  CouldNotCreateAddonFolder(PathBuf, io::Error),
+  CouldNotReadInstallFolder(PathBuf, io::Error),
+  CouldNotReadAddonFolder(PathBuf, io::Error),
+  CouldNotWriteAddonFolder(PathBuf, io::Error),
+  CouldNotWriteInstallFolder(PathBuf, io::Error),
   InvalidAddonFile,
-  AddonIsNotInstalled,
-  AddonAlreadyMounted,
-  AddonIsNotMounted,
-  CannotDeleteMountedAddon,
+  AddonIsNotInstalled(String),
+  AddonAlreadyMounted(String),
+  AddonIsNotMounted(String),
+  CannotDeleteMountedAddon(String),
+  CouldNotReadAddon(vpk::VpkError),
+  AddonWouldConflict(Vec<AddonConflict>),
+  NoFreeLoadSlot,
+  InvalidLoadOrder,
+  CouldNotReadArchive(ArchiveError),
+  CouldNotAccessJournal(PathBuf, io::Error),
+  OperationInProgress,
+  DeployMethodUnavailable,
+  /// A hard link or reflink chosen as deploy method could not be created.
+  CouldNotLinkAddon(PathBuf, io::Error),
+  Cancelled,
+  /// The thread running the operation panicked.
+  OperationFailed(String),
+}
+
+/// Errors of changing the game or storage path.
+#[derive(Debug)]
+pub enum SettingsError {
+  NoGamePath,
+  InvalidGamePath(PathBuf),
+  StorageInsideGamePath {
+    storage_path: PathBuf,
+    game_path: PathBuf,
+  },
+  StoragePathNotFound(PathBuf),
+  NotEnoughSpace {
+    needed: u64,
+    available: Option<u64>,
+  },
+  Migration(AddonError),
+  CouldNotSaveSettings(String),
+}
+
+impl From<AddonError> for ProfileError {
+  fn from(e: AddonError) -> Self {
+    ProfileError::Addon(e)
+  }
+}
+
+impl From<AddonError> for SettingsError {
+  fn from(e: AddonError) -> Self {
+    SettingsError::Migration(e)
+  }
+}
+
+impl AddonError {
+  /// Maps a failed copy to [`AddonError::Cancelled`] or to `io_error`.
+  pub fn from_copy_error(error: CopyError, io_error: fn(PathBuf, io::Error) -> AddonError) -> Self {
+    match error {
+      CopyError::Cancelled => AddonError::Cancelled,
+      CopyError::Io(path, e) => io_error(path, e),
+    }
+  }
+}
+
+impl SearchPathsError {
+  pub fn payload(&self) -> ErrorPayload {
+    match self {
+      SearchPathsError::CouldNotReadGameInfo(path, e) => ErrorPayload::with_path_io(
+        "CouldNotReadGameInfo",
+        "Could not read gameinfo.gi",
+        path,
+        e,
+      ),
+      SearchPathsError::CouldNotWriteGameInfo(path, e) => ErrorPayload::with_path_io(
+        "CouldNotWriteGameInfo",
+        "Could not write gameinfo.gi",
+        path,
+        e,
+      ),
+      SearchPathsError::CouldNotParseGameInfo(e) => {
+        ErrorPayload::with_detail("CouldNotParseGameInfo", "Could not parse gameinfo.gi", e)
+      }
+      SearchPathsError::NoSearchPathsBlock => {
+        ErrorPayload::new("NoSearchPathsBlock", "gameinfo.gi has no SearchPaths block")
+      }
+      SearchPathsError::CouldNotBackupGameInfo(path, e) => ErrorPayload::with_path_io(
+        "CouldNotBackupGameInfo",
+        "Could not back up gameinfo.gi",
+        path,
+        e,
+      ),
+      SearchPathsError::CouldNotReadBackup(path, e) => ErrorPayload::with_path_io(
+        "CouldNotReadBackup",
+        "Could not read gameinfo.gi backup",
+        path,
+        e,
+      ),
+      SearchPathsError::NoGamePath => ErrorPayload::new("NoGamePath", "Game path not found"),
+    }
+  }
 }
 
 impl Serialize for SearchPathsError {
@@ -54,633 +229,3124 @@ impl Serialize for SearchPathsError {
   where
     S: ser::Serializer,
   {
+    self.payload().serialize(serializer)
+  }
+}
+
+impl GameUpdateError {
+  pub fn payload(&self) -> ErrorPayload {
     match self {
-      SearchPathsError::CouldNotReadGameInfo(e) => {
-        serializer.serialize_str(&format!("Could not read gameinfo.gi: {}", e))
-      }
-      SearchPathsError::CouldNotWriteGameInfo(e) => {
-        serializer.serialize_str(&format!("Could not write gameinfo.gi: {}", e))
-      }
-      SearchPathsError::DeserializationError(e) => {
-        serializer.serialize_str(&format!("Deserialization error: {}", e))
-      }
-      SearchPathsError::SerializationError(e) => {
-        serializer.serialize_str(&format!("Serialization error: {}", e))
+      GameUpdateError::NoGamePath => ErrorPayload::new("NoGamePath", "Game path not found"),
+      GameUpdateError::CouldNotReadAppManifest(AppManifestError::Io(e)) => ErrorPayload::with_io(
+        "CouldNotReadAppManifest",
+        "Could not read Steam app manifest",
+        e,
+      ),
+      GameUpdateError::CouldNotReadAppManifest(e) => ErrorPayload::with_detail(
+        "CouldNotReadAppManifest",
+        "Could not read Steam app manifest",
+        e,
+      ),
+      GameUpdateError::SearchPaths(e) => e.payload(),
+    }
+  }
+}
+
+impl Serialize for GameUpdateError {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: ser::Serializer,
+  {
+    self.payload().serialize(serializer)
+  }
+}
+
+impl ProfileError {
+  pub fn payload(&self) -> ErrorPayload {
+    match self {
+      ProfileError::ProfileNotFound => ErrorPayload::new("ProfileNotFound", "Profile not found"),
+      ProfileError::ProfileAlreadyExists => ErrorPayload::new(
+        "ProfileAlreadyExists",
+        "A profile with this name already exists",
+      ),
+      ProfileError::InvalidProfileName => {
+        ErrorPayload::new("InvalidProfileName", "Invalid profile name")
       }
-      SearchPathsError::NoGamePath => serializer.serialize_str("Game path not found"),
+      ProfileError::Addon(e) => e.payload(),
     }
   }
 }
 
-impl Serialize for AddonError {
+impl Serialize for ProfileError {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
   where
     S: ser::Serializer,
   {
+    self.payload().serialize(serializer)
+  }
+}
+
+impl AddonError {
+  pub fn payload(&self) -> ErrorPayload {
     match self {
-      AddonError::CouldNotCreateAddonFolder(e) => {
-        serializer.serialize_str(&format!("Could not create addons folder: {}", e))
+      AddonError::CouldNotCreateAddonFolder(path, e) => ErrorPayload::with_path_io(
+        "CouldNotCreateAddonFolder",
+        "Could not create addons folder",
+        path,
+        e,
+      ),
+      AddonError::NoInstallPath => ErrorPayload::new("NoInstallPath", "Storage path not found"),
+      AddonError::NoGamePath => ErrorPayload::new("NoGamePath", "Game path not found"),
+      AddonError::CouldNotReadInstallFolder(path, e) => ErrorPayload::with_path_io(
+        "CouldNotReadInstallFolder",
+        "Could not read storage path",
+        path,
+        e,
+      ),
+      AddonError::CouldNotReadAddonFolder(path, e) => ErrorPayload::with_path_io(
+        "CouldNotReadAddonFolder",
+        "Could not read addons folder",
+        path,
+        e,
+      ),
+      AddonError::CouldNotWriteAddonFolder(path, e) => ErrorPayload::with_path_io(
+        "CouldNotWriteAddonFolder",
+        "Could not write addons folder",
+        path,
+        e,
+      ),
+      AddonError::CouldNotWriteInstallFolder(path, e) => ErrorPayload::with_path_io(
+        "CouldNotWriteInstallFolder",
+        "Could not write storage folder",
+        path,
+        e,
+      ),
+      AddonError::InvalidAddonFile => ErrorPayload::new("InvalidAddonFile", "Invalid addon file"),
+      AddonError::AddonIsNotInstalled(addon) => {
+        ErrorPayload::new("AddonIsNotInstalled", "Addon is not stored").param("addon", addon)
       }
-      AddonError::NoInstallPath => serializer.serialize_str("Storage path not found"),
-      AddonError::NoGamePath => serializer.serialize_str("Game path not found"),
-      AddonError::CouldNotReadInstallFolder(e) => {
-        serializer.serialize_str(&format!("Could not read storage path: {}", e))
+      AddonError::AddonAlreadyMounted(addon) => {
+        ErrorPayload::new("AddonAlreadyMounted", "Addon is already installed").param("addon", addon)
       }
-      AddonError::CouldNotReadAddonFolder(e) => {
-        serializer.serialize_str(&format!("Could not read addons folder: {}", e))
+      AddonError::AddonIsNotMounted(addon) => {
+        ErrorPayload::new("AddonIsNotMounted", "Addon is not installed").param("addon", addon)
       }
-      AddonError::CouldNotWriteAddonFolder(e) => {
-        serializer.serialize_str(&format!("Could not write addons folder: {}", e))
+      AddonError::CannotDeleteMountedAddon(addon) => {
+        ErrorPayload::new("CannotDeleteMountedAddon", "Cannot delete installed addon")
+          .param("addon", addon)
       }
-      AddonError::CouldNotWriteInstallFolder(e) => {
-        serializer.serialize_str(&format!("Could not write storage folder: {}", e))
+      AddonError::CouldNotReadAddon(VpkError::Io(e)) => {
+        ErrorPayload::with_io("CouldNotReadAddon", "Could not read addon file", e)
       }
-      AddonError::InvalidAddonFile => serializer.serialize_str("Invalid addon file"),
-      AddonError::AddonIsNotInstalled => serializer.serialize_str("Addon is not stored"),
-      AddonError::AddonAlreadyMounted => serializer.serialize_str("Addon is already installed"),
-      AddonError::AddonIsNotMounted => serializer.serialize_str("Addon is not installed"),
-      AddonError::CannotDeleteMountedAddon => {
-        serializer.serialize_str("Cannot delete installed addon")
+      AddonError::CouldNotReadAddon(e) => {
+        ErrorPayload::with_detail("CouldNotReadAddon", "Could not read addon file", e)
+      }
+      AddonError::AddonWouldConflict(conflicts) => ErrorPayload::new(
+        "AddonWouldConflict",
+        format!(
+          "Addon conflicts with installed addons on {} files",
+          conflicts.len()
+        ),
+      )
+      .param("count", conflicts.len())
+      .param(
+        "paths",
+        conflicts
+          .iter()
+          .map(|conflict| &conflict.path)
+          .collect::<Vec<_>>(),
+      )
+      .param(
+        "addons",
+        conflicts
+          .iter()
+          .flat_map(|conflict| &conflict.addons)
+          .collect::<BTreeSet<_>>(),
+      ),
+      AddonError::NoFreeLoadSlot => {
+        ErrorPayload::new("NoFreeLoadSlot", "No free addon load slot left")
+      }
+      AddonError::InvalidLoadOrder => ErrorPayload::new(
+        "InvalidLoadOrder",
+        "Load order does not match installed addons",
+      ),
+      AddonError::CouldNotReadArchive(ArchiveError::Io(e)) => {
+        ErrorPayload::with_io("CouldNotReadArchive", "Could not read archive", e)
+      }
+      AddonError::CouldNotReadArchive(e) => {
+        ErrorPayload::with_detail("CouldNotReadArchive", "Could not read archive", e)
+      }
+      AddonError::CouldNotAccessJournal(path, e) => ErrorPayload::with_path_io(
+        "CouldNotAccessJournal",
+        "Could not access operation journal",
+        path,
+        e,
+      ),
+      AddonError::OperationInProgress => {
+        ErrorPayload::new("OperationInProgress", "Another operation is in progress")
+      }
+      AddonError::DeployMethodUnavailable => ErrorPayload::new(
+        "DeployMethodUnavailable",
+        "Deploy method is not supported between the storage and game folders",
+      ),
+      AddonError::CouldNotLinkAddon(path, e) => ErrorPayload::with_path_io(
+        "CouldNotLinkAddon",
+        "Could not link addon into the addons folder",
+        path,
+        e,
+      ),
+      AddonError::Cancelled => ErrorPayload::new("Cancelled", "Operation was cancelled"),
+      AddonError::OperationFailed(detail) => {
+        ErrorPayload::with_detail("OperationFailed", "Operation stopped unexpectedly", detail)
       }
     }
   }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct SearchPaths {
-  #[serde(rename = "Game")]
-  game: Vec<String>,
-  #[serde(rename = "Mod")]
-  mod_key: Option<String>,
-  #[serde(rename = "Write")]
-  write: Option<String>,
+impl Serialize for AddonError {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: ser::Serializer,
+  {
+    self.payload().serialize(serializer)
+  }
+}
+
+impl SettingsError {
+  pub fn payload(&self) -> ErrorPayload {
+    match self {
+      SettingsError::NoGamePath => ErrorPayload::new("NoGamePath", "Game path not found"),
+      SettingsError::InvalidGamePath(path) => ErrorPayload::new(
+        "InvalidGamePath",
+        "Game path does not contain game/citadel/gameinfo.gi.",
+      )
+      .param("path", path),
+      SettingsError::StorageInsideGamePath {
+        storage_path,
+        game_path,
+      } => ErrorPayload::new(
+        "StorageInsideGamePath",
+        "Addon storage path cannot be inside the game path.",
+      )
+      .param("storagePath", storage_path)
+      .param("gamePath", game_path),
+      SettingsError::StoragePathNotFound(path) => {
+        ErrorPayload::new("StoragePathNotFound", "Addon storage path does not exist.")
+          .param("path", path)
+      }
+      SettingsError::NotEnoughSpace { needed, available } => ErrorPayload::new(
+        "NotEnoughSpace",
+        format!(
+          "Not enough free space in the new addon storage path, {} MB needed.",
+          (needed + 1024 * 1024 - 1) / (1024 * 1024)
+        ),
+      )
+      .param("neededBytes", needed)
+      .param("availableBytes", available),
+      SettingsError::Migration(e) => e.payload(),
+      SettingsError::CouldNotSaveSettings(e) => {
+        ErrorPayload::with_detail("CouldNotSaveSettings", "Could not save settings", e)
+      }
+    }
+  }
+}
+
+impl Serialize for SettingsError {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: ser::Serializer,
+  {
+    self.payload().serialize(serializer)
+  }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InstallAddonInfo {
   #[serde(rename = "filePath")]
-  file_path: String,
+  pub file_path: String,
   #[serde(rename = "fileName")]
-  file_name: Option<String>,
+  pub file_name: Option<String>,
   #[serde(rename = "displayName")]
-  display_name: Option<String>,
+  pub display_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum AddonLocation {
+  Stored,
+  Mounted,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddonFileEntry {
+  path: String,
+  size: u64,
+  crc: u32,
+  #[serde(rename = "archiveIndex")]
+  archive_index: u16,
+  #[serde(rename = "preloadBytes")]
+  preload_bytes: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddonContents {
+  version: u32,
+  files: Vec<AddonFileEntry>,
+  #[serde(rename = "fileCount")]
+  file_count: usize,
+  #[serde(rename = "totalSize")]
+  total_size: u64,
 }
 
-fn read_search_paths(path: &Path) -> Result<SearchPaths, SearchPathsError> {
-  let game_info_path = path.join("game/citadel/gameinfo.gi");
+#[derive(Debug, Serialize, Clone)]
+pub struct DeployedAddon {
+  /// Stored addon file name for managed addons, deployed file name otherwise.
+  #[serde(rename = "fileName")]
+  file_name: String,
+  #[serde(rename = "deployedFileName")]
+  deployed_file_name: String,
+  slot: Option<u32>,
+  managed: bool,
+  /// Target of a symlinked addon.
+  #[serde(rename = "linkTarget")]
+  link_target: Option<PathBuf>,
+  /// Symlink whose target is missing, for example after the storage was moved.
+  broken: bool,
+}
 
-  let content =
-    std::fs::read_to_string(game_info_path).map_err(SearchPathsError::CouldNotReadGameInfo)?;
+impl DeployedAddon {
+  pub fn file_name(&self) -> &str {
+    &self.file_name
+  }
+}
 
-  let lines = content.lines().collect::<Vec<_>>();
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum LinkRepairOutcome {
+  Relinked,
+  /// The symlink could not be created, the stored file was copied instead.
+  Copied,
+  /// The storage has no file with the link target's name, the link was left.
+  Missing,
+}
 
-  let mut search_paths = vec![];
+/// A dangling symlink in the addons folder and what was done about it.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct LinkRepair {
+  #[serde(rename = "deployedFileName")]
+  pub deployed_file_name: String,
+  /// Where the link pointed before the repair.
+  #[serde(rename = "linkTarget")]
+  pub link_target: PathBuf,
+  pub outcome: LinkRepairOutcome,
+}
 
-  for i in 0..lines.len() {
-    let line = lines[i];
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AdoptOutcome {
+  /// Copied into the storage.
+  Stored,
+  /// The storage already had the same addon, which now counts as installed.
+  Matched,
+  /// Not named `pakNN_dir.vpk`, the game doesn't load it.
+  NotLoadable,
+  /// Symlink whose target is missing.
+  Broken,
+  Failed,
+}
 
-    if line.contains("SearchPaths") {
-      search_paths.push(i);
+#[derive(Debug, Serialize, Clone)]
+pub struct AdoptedAddon {
+  #[serde(rename = "deployedFileName")]
+  pub deployed_file_name: String,
+  /// Stored file name of the adopted addon.
+  #[serde(rename = "fileName")]
+  pub file_name: Option<String>,
+  pub outcome: AdoptOutcome,
+  /// The deployed copy was replaced using the current deploy method.
+  pub redeployed: bool,
+  pub error: Option<ErrorPayload>,
+}
 
-      let mut j = i + 1;
-      loop {
-        search_paths.push(j);
+#[derive(Debug, Serialize, Clone)]
+pub struct AddonThumbnail {
+  /// Cached PNG in the addon storage.
+  pub path: PathBuf,
+  /// The same PNG as a `data:` URL, ready for an `<img>`.
+  #[serde(rename = "dataUrl")]
+  pub data_url: String,
+}
 
-        if lines[j].contains("}") {
-          break;
-        }
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum DriftState {
+  InSync,
+  /// One copy changed since the addon was last in sync.
+  Stale,
+  /// The copies differ and it's unknown which one changed, or both did.
+  Diverged,
+  /// The stored addon is gone, only the deployed copy is left.
+  Orphaned,
+}
 
-        j += 1;
-      }
-    }
-  }
+/// How the deployed copy of a managed addon compares to the stored one.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct AddonDrift {
+  #[serde(rename = "fileName")]
+  pub file_name: String,
+  #[serde(rename = "deployedFileName")]
+  pub deployed_file_name: String,
+  pub state: DriftState,
+  /// Copy that changed, for stale addons.
+  pub changed: Option<AddonLocation>,
+}
 
-  let search_paths = search_paths
-    .iter()
-    .map(|&i| lines[i])
-    .collect::<Vec<_>>()
-    .join("\n");
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum GameUpdateOutcome {
+  /// gameinfo.gi was left alone by the update.
+  StillPatched,
+  Repatched,
+  RepatchFailed,
+  /// Automatic re-patching is disabled, search paths have to be modded again.
+  PatchRequired,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameUpdateStatus {
+  #[serde(rename = "buildId")]
+  build_id: String,
+  #[serde(rename = "previousBuildId")]
+  previous_build_id: String,
+  #[serde(rename = "searchPathsState")]
+  search_paths_state: SearchPathsState,
+  outcome: GameUpdateOutcome,
+  error: Option<ErrorPayload>,
+  /// Milliseconds since the Unix epoch.
+  #[serde(rename = "detectedAt")]
+  detected_at: u64,
+}
 
-  let search_paths = keyvalues_serde::from_str::<SearchPaths>(&search_paths)
-    .map_err(|e| SearchPathsError::DeserializationError(Box::new(e)))?;
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileAction {
+  Mount,
+  Unmount,
+}
 
-  Ok(search_paths)
+#[derive(Debug, Serialize)]
+pub struct ProfileAddonResult {
+  addon: String,
+  action: ProfileAction,
+  error: Option<ErrorPayload>,
+  conflicts: Vec<AddonConflict>,
 }
 
-fn write_search_paths(path: &Path, search_paths: &SearchPaths) -> Result<(), SearchPathsError> {
-  let game_info_path = path.join("game/citadel/gameinfo.gi");
+#[derive(Debug, Serialize)]
+pub struct ProfileApplyReport {
+  results: Vec<ProfileAddonResult>,
+}
 
-  let content =
-    std::fs::read_to_string(&game_info_path).map_err(SearchPathsError::CouldNotReadGameInfo)?;
+#[derive(Debug, Serialize)]
+pub struct ConflictReport {
+  conflicts: Vec<AddonConflict>,
+  unreadable: Vec<String>,
+}
 
-  let lines = content.lines().collect::<Vec<_>>();
+/// Message of an error serialized for the frontend.
+pub fn error_message(e: &impl Serialize) -> String {
+  match json!(e) {
+    serde_json::Value::String(message) => message,
+    payload => payload["message"].as_str().unwrap_or_default().to_string(),
+  }
+}
 
-  let mut search_paths_lines = vec![];
+/// `file_name`, or the next free name when the storage already has a file,
+/// link or chunk with that name.
+fn free_stored_file_name(storage: &AddonStorage, file_name: &str) -> String {
+  if storage.addon_path(file_name).symlink_metadata().is_err()
+    && storage.chunk_files(file_name).is_empty()
+  {
+    file_name.to_string()
+  } else {
+    migration::free_file_name(&storage.path, file_name)
+  }
+}
 
-  let mut tab_level = 0;
+/// Whether both paths lead to the same file, for example through a symlink.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+  match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => false,
+  }
+}
 
-  for i in 0..lines.len() {
-    let line = lines[i];
+pub fn find_game_path() -> Option<PathBuf> {
+  find_game_installs().into_iter().next()
+}
 
-    if line.contains("SearchPaths") {
-      search_paths_lines.push(i);
+/// Deadlock installs found in every Steam library, the one Steam has
+/// registered first. Folders without gameinfo.gi are left out.
+pub fn find_game_installs() -> Vec<PathBuf> {
+  let Some(mut steam_dir) = SteamDir::locate() else {
+    return vec![];
+  };
 
-      tab_level = line.chars().take_while(|&c| c == '\t').count();
+  let registered_path = steam_dir.app(&GAME_ID).map(|app| app.path.clone());
 
-      let mut j = i + 1;
-      loop {
-        search_paths_lines.push(j);
+  let library_paths = steam_dir
+    .libraryfolders()
+    .paths
+    .iter()
+    .map(|steamapps_path| steamapps_path.join("common").join(GAME_FOLDER_NAME));
 
-        if lines[j].contains("}") {
-          break;
-        }
+  let mut game_paths: Vec<PathBuf> = vec![];
 
-        j += 1;
-      }
+  for game_path in registered_path.into_iter().chain(library_paths) {
+    if GameInstall::new(&game_path).is_valid() && !game_paths.contains(&game_path) {
+      game_paths.push(game_path);
     }
   }
 
-  let new_search_paths = keyvalues_serde::to_string(search_paths)
-    .map_err(|e| SearchPathsError::SerializationError(Box::new(e)))?
-    .replace('\"', "")
-    .split('\n')
-    .map(|line| {
-      let tabs = "\t".repeat(tab_level);
-      format!("{}{}", tabs, line)
-    })
-    .collect::<Vec<_>>()
-    .join("\n");
+  game_paths
+}
 
-  let mut new_lines = vec![];
+/// A Deadlock installation, the folder Steam installed the game to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameInstall {
+  pub path: PathBuf,
+}
 
-  for (i, line) in lines.iter().enumerate() {
-    if i == search_paths_lines[0] {
-      new_lines.push(new_search_paths.as_str());
-    } else if !search_paths_lines.contains(&i) {
-      new_lines.push(line);
-    }
+impl GameInstall {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    GameInstall { path: path.into() }
   }
 
-  let new_content = new_lines.join("\n");
+  pub fn locate() -> Option<Self> {
+    find_game_path().map(GameInstall::new)
+  }
 
-  std::fs::write(&game_info_path, new_content).map_err(SearchPathsError::CouldNotWriteGameInfo)?;
+  /// The game path set in the settings, or a detected one when none is set or
+  /// the set one is no longer a game folder.
+  pub fn resolve(config: &dyn ConfigStore) -> Option<Self> {
+    config
+      .get("game_path")
+      .and_then(|value| value.as_str().map(GameInstall::new))
+      .filter(GameInstall::is_valid)
+      .or_else(GameInstall::locate)
+  }
 
-  Ok(())
-}
+  /// Whether the folder looks like a Deadlock install.
+  pub fn is_valid(&self) -> bool {
+    self.game_info_path().is_file()
+  }
 
-pub fn find_game_path() -> Option<PathBuf> {
-  let mut steam_dir = SteamDir::locate()?;
+  pub fn game_info_path(&self) -> PathBuf {
+    self.path.join("game/citadel/gameinfo.gi")
+  }
 
-  let game_dir = steam_dir.app(&GAME_ID)?.to_owned();
+  pub fn addons_path(&self) -> PathBuf {
+    self.path.join("game/citadel/addons")
+  }
 
-  Some(game_dir.path)
-}
+  pub fn create_addons_folder_if_not_exists(&self) -> Result<PathBuf, io::Error> {
+    let addons_path = self.addons_path();
 
-pub fn create_addons_folder_if_not_exists(path: &Path) -> Result<PathBuf, io::Error> {
-  let addons_path = path.join("game/citadel/addons");
+    if !addons_path.exists() {
+      std::fs::create_dir_all(&addons_path)?;
+    }
 
-  if !addons_path.exists() {
-    std::fs::create_dir_all(&addons_path)?;
+    Ok(addons_path)
   }
 
-  Ok(addons_path)
-}
+  pub fn read_game_info(&self) -> Result<keyvalues::Document, SearchPathsError> {
+    let game_info_path = self.game_info_path();
 
-#[tauri::command]
-pub fn get_game_path(state: State<AppState>) -> Option<PathBuf> {
-  state.path.clone()
-}
+    let content = std::fs::read_to_string(&game_info_path)
+      .map_err(|e| SearchPathsError::CouldNotReadGameInfo(game_info_path, e))?;
 
-#[tauri::command]
-pub fn get_search_paths_state(
-  state: State<AppState>,
-) -> Result<SearchPathsState, SearchPathsError> {
-  let path = state.path.as_ref().ok_or(SearchPathsError::NoGamePath)?;
+    keyvalues::Document::parse(&content).map_err(SearchPathsError::CouldNotParseGameInfo)
+  }
 
-  let search_paths = read_search_paths(path)?;
+  pub fn search_paths_report(&self) -> Result<SearchPathsReport, SearchPathsError> {
+    let game_info = self.read_game_info()?;
 
-  match (
-    search_paths.mod_key,
-    search_paths.write,
-    search_paths.game.len(),
-  ) {
-    (None, None, 2) => {
-      if search_paths.game[0] == "citadel" && search_paths.game[1] == "core" {
-        return Ok(SearchPathsState::Vanilla);
-      }
-    }
-    (Some(mod_key), Some(write), 3) => {
-      if search_paths.game[0] == "citadel/addons"
-        && search_paths.game[1] == "citadel"
-        && search_paths.game[2] == "core"
-        && mod_key == "citadel"
-        && write == "citadel"
-      {
-        return Ok(SearchPathsState::Modded);
-      }
+    let search_paths = game_info
+      .root()
+      .find_block(&gameinfo::SEARCH_PATHS_BLOCK)
+      .ok_or(SearchPathsError::NoSearchPathsBlock)?;
+
+    Ok(gameinfo::analyze(search_paths))
+  }
+
+  /// Snapshots the current gameinfo.gi to `backups_path` and atomically
+  /// replaces it with `content`.
+  pub fn replace_game_info(
+    &self,
+    backups_path: &Path,
+    content: &[u8],
+  ) -> Result<(), SearchPathsError> {
+    let game_info_path = self.game_info_path();
+
+    let current_content = std::fs::read(&game_info_path)
+      .map_err(|e| SearchPathsError::CouldNotReadGameInfo(game_info_path.clone(), e))?;
+
+    if current_content == content {
+      return Ok(());
     }
-    _ => {}
-  };
 
-  Ok(SearchPathsState::Custom)
-}
+    backups::snapshot(backups_path, &current_content)
+      .map_err(|e| SearchPathsError::CouldNotBackupGameInfo(backups_path.to_path_buf(), e))?;
 
-#[tauri::command]
-pub fn mod_search_paths(state: State<AppState>) -> Result<(), SearchPathsError> {
-  let path = state.path.as_ref().ok_or(SearchPathsError::NoGamePath)?;
+    backups::write_atomic(&game_info_path, content)
+      .map_err(|e| SearchPathsError::CouldNotWriteGameInfo(game_info_path, e))?;
 
-  let search_paths = SearchPaths {
-    game: vec![
-      "citadel/addons".to_string(),
-      "citadel".to_string(),
-      "core".to_string(),
-    ],
-    mod_key: Some("citadel".to_string()),
-    write: Some("citadel".to_string()),
-  };
+    Ok(())
+  }
 
-  write_search_paths(path, &search_paths)?;
+  /// Applies `edit` to the `SearchPaths` block and writes the file back. The
+  /// rest of the file is left byte-for-byte unchanged.
+  pub fn edit_search_paths(
+    &self,
+    backups_path: &Path,
+    edit: impl FnOnce(&mut keyvalues::Block),
+  ) -> Result<(), SearchPathsError> {
+    let mut game_info = self.read_game_info()?;
 
-  Ok(())
-}
+    let search_paths = game_info
+      .root_mut()
+      .find_block_mut(&gameinfo::SEARCH_PATHS_BLOCK)
+      .ok_or(SearchPathsError::NoSearchPathsBlock)?;
 
-#[tauri::command]
-pub fn reset_search_paths(state: State<AppState>) -> Result<(), SearchPathsError> {
-  let path = state.path.as_ref().ok_or(SearchPathsError::NoGamePath)?;
+    edit(search_paths);
 
-  let search_paths = SearchPaths {
-    game: vec!["citadel".to_string(), "core".to_string()],
-    mod_key: None,
-    write: None,
-  };
+    self.replace_game_info(backups_path, game_info.to_string().as_bytes())
+  }
 
-  write_search_paths(path, &search_paths)?;
+  pub fn app_manifest(&self) -> Result<AppManifest, GameUpdateError> {
+    let manifest_path =
+      AppManifest::path(&self.path, GAME_ID).ok_or(GameUpdateError::NoGamePath)?;
 
-  Ok(())
+    AppManifest::open(&manifest_path).map_err(GameUpdateError::CouldNotReadAppManifest)
+  }
 }
 
-#[tauri::command]
-pub fn get_install_path(state: State<AppState>) -> Option<PathBuf> {
-  let state = state.install_path.lock().unwrap();
+fn read_addon_files(addon_path: &Path) -> Result<Vec<String>, vpk::VpkError> {
+  let directory = vpk::VpkDirectory::open(addon_path)?;
 
-  state.clone()
+  Ok(
+    directory
+      .entries
+      .into_iter()
+      .map(|entry| entry.path)
+      .collect(),
+  )
 }
 
-#[tauri::command]
-pub fn set_install_path(
-  state: State<AppState>,
-  install_path: String,
-  app_handle: AppHandle,
-) -> Result<(), String> {
-  let mut install_path_state = state.install_path.lock().unwrap();
+fn validate_profile_name(name: &str) -> Result<String, ProfileError> {
+  let name = name.trim();
 
-  let old_install_path = install_path_state.clone();
+  if name.is_empty() {
+    return Err(ProfileError::InvalidProfileName);
+  }
 
-  let new_install_path = PathBuf::from(install_path);
+  Ok(name.to_string())
+}
 
-  if let Some(old_install_path) = old_install_path.clone() {
-    if old_install_path == new_install_path {
-      return Ok(());
-    }
+/// Everything the addon and gameinfo logic works on, so it can run both
+/// behind the app's commands and from the command line tool.
+pub struct Context<'a> {
+  pub game: Option<GameInstall>,
+  pub storage: Option<AddonStorage>,
+  /// App data folder, gameinfo.gi backups are kept there.
+  pub data_path: Option<PathBuf>,
+  pub config: &'a dyn ConfigStore,
+  /// Every file copy goes through this job, for progress and cancellation.
+  pub copy_job: CopyJob,
+}
+
+impl Context<'_> {
+  fn read_config<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+    self
+      .config
+      .get(key)
+      .and_then(|value| T::deserialize(value).ok())
   }
 
-  let game_path = state.path.as_ref().ok_or("Game path not found")?;
+  fn write_config(&self, key: &str, value: impl Serialize) {
+    self.config.set(key, json!(value));
 
-  if new_install_path.starts_with(game_path) {
-    return Err("Addon storage path cannot be inside the game path.".to_string());
+    _ = self.config.save();
   }
 
-  if !new_install_path.exists() {
-    return Err("Addon storage path does not exist.".to_string());
+  fn game(&self) -> Result<&GameInstall, SearchPathsError> {
+    self.game.as_ref().ok_or(SearchPathsError::NoGamePath)
   }
 
-  *install_path_state = Some(new_install_path.clone());
+  pub fn search_paths_report(&self) -> Result<SearchPathsReport, SearchPathsError> {
+    self.game()?.search_paths_report()
+  }
 
-  let config_store = app_handle.store_builder(".config").build();
+  fn game_info_backups_path(&self) -> Result<PathBuf, SearchPathsError> {
+    self
+      .data_path
+      .as_ref()
+      .map(|path| path.join("gameinfo_backups"))
+      .ok_or_else(|| {
+        SearchPathsError::CouldNotBackupGameInfo(
+          PathBuf::from("gameinfo_backups"),
+          io::Error::new(io::ErrorKind::NotFound, "App data folder not found"),
+        )
+      })
+  }
 
-  config_store.set("install_path", json!(new_install_path.clone()));
+  fn replace_game_info(&self, content: &[u8]) -> Result<(), SearchPathsError> {
+    self
+      .game()?
+      .replace_game_info(&self.game_info_backups_path()?, content)
+  }
 
-  config_store.save().map_err(|e| e.to_string())?;
+  fn edit_search_paths(
+    &self,
+    edit: impl FnOnce(&mut keyvalues::Block),
+  ) -> Result<(), SearchPathsError> {
+    self
+      .game()?
+      .edit_search_paths(&self.game_info_backups_path()?, edit)
+  }
 
-  if let Some(old_install_path) = old_install_path {
-    if let Ok(entries) = std::fs::read_dir(&old_install_path) {
-      for entry in entries.flatten() {
-        if let Ok(file_type) = entry.file_type() {
-          if file_type.is_file() {
-            let extension = entry
-              .path()
-              .extension()
-              .map(|ext| ext.to_string_lossy().to_string());
+  /// Patches the search paths and adds the inserted entries to the recorded
+  /// ones.
+  fn patch_search_paths(&self) -> Result<(), SearchPathsError> {
+    let mut patched = PatchedEntries::default();
 
-            if let Some("vpk") = extension.as_deref() {
-              let file_name = entry.file_name().into_string().unwrap();
+    self.edit_search_paths(|block| patched = gameinfo::patch(block))?;
 
-              let destination = install_path_state.as_ref().unwrap().join(&file_name);
+    let recorded = self
+      .read_config::<PatchedEntries>(PATCHED_SEARCH_PATHS_KEY)
+      .unwrap_or_default();
 
-              let _ = std::fs::copy(entry.path(), &destination);
-            }
-          }
-        }
-      }
+    self.write_config(PATCHED_SEARCH_PATHS_KEY, recorded.union(patched));
+
+    Ok(())
+  }
+
+  pub fn mod_search_paths(&self) -> Result<(), SearchPathsError> {
+    self.patch_search_paths()?;
+
+    if let Ok(manifest) = self.read_app_manifest() {
+      self.write_config(LAST_PATCHED_BUILD_ID_KEY, manifest.build_id);
     }
+
+    Ok(())
   }
 
-  Ok(())
-}
+  /// Removes the entries inserted by patching. Entries the file had before
+  /// are kept, so a file modded by hand stays modded.
+  pub fn reset_search_paths(&self) -> Result<(), SearchPathsError> {
+    let patched = self
+      .read_config::<PatchedEntries>(PATCHED_SEARCH_PATHS_KEY)
+      .unwrap_or_default();
 
-#[tauri::command]
-pub fn list_installed_addons(state: State<AppState>) -> Result<Vec<String>, AddonError> {
-  let install_folder_path = state
-    .install_path
-    .lock()
-    .unwrap()
-    .clone()
-    .ok_or(AddonError::NoInstallPath)?;
+    self.edit_search_paths(|block| gameinfo::unpatch(block, patched))?;
 
-  let mut addons = vec![];
+    // Reset on purpose, don't re-patch it after the next update.
+    self.config.delete(LAST_PATCHED_BUILD_ID_KEY);
+    self.config.delete(PATCHED_SEARCH_PATHS_KEY);
 
-  for entry in
-    std::fs::read_dir(install_folder_path).map_err(AddonError::CouldNotReadInstallFolder)?
-  {
-    let entry = entry.map_err(AddonError::CouldNotReadInstallFolder)?;
+    _ = self.config.save();
 
-    let file_type = entry
-      .file_type()
-      .map_err(AddonError::CouldNotReadInstallFolder)?;
+    Ok(())
+  }
 
-    if file_type.is_file() {
-      let extension = entry
-        .path()
-        .extension()
-        .map(|ext| ext.to_string_lossy().to_string());
+  pub fn list_game_info_backups(&self) -> Result<Vec<GameInfoBackup>, SearchPathsError> {
+    let backups_path = self.game_info_backups_path()?;
 
-      if let Some("vpk") = extension.as_deref() {
-        if let Ok(addon_name) = entry.file_name().into_string() {
-          addons.push(addon_name);
-        }
-      }
-    }
+    backups::list(&backups_path).map_err(|e| SearchPathsError::CouldNotReadBackup(backups_path, e))
   }
 
-  Ok(addons)
-}
+  fn read_game_info_backup(&self, backup_id: &str) -> Result<Vec<u8>, SearchPathsError> {
+    let backups_path = self.game_info_backups_path()?;
 
-#[tauri::command]
-pub fn list_mounted_addons(state: State<AppState>) -> Result<Vec<String>, AddonError> {
-  let addons_game_folder =
-    create_addons_folder_if_not_exists(state.path.as_ref().ok_or(AddonError::NoGamePath)?)
-      .map_err(AddonError::CouldNotCreateAddonFolder)?;
+    backups::read(&backups_path, backup_id)
+      .map_err(|e| SearchPathsError::CouldNotReadBackup(backups_path, e))
+  }
 
-  let mut addons = vec![];
+  /// Diff from the given snapshot to the current gameinfo.gi.
+  pub fn diff_game_info_backup(&self, backup_id: &str) -> Result<Vec<DiffLine>, SearchPathsError> {
+    let game = self.game()?;
 
-  for entry in std::fs::read_dir(addons_game_folder).map_err(AddonError::CouldNotReadAddonFolder)? {
-    let entry = entry.map_err(AddonError::CouldNotReadAddonFolder)?;
+    let backup = self.read_game_info_backup(backup_id)?;
 
-    let file_type = entry
-      .file_type()
-      .map_err(AddonError::CouldNotReadAddonFolder)?;
+    let game_info_path = game.game_info_path();
 
-    if file_type.is_file() || file_type.is_symlink_file() {
-      let extension = entry
-        .path()
-        .extension()
-        .map(|ext| ext.to_string_lossy().to_string());
+    let current = std::fs::read(&game_info_path)
+      .map_err(|e| SearchPathsError::CouldNotReadGameInfo(game_info_path, e))?;
 
-      if let Some("vpk") = extension.as_deref() {
-        if let Ok(addon_name) = entry.file_name().into_string() {
-          addons.push(addon_name);
-        }
-      }
-    }
+    Ok(backups::diff_lines(
+      &String::from_utf8_lossy(&backup),
+      &String::from_utf8_lossy(&current),
+    ))
   }
 
-  Ok(addons)
-}
+  pub fn restore_game_info_backup(&self, backup_id: &str) -> Result<(), SearchPathsError> {
+    let backup = self.read_game_info_backup(backup_id)?;
 
-#[tauri::command]
-pub fn install_addon(
-  state: State<AppState>,
-  input: InstallAddonInfo,
-  app_handle: AppHandle,
-) -> Result<(), AddonError> {
-  let install_folder_path = state
-    .install_path
-    .lock()
-    .unwrap()
-    .clone()
-    .ok_or(AddonError::NoInstallPath)?;
+    self.replace_game_info(&backup)?;
 
-  let file_path = PathBuf::from(input.file_path);
+    // Which entries of the restored file were inserted by patching is unknown,
+    // none of them are removed on reset.
+    self.config.delete(PATCHED_SEARCH_PATHS_KEY);
 
-  if !file_path.exists() {
-    return Err(AddonError::InvalidAddonFile);
-  }
+    _ = self.config.save();
 
-  let extension = file_path
-    .extension()
-    .ok_or(AddonError::InvalidAddonFile)?
-    .to_string_lossy();
+    Ok(())
+  }
 
-  if extension != "vpk" {
-    return Err(AddonError::InvalidAddonFile);
+  fn read_app_manifest(&self) -> Result<AppManifest, GameUpdateError> {
+    self
+      .game
+      .as_ref()
+      .ok_or(GameUpdateError::NoGamePath)?
+      .app_manifest()
   }
 
-  let file_name = {
-    if let Some(file_name) = input.file_name {
-      file_name
-    } else {
-      file_path
-        .file_name()
-        .ok_or(AddonError::InvalidAddonFile)?
-        .to_string_lossy()
-        .to_string()
+  /// Compares the installed build and gameinfo.gi against the last patched
+  /// build. When the game was updated or gameinfo.gi was reverted, re-patches
+  /// it if enabled and records the outcome.
+  ///
+  /// Returns `None` when there is nothing new to report.
+  pub fn check_for_game_update(&self) -> Result<Option<GameUpdateStatus>, GameUpdateError> {
+    let manifest = self.read_app_manifest()?;
+
+    // Steam may still be replacing files, wait for the next check.
+    if !manifest.is_fully_installed() {
+      return Ok(None);
     }
-  };
 
-  let destination = install_folder_path.join(&file_name);
+    let search_paths_state = self
+      .search_paths_report()
+      .map_err(GameUpdateError::SearchPaths)?
+      .state;
 
-  std::fs::copy(&file_path, &destination).map_err(AddonError::CouldNotWriteInstallFolder)?;
+    let Some(last_patched_build_id) = self.read_config::<String>(LAST_PATCHED_BUILD_ID_KEY) else {
+      // Search paths modded before build ids were recorded.
+      if search_paths_state == SearchPathsState::Modded {
+        self.write_config(LAST_PATCHED_BUILD_ID_KEY, &manifest.build_id);
+      }
 
-  if let Some(display_name) = input.display_name {
-    let config_store = app_handle.store_builder(".config").build();
+      return Ok(None);
+    };
 
-    if !config_store.has("addons") {
-      config_store.set("addons", json!({}));
+    if last_patched_build_id == manifest.build_id && search_paths_state != SearchPathsState::Vanilla
+    {
+      return Ok(None);
     }
 
-    if let Some(mut current_addons) = config_store.get("addons") {
-      if let Some(addons_object) = current_addons.as_object_mut() {
-        addons_object.insert(
-          file_name.clone(),
-          json!(
-            {
-              "displayName": display_name
-            }
-          ),
-        );
-
-        config_store.set("addons", current_addons);
-
-        _ = config_store.save();
+    let (outcome, error) = if search_paths_state == SearchPathsState::Modded {
+      (GameUpdateOutcome::StillPatched, None)
+    } else if !self.auto_repatch() {
+      (GameUpdateOutcome::PatchRequired, None)
+    } else {
+      match self.patch_search_paths() {
+        Ok(()) => (GameUpdateOutcome::Repatched, None),
+        Err(e) => (GameUpdateOutcome::RepatchFailed, Some(e.payload())),
       }
+    };
+
+    if matches!(
+      outcome,
+      GameUpdateOutcome::StillPatched | GameUpdateOutcome::Repatched
+    ) {
+      self.write_config(LAST_PATCHED_BUILD_ID_KEY, &manifest.build_id);
+    } else if self
+      .game_update_status()
+      .is_some_and(|status| status.build_id == manifest.build_id && status.outcome == outcome)
+    {
+      // Already reported, don't repeat it on every check.
+      return Ok(None);
     }
-  }
-
-  Ok(())
-}
-
-#[tauri::command]
-pub fn uninstall_addon(state: State<AppState>, addon_file_name: String) -> Result<(), AddonError> {
-  let install_folder_path = state
-    .install_path
-    .lock()
-    .unwrap()
-    .clone()
-    .ok_or(AddonError::NoInstallPath)?;
 
-  let addon_game_folder_path = state
-    .path
-    .as_ref()
-    .ok_or(AddonError::NoGamePath)?
-    .join("game/citadel/addons")
-    .join(&addon_file_name);
+    let status = GameUpdateStatus {
+      build_id: manifest.build_id,
+      previous_build_id: last_patched_build_id,
+      search_paths_state,
+      outcome,
+      error,
+      detected_at: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default(),
+    };
+
+    self.write_config(GAME_UPDATE_STATUS_KEY, &status);
+
+    Ok(Some(status))
+  }
 
-  let addon_install_path = install_folder_path.join(&addon_file_name);
+  /// Last recorded game update, if any.
+  pub fn game_update_status(&self) -> Option<GameUpdateStatus> {
+    self.read_config(GAME_UPDATE_STATUS_KEY)
+  }
 
-  if !addon_install_path.exists() {
-    return Err(AddonError::AddonIsNotInstalled);
+  pub fn auto_repatch(&self) -> bool {
+    self.read_config("auto_repatch").unwrap_or(false)
   }
 
-  if addon_game_folder_path.exists() {
-    return Err(AddonError::CannotDeleteMountedAddon);
+  pub fn set_auto_repatch(&self, auto_repatch: bool) {
+    self.write_config("auto_repatch", auto_repatch);
   }
 
-  std::fs::remove_file(&addon_install_path).map_err(AddonError::CouldNotWriteInstallFolder)?;
+  /// Validates and saves a game path, used instead of the detected one from
+  /// then on.
+  pub fn set_game_path(&self, game_path: &Path) -> Result<GameInstall, SettingsError> {
+    let game = GameInstall::new(game_path);
 
-  Ok(())
-}
+    if !game.is_valid() {
+      return Err(SettingsError::InvalidGamePath(game_path.to_path_buf()));
+    }
 
-#[tauri::command]
-pub fn mount_addon(
-  state: State<AppState>,
-  addon_file_name: String,
-  app_handle: AppHandle,
-) -> Result<(), AddonError> {
-  let addons_game_folder_path =
-    create_addons_folder_if_not_exists(state.path.as_ref().ok_or(AddonError::NoGamePath)?)
-      .map_err(AddonError::CouldNotCreateAddonFolder)?;
+    if let Some(storage) = self
+      .storage
+      .as_ref()
+      .filter(|storage| storage.path.starts_with(game_path))
+    {
+      return Err(SettingsError::StorageInsideGamePath {
+        storage_path: storage.path.clone(),
+        game_path: game_path.to_path_buf(),
+      });
+    }
 
-  let addon_game_path = addons_game_folder_path.join(&addon_file_name);
+    self.config.set("game_path", json!(game_path));
 
-  let addon_install_path = state
-    .install_path
-    .lock()
-    .unwrap()
-    .clone()
-    .ok_or(AddonError::NoInstallPath)?
-    .join(&addon_file_name);
+    self
+      .config
+      .save()
+      .map_err(SettingsError::CouldNotSaveSettings)?;
 
-  if !addon_install_path.exists() {
-    return Err(AddonError::AddonIsNotInstalled);
+    Ok(game)
   }
 
-  if addon_game_path.exists() {
-    return Err(AddonError::AddonAlreadyMounted);
+  /// Forgets the saved game path and looks for the game again.
+  pub fn detect_game_path(&self) -> Result<Option<GameInstall>, SettingsError> {
+    if self.config.delete("game_path") {
+      self
+        .config
+        .save()
+        .map_err(SettingsError::CouldNotSaveSettings)?;
+    }
+
+    Ok(GameInstall::locate())
   }
 
-  let config_store = app_handle.store_builder(".config").build();
+  /// Changes the storage folder. Addons in the current folder are copied to
+  /// the new one and verified first, see [`Context::migrate_storage`].
+  pub fn set_install_path(
+    &self,
+    new_install_path: &Path,
+    options: MigrationOptions,
+  ) -> Result<MigrationReport, SettingsError> {
+    if self
+      .storage
+      .as_ref()
+      .is_some_and(|storage| storage.path == new_install_path)
+    {
+      return Ok(MigrationReport::new(true));
+    }
 
-  let deploy_method = config_store
-    .get("deploy_method")
-    .and_then(|s| -> Option<DeployMethod> { DeployMethod::deserialize(s).ok() })
-    .unwrap_or(DeployMethod::Copy);
+    let game = self.game.as_ref().ok_or(SettingsError::NoGamePath)?;
 
-  if deploy_method == DeployMethod::Symlink && is_symlink_available(state.clone()) {
-    std::os::windows::fs::symlink_file(&addon_install_path, &addon_game_path)
-      .map_err(AddonError::CouldNotWriteAddonFolder)?;
-  } else {
-    std::fs::copy(&addon_install_path, &addon_game_path)
-      .map_err(AddonError::CouldNotWriteAddonFolder)?;
-  }
+    if new_install_path.starts_with(&game.path) {
+      return Err(SettingsError::StorageInsideGamePath {
+        storage_path: new_install_path.to_path_buf(),
+        game_path: game.path.clone(),
+      });
+    }
 
-  Ok(())
-}
+    if !new_install_path.exists() {
+      return Err(SettingsError::StoragePathNotFound(
+        new_install_path.to_path_buf(),
+      ));
+    }
+
+    // Nothing to migrate when the old storage folder is gone, for example
+    // after it was moved by hand or its drive letter changed.
+    let (mut report, moved_files) = match self
+      .storage
+      .as_ref()
+      .filter(|storage| storage.path.exists())
+    {
+      Some(storage) => {
+        let plan = self
+          .plan_storage_migration(new_install_path)
+          .map_err(SettingsError::Migration)?;
+
+        if !plan.enough_space {
+          return Err(SettingsError::NotEnoughSpace {
+            needed: plan.copy_bytes,
+            available: plan.available_bytes,
+          });
+        }
 
-#[tauri::command]
-pub fn unmount_addon(state: State<AppState>, addon_file_name: String) -> Result<(), AddonError> {
-  let addons_game_folder_path =
-    create_addons_folder_if_not_exists(state.path.as_ref().ok_or(AddonError::NoGamePath)?)
-      .map_err(AddonError::CouldNotCreateAddonFolder)?;
+        let (report, moved_files) = self
+          .migrate_storage(storage, new_install_path, &plan, options)
+          .map_err(SettingsError::Migration)?;
 
-  let addon_game_path = addons_game_folder_path.join(&addon_file_name);
-  let addon_install_path = state
-    .install_path
-    .lock()
-    .unwrap()
-    .clone()
-    .ok_or(AddonError::NoInstallPath)?
-    .join(&addon_file_name);
+        if !report.completed {
+          return Ok(report);
+        }
 
-  if !addon_game_path.exists() {
-    return Err(AddonError::AddonIsNotMounted);
-  }
+        (report, Some((storage.path.clone(), moved_files)))
+      }
+      None => {
+        self.config.set("install_path", json!(new_install_path));
 
-  if !addon_install_path.exists() {
-    let _ = std::fs::copy(&addon_game_path, &addon_install_path);
-  }
+        self
+          .config
+          .save()
+          .map_err(SettingsError::CouldNotSaveSettings)?;
 
-  std::fs::remove_file(&addon_game_path).map_err(AddonError::CouldNotWriteAddonFolder)?;
+        (MigrationReport::new(true), None)
+      }
+    };
 
-  Ok(())
-}
+    let moved = moved_files
+      .as_ref()
+      .map(|(old_path, moved_files)| (old_path.as_path(), moved_files));
 
-#[tauri::command]
-pub fn set_deploy_method(
-  state: State<AppState>,
-  deploy_method: DeployMethod,
-  app_handle: AppHandle,
-) -> Result<(), AddonError> {
-  let mounted_addons = list_mounted_addons(state.clone())?;
+    match self.relink_links(&AddonStorage::new(new_install_path), moved) {
+      Ok(relinked) => report.relinked = relinked,
+      Err(e) => log::warn!("Could not repair addon links: {}", error_message(&e)),
+    }
 
-  for addon in mounted_addons.iter() {
-    unmount_addon(state.clone(), addon.to_owned())?;
+    Ok(report)
   }
 
-  let config_store = app_handle.store_builder(".config").build();
+  /// Lists the stored addons with their size and whether the same names are
+  /// taken in `new_install_path`, and checks there is enough free space.
+  pub fn plan_storage_migration(
+    &self,
+    new_install_path: &Path,
+  ) -> Result<MigrationPlan, AddonError> {
+    let hash_index_path = self.hash_index_path();
 
-  config_store.set("deploy_method", json!(deploy_method));
+    let mut index = hash_index_path
+      .as_deref()
+      .map(HashIndex::load)
+      .unwrap_or_default();
 
-  _ = config_store.save();
+    let plan = migration::plan(self.storage()?, new_install_path, &mut index)?;
 
-  for addon in mounted_addons.iter() {
-    mount_addon(state.clone(), addon.to_owned(), app_handle.clone())?;
+    if let Some(hash_index_path) = hash_index_path {
+      let _ = index.save(&hash_index_path);
+    }
+
+    Ok(plan)
   }
 
-  Ok(())
-}
+  /// Copies stored addons to `new_install_path` and switches the storage path
+  /// to it. Every file is copied under a temporary name and compared with the
+  /// original before it takes its place, and nothing is switched unless all
+  /// addons made it. Returns the report and the new names of the moved files.
+  fn migrate_storage(
+    &self,
+    storage: &AddonStorage,
+    new_install_path: &Path,
+    plan: &MigrationPlan,
+    options: MigrationOptions,
+  ) -> Result<(MigrationReport, HashMap<String, String>), AddonError> {
+    let mut report = MigrationReport::new(false);
+
+    // (addon, new file name, outcome, files as (source, temporary, final))
+    let mut steps = vec![];
+
+    for addon in &plan.addons {
+      let (new_file_name, outcome) = match (addon.conflict, options.on_conflict) {
+        (None, _) => (addon.file_name.clone(), MigrationOutcome::Copied),
+        (Some(MigrationConflict::Identical), _) => {
+          (addon.file_name.clone(), MigrationOutcome::AlreadyPresent)
+        }
+        (Some(MigrationConflict::Different), MigrationConflictPolicy::Skip) => {
+          (addon.file_name.clone(), MigrationOutcome::Skipped)
+        }
+        (Some(MigrationConflict::Different), MigrationConflictPolicy::Overwrite) => {
+          (addon.file_name.clone(), MigrationOutcome::Overwritten)
+        }
+        (Some(MigrationConflict::Different), MigrationConflictPolicy::Rename) => (
+          migration::free_file_name(new_install_path, &addon.file_name),
+          MigrationOutcome::Renamed,
+        ),
+      };
+
+      let mut files = vec![];
+
+      for (index, file_name) in addon.files.iter().enumerate() {
+        let new_name = match index {
+          0 => Some(new_file_name.clone()),
+          index => vpk::chunk_file_name(&new_file_name, index as u16 - 1),
+        }
+        .ok_or(AddonError::InvalidAddonFile)?;
 
-#[tauri::command]
-pub fn is_symlink_available(state: State<AppState>) -> bool {
-  let install_path = state.install_path.lock().unwrap();
+        let final_path = new_install_path.join(&new_name);
 
-  if let Some(game_path) = state.path.as_ref() {
-    if let Some(install_path) = install_path.as_ref() {
-      let game_path_drive = game_path.components().next().unwrap().as_os_str();
-      let install_path_drive = install_path.components().next().unwrap().as_os_str();
+        files.push((
+          storage.addon_path(file_name),
+          final_path.with_file_name(format!("{}.migrating", new_name)),
+          final_path,
+        ));
+      }
 
-      return game_path_drive == install_path_drive;
+      steps.push((addon, new_file_name, outcome, files));
     }
-  }
 
-  false
-}
+    let copies = |outcome: MigrationOutcome| {
+      matches!(
+        outcome,
+        MigrationOutcome::Copied | MigrationOutcome::Overwritten | MigrationOutcome::Renamed
+      )
+    };
+
+    // Overwritten files aren't listed, a rollback must not delete them.
+    let created_files = steps
+      .iter()
+      .filter(|(_, _, outcome, _)| copies(*outcome))
+      .flat_map(|(_, _, outcome, files)| {
+        files
+          .iter()
+          .flat_map(move |(_, temporary_path, final_path)| {
+            std::iter::once(temporary_path.clone())
+              .chain((*outcome != MigrationOutcome::Overwritten).then(|| final_path.clone()))
+          })
+      })
+      .collect();
+
+    let transaction = self.begin_transaction(
+      Operation::MigrateStorage {
+        from: storage.path.clone(),
+        to: new_install_path.to_path_buf(),
+      },
+      created_files,
+    )?;
+
+    let mut index = HashIndex::default();
+
+    for (addon, _, outcome, files) in &steps {
+      if !copies(*outcome) {
+        continue;
+      }
 
-#[tauri::command]
-pub fn get_deploy_method(app_handle: AppHandle) -> DeployMethod {
-  let config_store = app_handle.store_builder(".config").build();
+      let temporary_files = files
+        .iter()
+        .map(|(source_path, temporary_path, _)| (source_path.clone(), temporary_path.clone()))
+        .collect::<Vec<_>>();
 
-  config_store
-    .get("deploy_method")
-    .and_then(|s| -> Option<DeployMethod> { DeployMethod::deserialize(s).ok() })
-    .unwrap_or(DeployMethod::Copy)
+      let error = match self.copy_job.copy_files(&temporary_files) {
+        Err(CopyError::Cancelled) => {
+          return self.finish_transaction(&transaction, Err(AddonError::Cancelled));
+        }
+        Err(CopyError::Io(path, e)) => {
+          Some(AddonError::CouldNotWriteInstallFolder(path, e).payload())
+        }
+        Ok(()) => temporary_files
+          .iter()
+          .any(|(source_path, temporary_path)| {
+            match (
+              index.hash_file(source_path),
+              index.hash_file(temporary_path),
+            ) {
+              (Ok(source_hash), Ok(copy_hash)) => source_hash != copy_hash,
+              _ => true,
+            }
+          })
+          .then(|| ErrorPayload::new("CopyMismatch", "Copy does not match the original")),
+      };
+
+      if let Some(error) = error {
+        report.failed.push(FailedAddon {
+          file_name: addon.file_name.clone(),
+          error,
+        });
+      }
+    }
+
+    if !report.failed.is_empty() {
+      self.roll_back(&transaction)?;
+
+      return Ok((report, HashMap::new()));
+    }
+
+    let result = (|| {
+      for (_, _, outcome, files) in &steps {
+        if copies(*outcome) {
+          for (_, temporary_path, final_path) in files {
+            std::fs::rename(temporary_path, final_path)
+              .map_err(|e| AddonError::CouldNotWriteInstallFolder(final_path.clone(), e))?;
+          }
+        }
+      }
+
+      for (addon, new_file_name, outcome, _) in &steps {
+        if *outcome == MigrationOutcome::Renamed {
+          self.rename_addon_references(&addon.file_name, new_file_name);
+        }
+      }
+
+      self.config.set("install_path", json!(new_install_path));
+
+      self.config.save().map_err(|e| {
+        AddonError::CouldNotWriteInstallFolder(
+          new_install_path.to_path_buf(),
+          io::Error::new(io::ErrorKind::Other, e),
+        )
+      })
+    })();
+
+    self.finish_transaction(&transaction, result)?;
+
+    report.completed = true;
+
+    let mut moved_files = HashMap::new();
+
+    for (addon, new_file_name, outcome, files) in steps {
+      if outcome == MigrationOutcome::Skipped {
+        continue;
+      }
+
+      for (source_path, _, final_path) in files {
+        if let (Some(file_name), Some(new_name)) = (source_path.file_name(), final_path.file_name())
+        {
+          moved_files.insert(
+            file_name.to_string_lossy().to_string(),
+            new_name.to_string_lossy().to_string(),
+          );
+        }
+      }
+
+      report.migrated.push(MigratedAddon {
+        file_name: addon.file_name.clone(),
+        new_file_name,
+        outcome,
+      });
+    }
+
+    if options.delete_old {
+      // Every addon is attempted, also after a failed one.
+      let results = report
+        .migrated
+        .iter()
+        .map(|addon| storage.remove(&addon.file_name))
+        .collect::<Vec<_>>();
+
+      report.old_copies_deleted = results.iter().all(Result::is_ok);
+    }
+
+    Ok((report, moved_files))
+  }
+
+  /// Updates display names, the mount list and profiles after a stored addon
+  /// got a new file name.
+  fn rename_addon_references(&self, file_name: &str, new_file_name: &str) {
+    let mut addons = self.config.get("addons").unwrap_or_else(|| json!({}));
+
+    if let Some(addons_object) = addons.as_object_mut() {
+      if let Some(addon) = addons_object.remove(file_name) {
+        addons_object.insert(new_file_name.to_string(), addon);
+
+        self.write_config("addons", addons);
+      }
+    }
+
+    let mut mount_list = self.read_mount_list();
+
+    for mounted_addon in &mut mount_list {
+      if mounted_addon.addon == file_name {
+        mounted_addon.addon = new_file_name.to_string();
+      }
+    }
+
+    self.write_mount_list(&mount_list);
+
+    let mut profiles = self.list_profiles();
+
+    for profile in &mut profiles {
+      for addon in &mut profile.addons {
+        if addon == file_name {
+          *addon = new_file_name.to_string();
+        }
+      }
+    }
+
+    self.write_profiles(&profiles);
+  }
+
+  fn journal_path(&self) -> Result<PathBuf, AddonError> {
+    self
+      .data_path
+      .as_ref()
+      .map(|path| path.join("journal.json"))
+      .ok_or_else(|| {
+        AddonError::CouldNotAccessJournal(
+          PathBuf::from("journal.json"),
+          io::Error::new(io::ErrorKind::NotFound, "App data folder not found"),
+        )
+      })
+  }
+
+  /// Records the intent of an operation that changes several files, before it
+  /// changes any of them.
+  fn begin_transaction(
+    &self,
+    operation: Operation,
+    created_files: Vec<PathBuf>,
+  ) -> Result<Transaction, AddonError> {
+    let journal_path = self.journal_path()?;
+
+    if journal::read(&journal_path)
+      .map_err(|e| AddonError::CouldNotAccessJournal(journal_path.clone(), e))?
+      .is_some()
+    {
+      return Err(AddonError::OperationInProgress);
+    }
+
+    // Managed addons only, the ones placed by hand are never touched.
+    let mounted = match operation {
+      Operation::MigrateStorage { .. } => vec![],
+      _ => self.list_mounted_addons()?,
+    };
+
+    let transaction = Transaction {
+      operation,
+      mounted,
+      created_files,
+    };
+
+    journal::write(&journal_path, &transaction)
+      .map_err(|e| AddonError::CouldNotAccessJournal(journal_path.clone(), e))?;
+
+    Ok(transaction)
+  }
+
+  /// Clears the journal when the operation succeeded, rolls it back otherwise.
+  fn finish_transaction<T>(
+    &self,
+    transaction: &Transaction,
+    result: Result<T, AddonError>,
+  ) -> Result<T, AddonError> {
+    match result {
+      Ok(value) => {
+        self.clear_journal()?;
+
+        Ok(value)
+      }
+      Err(e) => {
+        if let Err(rollback_error) = self.roll_back(transaction) {
+          log::error!(
+            "Could not roll back {:?}: {}",
+            transaction.operation,
+            error_message(&rollback_error)
+          );
+        }
+
+        Err(e)
+      }
+    }
+  }
+
+  /// Undoes a transaction as far as possible and clears the journal. Every
+  /// step is attempted even when an earlier one fails, the first error is
+  /// returned.
+  fn roll_back(&self, transaction: &Transaction) -> Result<(), AddonError> {
+    let result = match &transaction.operation {
+      Operation::SetDeployMethod { from, .. } => {
+        self.write_config("deploy_method", from);
+
+        self.restore_mounted_addons(&transaction.mounted, true)
+      }
+      Operation::ApplyProfile { .. } => self.restore_mounted_addons(&transaction.mounted, false),
+      Operation::MigrateStorage { from, .. } => {
+        let mut result = Ok(());
+
+        for file in &transaction.created_files {
+          if let Err(e) = std::fs::remove_file(file) {
+            if e.kind() != io::ErrorKind::NotFound && result.is_ok() {
+              result = Err(AddonError::CouldNotWriteInstallFolder(file.clone(), e));
+            }
+          }
+        }
+
+        self.write_config("install_path", from);
+
+        result
+      }
+    };
+
+    self.clear_journal()?;
+
+    result
+  }
+
+  fn clear_journal(&self) -> Result<(), AddonError> {
+    let journal_path = self.journal_path()?;
+
+    journal::clear(&journal_path).map_err(|e| AddonError::CouldNotAccessJournal(journal_path, e))
+  }
+
+  /// Brings managed addons back to `mounted`, in that order. With `redeploy`
+  /// the ones that stay are deployed again in place using the current deploy
+  /// method. Addons placed by hand are left alone.
+  fn restore_mounted_addons(&self, mounted: &[String], redeploy: bool) -> Result<(), AddonError> {
+    let mut result = Ok(());
+
+    for addon in self.scan_mounted_addons()? {
+      if !addon.managed {
+        continue;
+      }
+
+      let step = if !mounted.contains(&addon.file_name) {
+        self.unmount_addon(&addon.file_name)
+      } else if redeploy && self.storage()?.contains(&addon.file_name) {
+        self.redeploy_addon(&addon.file_name, &addon.deployed_file_name, true)
+      } else {
+        Ok(())
+      };
+
+      if let Err(e) = step {
+        result = result.and(Err(e));
+      }
+    }
+
+    let mounted_addons = self.list_mounted_addons()?;
+
+    for addon in mounted {
+      if !mounted_addons.contains(addon) {
+        if let Err(e) = self.deploy_addon(addon, ConflictPolicy::Ignore) {
+          result = result.and(Err(e));
+        }
+      }
+    }
+
+    let managed_addons = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .filter(|mounted_addon| mounted_addon.managed)
+      .map(|mounted_addon| mounted_addon.file_name)
+      .collect::<Vec<_>>();
+
+    let order = profiles::load_order(mounted, &managed_addons);
+
+    if order != managed_addons {
+      result = result.and(self.reorder_mounted_addons(order));
+    }
+
+    result
+  }
+
+  /// Rolls back an operation that was interrupted, by a crash or by closing
+  /// the app, and returns it.
+  pub fn recover(&self) -> Result<Option<Operation>, AddonError> {
+    let journal_path = self.journal_path()?;
+
+    let Some(transaction) = journal::read(&journal_path)
+      .map_err(|e| AddonError::CouldNotAccessJournal(journal_path, e))?
+    else {
+      return Ok(None);
+    };
+
+    self.roll_back(&transaction)?;
+
+    Ok(Some(transaction.operation))
+  }
+
+  fn storage(&self) -> Result<&AddonStorage, AddonError> {
+    self.storage.as_ref().ok_or(AddonError::NoInstallPath)
+  }
+
+  pub fn list_installed_addons(&self) -> Result<Vec<String>, AddonError> {
+    self.storage()?.list()
+  }
+
+  fn addons_game_folder_path(&self) -> Result<PathBuf, AddonError> {
+    let game = self.game.as_ref().ok_or(AddonError::NoGamePath)?;
+
+    game
+      .create_addons_folder_if_not_exists()
+      .map_err(|e| AddonError::CouldNotCreateAddonFolder(game.addons_path(), e))
+  }
+
+  /// Lists addons in the game addons folder ordered by priority. Files deployed
+  /// by the manager are reported under their stored name.
+  pub fn scan_mounted_addons(&self) -> Result<Vec<DeployedAddon>, AddonError> {
+    let addons_game_folder = self.addons_game_folder_path()?;
+
+    let mount_list = self.read_mount_list();
+
+    let mut addons = vec![];
+
+    let read_error = |e| AddonError::CouldNotReadAddonFolder(addons_game_folder.clone(), e);
+
+    for entry in std::fs::read_dir(&addons_game_folder).map_err(read_error)? {
+      let entry = entry.map_err(read_error)?;
+
+      let file_type = entry.file_type().map_err(read_error)?;
+
+      if file_type.is_file() || platform::is_symlink_file(&entry.path(), &file_type) {
+        let extension = entry
+          .path()
+          .extension()
+          .map(|ext| ext.to_string_lossy().to_string());
+
+        if let Some("vpk") = extension.as_deref() {
+          if let Ok(deployed_file_name) = entry.file_name().into_string() {
+            if vpk::is_chunk_file(&addons_game_folder, &deployed_file_name) {
+              continue;
+            }
+
+            let link_target = std::fs::read_link(entry.path()).ok();
+            let broken = link_target.is_some() && !entry.path().exists();
+
+            let mounted_addon = mount_list
+              .iter()
+              .find(|mounted_addon| mounted_addon.deployed_file_name() == deployed_file_name);
+
+            addons.push(match mounted_addon {
+              Some(mounted_addon) => DeployedAddon {
+                file_name: mounted_addon.addon.clone(),
+                deployed_file_name,
+                slot: Some(mounted_addon.slot),
+                managed: true,
+                link_target,
+                broken,
+              },
+              None => DeployedAddon {
+                file_name: deployed_file_name.clone(),
+                slot: load_order::pak_slot(&deployed_file_name),
+                deployed_file_name,
+                managed: false,
+                link_target,
+                broken,
+              },
+            });
+          }
+        }
+      }
+    }
+
+    addons.sort_by(|a, b| {
+      let a_key = (a.slot.unwrap_or(u32::MAX), &a.file_name);
+      let b_key = (b.slot.unwrap_or(u32::MAX), &b.file_name);
+
+      a_key.cmp(&b_key)
+    });
+
+    Ok(addons)
+  }
+
+  /// Stored names of the installed managed addons, highest priority first.
+  /// Addons placed in the addons folder by hand are only listed by
+  /// [`Context::scan_mounted_addons`], their names can match stored ones.
+  pub fn list_mounted_addons(&self) -> Result<Vec<String>, AddonError> {
+    let addons = self.scan_mounted_addons()?;
+
+    Ok(
+      addons
+        .into_iter()
+        .filter(|addon| addon.managed)
+        .map(|addon| addon.file_name)
+        .collect(),
+    )
+  }
+
+  fn read_mount_list(&self) -> Vec<MountedAddon> {
+    self.read_config("mounted_addons").unwrap_or_default()
+  }
+
+  fn write_mount_list(&self, mount_list: &[MountedAddon]) {
+    self.write_config("mounted_addons", mount_list);
+  }
+
+  pub fn install_addon(&self, input: InstallAddonInfo) -> Result<(), AddonError> {
+    let storage = self.storage()?;
+
+    let file_path = PathBuf::from(input.file_path);
+
+    if !file_path.exists() {
+      return Err(AddonError::InvalidAddonFile);
+    }
+
+    let extension = file_path
+      .extension()
+      .ok_or(AddonError::InvalidAddonFile)?
+      .to_string_lossy();
+
+    if extension != "vpk" {
+      return Err(AddonError::InvalidAddonFile);
+    }
+
+    let file_name = {
+      if let Some(file_name) = input.file_name {
+        file_name
+      } else {
+        file_path
+          .file_name()
+          .ok_or(AddonError::InvalidAddonFile)?
+          .to_string_lossy()
+          .to_string()
+      }
+    };
+
+    self.store_addon(storage, &file_path, &file_name, input.display_name)
+  }
+
+  /// Copies an addon file, along with its data chunks, to the storage folder
+  /// under `file_name` and records its display name.
+  fn store_addon(
+    &self,
+    storage: &AddonStorage,
+    file_path: &Path,
+    file_name: &str,
+    display_name: Option<String>,
+  ) -> Result<(), AddonError> {
+    storage.store(file_path, file_name, &self.copy_job)?;
+
+    let mut metadata = self
+      .classify_stored_addon(storage, file_name)
+      .map(|classification| json!(classification))
+      .unwrap_or_else(|_| json!({}));
+
+    if let Some(display_name) = display_name {
+      metadata["displayName"] = json!(display_name);
+    }
+
+    self.update_addon_metadata([(file_name.to_string(), metadata)]);
+
+    Ok(())
+  }
+
+  /// Merges metadata into the entries of stored addons in the `addons` config,
+  /// keyed by stored file name.
+  fn update_addon_metadata(&self, updates: impl IntoIterator<Item = (String, serde_json::Value)>) {
+    let mut addons = self.config.get("addons").unwrap_or_else(|| json!({}));
+
+    let Some(addons_object) = addons.as_object_mut() else {
+      return;
+    };
+
+    for (file_name, metadata) in updates {
+      let addon = addons_object.entry(file_name).or_insert_with(|| json!({}));
+
+      match (addon.as_object_mut(), metadata) {
+        (Some(addon), serde_json::Value::Object(metadata)) => addon.extend(metadata),
+        (_, metadata) => *addon = metadata,
+      }
+    }
+
+    self.write_config("addons", addons);
+  }
+
+  fn classify_stored_addon(
+    &self,
+    storage: &AddonStorage,
+    file_name: &str,
+  ) -> Result<AddonClassification, AddonError> {
+    let directory = vpk::VpkDirectory::open(&storage.addon_path(file_name))
+      .map_err(AddonError::CouldNotReadAddon)?;
+
+    Ok(categories::classify(
+      directory.entries.iter().map(|entry| entry.path.as_str()),
+    ))
+  }
+
+  /// Categories and heroes of an addon, recorded in its metadata, or read from
+  /// its VPK and recorded when missing. Addons only present in the addons
+  /// folder are looked up there and not recorded.
+  pub fn addon_classification(
+    &self,
+    addon_file_name: &str,
+  ) -> Result<AddonClassification, AddonError> {
+    let recorded = self
+      .config
+      .get("addons")
+      .and_then(|addons| addons.get(addon_file_name).cloned())
+      .and_then(|addon| AddonClassification::deserialize(addon).ok());
+
+    if let Some(classification) = recorded {
+      return Ok(classification);
+    }
+
+    let storage = self.storage()?;
+
+    if storage.contains(addon_file_name) {
+      let classification = self.classify_stored_addon(storage, addon_file_name)?;
+
+      self.update_addon_metadata([(addon_file_name.to_string(), json!(classification))]);
+
+      return Ok(classification);
+    }
+
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let deployed_addon = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .find(|addon| !addon.managed && addon.file_name == addon_file_name)
+      .ok_or_else(|| AddonError::AddonIsNotInstalled(addon_file_name.to_string()))?;
+
+    let directory =
+      vpk::VpkDirectory::open(&addons_game_folder_path.join(deployed_addon.deployed_file_name))
+        .map_err(AddonError::CouldNotReadAddon)?;
+
+    Ok(categories::classify(
+      directory.entries.iter().map(|entry| entry.path.as_str()),
+    ))
+  }
+
+  /// Classifies every stored addon, reading the VPK of those without a
+  /// recorded classification, or of all of them with `force`. Addons that
+  /// can't be read are left out.
+  pub fn classify_addons(
+    &self,
+    force: bool,
+  ) -> Result<BTreeMap<String, AddonClassification>, AddonError> {
+    let storage = self.storage()?;
+
+    let recorded = self.config.get("addons").unwrap_or_else(|| json!({}));
+
+    let mut classifications = BTreeMap::new();
+    let mut updates = vec![];
+
+    for file_name in storage.list()? {
+      let classification = recorded
+        .get(&file_name)
+        .filter(|_| !force)
+        .and_then(|addon| AddonClassification::deserialize(addon).ok());
+
+      let classification = match classification {
+        Some(classification) => classification,
+        None => match self.classify_stored_addon(storage, &file_name) {
+          Ok(classification) => {
+            updates.push((file_name.clone(), json!(classification)));
+
+            classification
+          }
+          Err(_) => continue,
+        },
+      };
+
+      classifications.insert(file_name, classification);
+    }
+
+    if !updates.is_empty() {
+      self.update_addon_metadata(updates);
+    }
+
+    Ok(classifications)
+  }
+
+  /// Keeps the addons whose classification matches `filter`, see
+  /// [`Context::addon_classification`].
+  pub fn filter_addons(
+    &self,
+    addons: Vec<String>,
+    filter: &AddonFilter,
+  ) -> Result<Vec<String>, AddonError> {
+    let mut filtered_addons = vec![];
+
+    for addon in addons {
+      match self.addon_classification(&addon) {
+        Ok(classification) if classification.matches(filter) => filtered_addons.push(addon),
+        Ok(_) | Err(AddonError::CouldNotReadAddon(_)) => {}
+        Err(e) => return Err(e),
+      }
+    }
+
+    Ok(filtered_addons)
+  }
+
+  /// Preview of a stored addon, see [`thumbnails::render_thumbnail`]. It is
+  /// cached in the storage folder until the addon file changes. `None` when
+  /// the addon contains no image that can be decoded.
+  pub fn addon_thumbnail(
+    &self,
+    addon_file_name: &str,
+  ) -> Result<Option<AddonThumbnail>, AddonError> {
+    let storage = self.storage()?;
+    let addon_path = storage.addon_path(addon_file_name);
+    let thumbnail_path = storage.thumbnail_path(addon_file_name);
+
+    let addon_modified = std::fs::metadata(&addon_path)
+      .and_then(|metadata| metadata.modified())
+      .map_err(|_| AddonError::AddonIsNotInstalled(addon_file_name.to_string()))?;
+
+    let is_cached = std::fs::metadata(&thumbnail_path)
+      .and_then(|metadata| metadata.modified())
+      .is_ok_and(|modified| modified >= addon_modified);
+
+    let png = if is_cached {
+      std::fs::read(&thumbnail_path)
+        .map_err(|e| AddonError::CouldNotReadInstallFolder(thumbnail_path.clone(), e))?
+    } else {
+      // An empty file remembers that there is nothing to show.
+      let png = thumbnails::render_thumbnail(&addon_path)
+        .map_err(AddonError::CouldNotReadAddon)?
+        .unwrap_or_default();
+
+      // The cache only saves time, failing to write it is fine.
+      if let Some(folder) = thumbnail_path.parent() {
+        let _ = std::fs::create_dir_all(folder);
+      }
+
+      let _ = std::fs::write(&thumbnail_path, &png);
+
+      png
+    };
+
+    if png.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(AddonThumbnail {
+      path: thumbnail_path,
+      data_url: format!("data:image/png;base64,{}", BASE64_STANDARD.encode(png)),
+    }))
+  }
+
+  /// Stores addons contained in a zip or 7z archive. `addons` are paths inside
+  /// the archive as returned by [`archive::list_addons`], every addon is
+  /// imported when it is empty. Returns the stored file names.
+  pub fn import_archive(
+    &self,
+    archive_path: &Path,
+    addons: &[String],
+  ) -> Result<Vec<String>, AddonError> {
+    let storage = self.storage()?;
+
+    let archive_addons =
+      archive::list_addons(archive_path).map_err(AddonError::CouldNotReadArchive)?;
+
+    let selected_addons = if addons.is_empty() {
+      archive_addons
+    } else {
+      addons
+        .iter()
+        .map(|path| {
+          archive_addons
+            .iter()
+            .find(|archive_addon| &archive_addon.path == path)
+            .cloned()
+            .ok_or(AddonError::InvalidAddonFile)
+        })
+        .collect::<Result<Vec<_>, _>>()?
+    };
+
+    if selected_addons.is_empty() {
+      return Err(AddonError::InvalidAddonFile);
+    }
+
+    // Imports can run at the same time, each one needs a folder of its own.
+    let extract_folder_path = std::env::temp_dir().join(format!(
+      "citadel-cm-import-{}-{}",
+      std::process::id(),
+      IMPORT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = self.import_archive_addons(
+      archive_path,
+      &extract_folder_path,
+      storage,
+      &selected_addons,
+    );
+
+    let _ = std::fs::remove_dir_all(&extract_folder_path);
+
+    result
+  }
+
+  fn import_archive_addons(
+    &self,
+    archive_path: &Path,
+    extract_folder_path: &Path,
+    storage: &AddonStorage,
+    addons: &[ArchiveAddon],
+  ) -> Result<Vec<String>, AddonError> {
+    let mut files = vec![];
+
+    // Each addon gets its own folder, archives often contain variants of the
+    // same addon with identical file names.
+    for (index, addon) in addons.iter().enumerate() {
+      let addon_folder_path = extract_folder_path.join(index.to_string());
+
+      std::fs::create_dir_all(&addon_folder_path)
+        .map_err(|e| AddonError::CouldNotReadArchive(ArchiveError::Io(e)))?;
+
+      files.push((addon.path.clone(), addon_folder_path.join(&addon.file_name)));
+
+      for (chunk_index, chunk_path) in addon.chunks.iter().enumerate() {
+        let chunk_file_name = vpk::chunk_file_name(&addon.file_name, chunk_index as u16)
+          .ok_or(AddonError::InvalidAddonFile)?;
+
+        files.push((chunk_path.clone(), addon_folder_path.join(chunk_file_name)));
+      }
+    }
+
+    archive::extract(archive_path, &files, &self.copy_job).map_err(|e| match e {
+      ArchiveError::Cancelled => AddonError::Cancelled,
+      e => AddonError::CouldNotReadArchive(e),
+    })?;
+
+    let mut stored_file_names: Vec<String> = vec![];
+
+    for (index, addon) in addons.iter().enumerate() {
+      // Never overwrite a stored addon, it may be linked into the game. This
+      // also covers addons of this archive stored a moment ago.
+      let file_name = if storage.contains(&addon.file_name) {
+        migration::free_file_name(&storage.path, &addon.file_name)
+      } else {
+        addon.file_name.clone()
+      };
+
+      self.store_addon(
+        storage,
+        &extract_folder_path
+          .join(index.to_string())
+          .join(&addon.file_name),
+        &file_name,
+        Some(addon.display_name.clone()),
+      )?;
+
+      stored_file_names.push(file_name);
+    }
+
+    Ok(stored_file_names)
+  }
+
+  pub fn uninstall_addon(&self, addon_file_name: &str) -> Result<(), AddonError> {
+    let storage = self.storage()?;
+
+    if !storage.contains(addon_file_name) {
+      return Err(AddonError::AddonIsNotInstalled(addon_file_name.to_string()));
+    }
+
+    let mounted_addons = self.list_mounted_addons()?;
+
+    if mounted_addons.iter().any(|addon| addon == addon_file_name) {
+      return Err(AddonError::CannotDeleteMountedAddon(
+        addon_file_name.to_string(),
+      ));
+    }
+
+    storage.remove(addon_file_name)
+  }
+
+  pub fn mount_addon(&self, addon_file_name: &str) -> Result<Vec<AddonConflict>, AddonError> {
+    self.deploy_addon(addon_file_name, self.conflict_policy())
+  }
+
+  fn deploy_addon(
+    &self,
+    addon_file_name: &str,
+    conflict_policy: ConflictPolicy,
+  ) -> Result<Vec<AddonConflict>, AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let storage = self.storage()?;
+
+    let addon_install_path = storage.addon_path(addon_file_name);
+
+    if !addon_install_path.exists() {
+      return Err(AddonError::AddonIsNotInstalled(addon_file_name.to_string()));
+    }
+
+    let mounted_addons = self.scan_mounted_addons()?;
+
+    if mounted_addons
+      .iter()
+      .any(|mounted_addon| mounted_addon.managed && mounted_addon.file_name == addon_file_name)
+    {
+      return Err(AddonError::AddonAlreadyMounted(addon_file_name.to_string()));
+    }
+
+    let occupied_slots = mounted_addons
+      .iter()
+      .filter_map(|mounted_addon| mounted_addon.slot)
+      .collect::<Vec<_>>();
+
+    let slot = load_order::next_free_slot(&occupied_slots).ok_or(AddonError::NoFreeLoadSlot)?;
+
+    let addon_game_path = addons_game_folder_path.join(load_order::slot_file_name(slot));
+
+    let candidate = Some((addon_file_name, addon_install_path.as_path(), Some(slot)));
+
+    let conflicts = match conflict_policy {
+      ConflictPolicy::Ignore => vec![],
+      ConflictPolicy::Warn => self
+        .compute_conflicts(candidate)
+        .map(|report| report.conflicts)
+        .unwrap_or_default(),
+      ConflictPolicy::Refuse => {
+        let report = self.compute_conflicts(candidate)?;
+
+        if !report.conflicts.is_empty() {
+          return Err(AddonError::AddonWouldConflict(report.conflicts));
+        }
+
+        report.conflicts
+      }
+    };
+
+    let mut files = vec![(addon_install_path, addon_game_path)];
+
+    for (index, chunk_file_name) in storage.chunk_files(addon_file_name).into_iter().enumerate() {
+      if let Some(deployed_chunk_file_name) =
+        vpk::chunk_file_name(&load_order::slot_file_name(slot), index as u16)
+      {
+        files.push((
+          storage.addon_path(&chunk_file_name),
+          addons_game_folder_path.join(deployed_chunk_file_name),
+        ));
+      }
+    }
+
+    self.deploy_files(files)?;
+
+    let mut mount_list = self.read_mount_list();
+
+    mount_list
+      .retain(|mounted_addon| mounted_addon.slot != slot && mounted_addon.addon != addon_file_name);
+
+    mount_list.push(MountedAddon {
+      addon: addon_file_name.to_string(),
+      slot,
+    });
+
+    self.write_mount_list(&mount_list);
+
+    Ok(conflicts)
+  }
+
+  /// Links or copies stored files into the addons folder with the current
+  /// deploy method.
+  fn deploy_files(&self, files: Vec<(PathBuf, PathBuf)>) -> Result<(), AddonError> {
+    let deploy_method = self.deploy_method();
+    let link_file = deploy_method.link_file();
+
+    let mut linked_files = vec![];
+    let mut copied_files = vec![];
+
+    for (source, destination) in files {
+      match link_file.map(|link_file| link_file(&source, &destination)) {
+        Some(Ok(())) => linked_files.push((source, destination)),
+        // Symlinks fall back to a copy, for example when Windows doesn't allow
+        // creating them. Hard links and reflinks are picked to save space, a
+        // copy would silently take it.
+        Some(Err(e)) if deploy_method != DeployMethod::Symlink => {
+          for (_, destination) in &linked_files {
+            let _ = std::fs::remove_file(destination);
+          }
+
+          return Err(AddonError::CouldNotLinkAddon(destination, e));
+        }
+        _ => copied_files.push((source, destination)),
+      }
+    }
+
+    if let Err(e) = self.copy_job.copy_files(&copied_files) {
+      // Don't leave a partially deployed chunk set behind.
+      for (_, destination) in &linked_files {
+        let _ = std::fs::remove_file(destination);
+      }
+
+      return Err(AddonError::from_copy_error(
+        e,
+        AddonError::CouldNotWriteAddonFolder,
+      ));
+    }
+
+    Ok(())
+  }
+
+  pub fn unmount_addon(&self, addon_file_name: &str) -> Result<(), AddonError> {
+    let mounted_addon = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .find(|mounted_addon| mounted_addon.managed && mounted_addon.file_name == addon_file_name)
+      .ok_or_else(|| AddonError::AddonIsNotMounted(addon_file_name.to_string()))?;
+
+    // The deployed files are the only copy when the stored one is gone.
+    let store_as =
+      (!self.storage()?.addon_path(addon_file_name).exists()).then_some(addon_file_name);
+
+    self.remove_deployed_addon(&mounted_addon, store_as)?;
+
+    let mut mount_list = self.read_mount_list();
+
+    mount_list.retain(|mounted_addon| mounted_addon.addon != addon_file_name);
+
+    self.write_mount_list(&mount_list);
+
+    Ok(())
+  }
+
+  /// Moves an addon placed in the addons folder by hand into the storage,
+  /// under its deployed name or a free one when the storage already has a
+  /// file with that name. Returns the stored name, `None` for a broken symlink
+  /// that is only removed.
+  pub fn unmount_unmanaged_addon(
+    &self,
+    deployed_file_name: &str,
+  ) -> Result<Option<String>, AddonError> {
+    let storage = self.storage()?;
+
+    let mounted_addon = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .find(|mounted_addon| {
+        !mounted_addon.managed && mounted_addon.deployed_file_name == deployed_file_name
+      })
+      .ok_or_else(|| AddonError::AddonIsNotMounted(deployed_file_name.to_string()))?;
+
+    let file_name =
+      (!mounted_addon.broken).then(|| free_stored_file_name(storage, deployed_file_name));
+
+    self.remove_deployed_addon(&mounted_addon, file_name.as_deref())?;
+
+    Ok(file_name)
+  }
+
+  /// Deletes the deployed files of `addon`, after copying them into the
+  /// storage as `store_as` when given. They stay deployed unless the copy is
+  /// complete.
+  fn remove_deployed_addon(
+    &self,
+    addon: &DeployedAddon,
+    store_as: Option<&str>,
+  ) -> Result<(), AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let addon_game_path = addons_game_folder_path.join(&addon.deployed_file_name);
+
+    let chunk_files = vpk::find_chunk_files(&addons_game_folder_path, &addon.deployed_file_name);
+
+    if let Some(file_name) = store_as {
+      let addon_install_path = self.storage()?.addon_path(file_name);
+
+      let mut files = vec![(addon_game_path.clone(), addon_install_path.clone())];
+
+      for (index, chunk_file_name) in chunk_files.iter().enumerate() {
+        let stored_chunk_file_name =
+          vpk::chunk_file_name(file_name, index as u16).ok_or(AddonError::InvalidAddonFile)?;
+
+        files.push((
+          addons_game_folder_path.join(chunk_file_name),
+          addon_install_path.with_file_name(stored_chunk_file_name),
+        ));
+      }
+
+      self
+        .copy_job
+        .copy_files(&files)
+        .map_err(|e| AddonError::from_copy_error(e, AddonError::CouldNotWriteInstallFolder))?;
+    }
+
+    std::fs::remove_file(&addon_game_path)
+      .map_err(|e| AddonError::CouldNotWriteAddonFolder(addon_game_path.clone(), e))?;
+
+    for chunk_file_name in chunk_files {
+      let chunk_path = addons_game_folder_path.join(chunk_file_name);
+
+      std::fs::remove_file(&chunk_path)
+        .map_err(|e| AddonError::CouldNotWriteAddonFolder(chunk_path, e))?;
+    }
+
+    Ok(())
+  }
+
+  /// Turns addons placed in the addons folder by hand into managed ones. Each
+  /// is matched by hash against stored addons that aren't installed, or else
+  /// copied into the storage. With `redeploy` the deployed copies are then
+  /// replaced using the current deploy method.
+  pub fn adopt_unmanaged_addons(&self, redeploy: bool) -> Result<Vec<AdoptedAddon>, AddonError> {
+    let storage = self.storage()?;
+
+    let hash_index_path = self.hash_index_path();
+
+    let mut index = hash_index_path
+      .as_deref()
+      .map(HashIndex::load)
+      .unwrap_or_default();
+
+    let unmanaged_addons = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .filter(|addon| !addon.managed)
+      .collect::<Vec<_>>();
+
+    // Stored addons that aren't installed, by hash of all their files.
+    let mut candidates = vec![];
+
+    if !unmanaged_addons.is_empty() {
+      let mount_list = self.read_mount_list();
+
+      for file_name in storage.list()? {
+        if mount_list
+          .iter()
+          .any(|mounted_addon| mounted_addon.addon == file_name)
+        {
+          continue;
+        }
+
+        let files = std::iter::once(file_name.clone())
+          .chain(storage.chunk_files(&file_name))
+          .map(|file_name| storage.addon_path(&file_name))
+          .collect::<Vec<_>>();
+
+        if let Ok(hash) = index.hash_files(&files) {
+          candidates.push((file_name, hash));
+        }
+      }
+    }
+
+    let mut adopted_addons = vec![];
+
+    for addon in unmanaged_addons {
+      let mut adopted_addon = AdoptedAddon {
+        deployed_file_name: addon.deployed_file_name.clone(),
+        file_name: None,
+        outcome: AdoptOutcome::Failed,
+        redeployed: false,
+        error: None,
+      };
+
+      match (addon.broken, addon.slot) {
+        (true, _) => adopted_addon.outcome = AdoptOutcome::Broken,
+        (false, None) => adopted_addon.outcome = AdoptOutcome::NotLoadable,
+        (false, Some(slot)) => {
+          match self.adopt_addon(storage, &mut index, &mut candidates, &addon, slot) {
+            Ok((file_name, outcome)) => {
+              adopted_addon.outcome = outcome;
+
+              if redeploy && self.deploy_method() != DeployMethod::Copy {
+                match self.redeploy_addon(&file_name, &addon.deployed_file_name, false) {
+                  Ok(()) => adopted_addon.redeployed = true,
+                  Err(AddonError::Cancelled) => return Err(AddonError::Cancelled),
+                  Err(e) => adopted_addon.error = Some(e.payload()),
+                }
+              }
+
+              adopted_addon.file_name = Some(file_name);
+            }
+            Err(AddonError::Cancelled) => return Err(AddonError::Cancelled),
+            Err(e) => adopted_addon.error = Some(e.payload()),
+          }
+        }
+      }
+
+      adopted_addons.push(adopted_addon);
+    }
+
+    if let Some(hash_index_path) = hash_index_path {
+      let _ = index.save(&hash_index_path);
+    }
+
+    Ok(adopted_addons)
+  }
+
+  /// Finds or stores the addon deployed as `addon` and records it as installed
+  /// in `slot`. Returns its stored file name.
+  fn adopt_addon(
+    &self,
+    storage: &AddonStorage,
+    index: &mut HashIndex,
+    candidates: &mut Vec<(String, String)>,
+    addon: &DeployedAddon,
+    slot: u32,
+  ) -> Result<(String, AdoptOutcome), AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let deployed_files = std::iter::once(addon.deployed_file_name.clone())
+      .chain(vpk::find_chunk_files(
+        &addons_game_folder_path,
+        &addon.deployed_file_name,
+      ))
+      .collect::<Vec<_>>();
+
+    let deployed_paths = deployed_files
+      .iter()
+      .map(|file_name| addons_game_folder_path.join(file_name))
+      .collect::<Vec<_>>();
+
+    let hash = index
+      .hash_files(&deployed_paths)
+      .map_err(|e| AddonError::CouldNotReadAddonFolder(deployed_paths[0].clone(), e))?;
+
+    let matched = candidates
+      .iter()
+      .position(|(_, candidate_hash)| *candidate_hash == hash)
+      .map(|position| candidates.remove(position).0);
+
+    let (file_name, outcome) = match matched {
+      Some(file_name) => (file_name, AdoptOutcome::Matched),
+      None => {
+        let file_name = free_stored_file_name(storage, &addon.deployed_file_name);
+
+        let mut files = vec![];
+
+        for (index, deployed_path) in deployed_paths.iter().enumerate() {
+          let stored_file_name = match index {
+            0 => Some(file_name.clone()),
+            index => vpk::chunk_file_name(&file_name, index as u16 - 1),
+          }
+          .ok_or(AddonError::InvalidAddonFile)?;
+
+          files.push((deployed_path.clone(), storage.addon_path(&stored_file_name)));
+        }
+
+        self
+          .copy_job
+          .copy_files(&files)
+          .map_err(|e| AddonError::from_copy_error(e, AddonError::CouldNotWriteInstallFolder))?;
+
+        let stored_paths = files
+          .iter()
+          .map(|(_, stored_path)| stored_path.clone())
+          .collect::<Vec<_>>();
+
+        if index.hash_files(&stored_paths).ok().as_ref() != Some(&hash) {
+          for stored_path in stored_paths {
+            let _ = std::fs::remove_file(stored_path);
+          }
+
+          return Err(AddonError::InvalidAddonFile);
+        }
+
+        let mut metadata = self
+          .classify_stored_addon(storage, &file_name)
+          .map(|classification| json!(classification))
+          .unwrap_or_else(|_| json!({}));
+
+        metadata["displayName"] = json!(format!("Adopted {}", addon.deployed_file_name));
+
+        self.update_addon_metadata([(file_name.clone(), metadata)]);
+
+        (file_name, AdoptOutcome::Stored)
+      }
+    };
+
+    index.set_synced_hash(&file_name, hash);
+
+    let mut mount_list = self.read_mount_list();
+
+    mount_list.retain(|mounted_addon| mounted_addon.slot != slot);
+
+    mount_list.push(MountedAddon {
+      addon: file_name.clone(),
+      slot,
+    });
+
+    self.write_mount_list(&mount_list);
+
+    Ok((file_name, outcome))
+  }
+
+  /// Replaces the deployed copy of a managed addon using the current deploy
+  /// method. Without `force` a deployed file that already is the stored one,
+  /// through a link, is kept.
+  fn redeploy_addon(
+    &self,
+    file_name: &str,
+    deployed_file_name: &str,
+    force: bool,
+  ) -> Result<(), AddonError> {
+    let (stored_files, deployed_files) = self.addon_file_pairs(&DeployedAddon {
+      file_name: file_name.to_string(),
+      deployed_file_name: deployed_file_name.to_string(),
+      slot: None,
+      managed: true,
+      link_target: None,
+      broken: false,
+    })?;
+
+    if stored_files.len() != deployed_files.len() {
+      return Err(AddonError::InvalidAddonFile);
+    }
+
+    if !force && is_same_file(&stored_files[0], &deployed_files[0]) {
+      return Ok(());
+    }
+
+    // The old copies are kept aside until the new ones are in place.
+    let set_aside_files = deployed_files
+      .iter()
+      .map(|deployed_file| deployed_file.with_extension("vpk.adopting"))
+      .collect::<Vec<_>>();
+
+    let restore = |count: usize| {
+      for (deployed_file, set_aside_file) in deployed_files.iter().zip(&set_aside_files).take(count)
+      {
+        let _ = std::fs::rename(set_aside_file, deployed_file);
+      }
+    };
+
+    for (index, (deployed_file, set_aside_file)) in
+      deployed_files.iter().zip(&set_aside_files).enumerate()
+    {
+      if let Err(e) = std::fs::rename(deployed_file, set_aside_file) {
+        restore(index);
+
+        return Err(AddonError::CouldNotWriteAddonFolder(
+          deployed_file.clone(),
+          e,
+        ));
+      }
+    }
+
+    if let Err(e) = self.deploy_files(
+      stored_files
+        .into_iter()
+        .zip(deployed_files.clone())
+        .collect(),
+    ) {
+      restore(set_aside_files.len());
+
+      return Err(e);
+    }
+
+    for set_aside_file in set_aside_files {
+      let _ = std::fs::remove_file(set_aside_file);
+    }
+
+    Ok(())
+  }
+
+  /// Points dangling symlinks in the addons folder to the file with the same
+  /// name in the current storage, or copies it there when linking fails.
+  pub fn repair_links(&self) -> Result<Vec<LinkRepair>, AddonError> {
+    self.relink_links(self.storage()?, None)
+  }
+
+  /// Relinks dangling symlinks to `storage`. With `moved`, links into the old
+  /// storage folder are relinked too, even when they still work, following the
+  /// old to new file names of the moved files.
+  fn relink_links(
+    &self,
+    storage: &AddonStorage,
+    moved: Option<(&Path, &HashMap<String, String>)>,
+  ) -> Result<Vec<LinkRepair>, AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let mut repairs = vec![];
+
+    let read_error = |e| AddonError::CouldNotReadAddonFolder(addons_game_folder_path.clone(), e);
+
+    for entry in std::fs::read_dir(&addons_game_folder_path).map_err(read_error)? {
+      let entry = entry.map_err(read_error)?;
+
+      let link_path = entry.path();
+
+      let Ok(link_target) = std::fs::read_link(&link_path) else {
+        continue;
+      };
+
+      if !link_path.extension().is_some_and(|ext| ext == "vpk") {
+        continue;
+      }
+
+      let link_file_name = link_target
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string());
+
+      let moved_file_name = moved.and_then(|(old_path, moved_files)| {
+        link_target
+          .parent()
+          .filter(|parent| *parent == old_path)
+          .and(link_file_name.as_ref())
+          .and_then(|file_name| moved_files.get(file_name))
+      });
+
+      if moved_file_name.is_none() && link_path.exists() {
+        continue;
+      }
+
+      let Ok(deployed_file_name) = entry.file_name().into_string() else {
+        continue;
+      };
+
+      // Data chunks are linked under their stored names too, so every link can
+      // be matched by its target's file name.
+      let stored_path = moved_file_name
+        .or(link_file_name.as_ref())
+        .map(|file_name| storage.path.join(file_name))
+        .filter(|stored_path| stored_path.is_file());
+
+      let outcome = match stored_path {
+        None => LinkRepairOutcome::Missing,
+        Some(stored_path) => {
+          std::fs::remove_file(&link_path)
+            .map_err(|e| AddonError::CouldNotWriteAddonFolder(link_path.clone(), e))?;
+
+          if platform::symlink_file(&stored_path, &link_path).is_ok() {
+            LinkRepairOutcome::Relinked
+          } else {
+            self
+              .copy_job
+              .copy_files(&[(stored_path, link_path)])
+              .map_err(|e| AddonError::from_copy_error(e, AddonError::CouldNotWriteAddonFolder))?;
+
+            LinkRepairOutcome::Copied
+          }
+        }
+      };
+
+      repairs.push(LinkRepair {
+        deployed_file_name,
+        link_target,
+        outcome,
+      });
+    }
+
+    repairs.sort_by(|a, b| a.deployed_file_name.cmp(&b.deployed_file_name));
+
+    Ok(repairs)
+  }
+
+  /// Reassigns `pakNN_dir.vpk` slots so that managed addons are loaded in the
+  /// given order, highest priority first. Unmanaged addons keep their slots.
+  pub fn reorder_mounted_addons(&self, order: Vec<String>) -> Result<(), AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let mounted_addons = self.scan_mounted_addons()?;
+
+    let (managed_addons, unmanaged_addons): (Vec<_>, Vec<_>) = mounted_addons
+      .into_iter()
+      .partition(|mounted_addon| mounted_addon.managed);
+
+    if order.len() != managed_addons.len()
+      || !managed_addons
+        .iter()
+        .all(|mounted_addon| order.contains(&mounted_addon.file_name))
+    {
+      return Err(AddonError::InvalidLoadOrder);
+    }
+
+    let reserved_slots = unmanaged_addons
+      .iter()
+      .filter_map(|mounted_addon| mounted_addon.slot)
+      .collect::<Vec<_>>();
+
+    let slots =
+      load_order::assign_slots(order.len(), &reserved_slots).ok_or(AddonError::NoFreeLoadSlot)?;
+
+    let renames = order
+      .iter()
+      .zip(&slots)
+      .filter_map(|(addon, &slot)| {
+        let mounted_addon = managed_addons
+          .iter()
+          .find(|mounted_addon| &mounted_addon.file_name == addon)?;
+
+        Some((
+          mounted_addon.deployed_file_name.clone(),
+          load_order::slot_file_name(slot),
+        ))
+      })
+      .flat_map(|(from, to)| {
+        // Data chunks follow the `_dir.vpk` file to its new slot.
+        let chunk_renames = vpk::find_chunk_files(&addons_game_folder_path, &from)
+          .into_iter()
+          .enumerate()
+          .filter_map(|(index, chunk_file_name)| {
+            Some((chunk_file_name, vpk::chunk_file_name(&to, index as u16)?))
+          })
+          .collect::<Vec<_>>();
+
+        std::iter::once((from, to)).chain(chunk_renames)
+      })
+      .collect::<Vec<_>>();
+
+    load_order::rename_all(&addons_game_folder_path, &renames)
+      .map_err(|(path, e)| AddonError::CouldNotWriteAddonFolder(path, e))?;
+
+    let mount_list = order
+      .into_iter()
+      .zip(slots)
+      .map(|(addon, slot)| MountedAddon { addon, slot })
+      .collect::<Vec<_>>();
+
+    self.write_mount_list(&mount_list);
+
+    Ok(())
+  }
+
+  pub fn set_deploy_method(&self, deploy_method: DeployMethod) -> Result<(), AddonError> {
+    if !self.deploy_capabilities().supports(deploy_method) {
+      return Err(AddonError::DeployMethodUnavailable);
+    }
+
+    let transaction = self.begin_transaction(
+      Operation::SetDeployMethod {
+        from: self.deploy_method(),
+        to: deploy_method,
+      },
+      vec![],
+    )?;
+
+    self.write_config("deploy_method", deploy_method);
+
+    let result = self.redeploy_managed_addons();
+
+    self.finish_transaction(&transaction, result)
+  }
+
+  /// Replaces the deployed files of every managed addon in place, so they keep
+  /// their slots. Addons placed by hand are left alone, and so are addons
+  /// whose stored copy is gone, the deployed one is all there is.
+  fn redeploy_managed_addons(&self) -> Result<(), AddonError> {
+    let storage = self.storage()?;
+
+    for addon in self.scan_mounted_addons()? {
+      if addon.managed && storage.contains(&addon.file_name) {
+        self.redeploy_addon(&addon.file_name, &addon.deployed_file_name, true)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn deploy_method(&self) -> DeployMethod {
+    self
+      .read_config("deploy_method")
+      .unwrap_or(DeployMethod::Copy)
+  }
+
+  /// Tests which deploy methods work by linking a temporary file from the
+  /// storage into the game addons folder.
+  pub fn deploy_capabilities(&self) -> DeployCapabilities {
+    let (Some(game), Some(storage)) = (self.game.as_ref(), self.storage.as_ref()) else {
+      return DeployCapabilities::default();
+    };
+
+    let Ok(addons_game_folder_path) = game.create_addons_folder_if_not_exists() else {
+      return DeployCapabilities::default();
+    };
+
+    let probe = |deploy_method: DeployMethod| {
+      deploy_method.link_file().is_some_and(|link_file| {
+        platform::probe_link(&storage.path, &addons_game_folder_path, link_file)
+      })
+    };
+
+    DeployCapabilities {
+      symlink: probe(DeployMethod::Symlink),
+      hardlink: probe(DeployMethod::Hardlink),
+      reflink: probe(DeployMethod::Reflink),
+    }
+  }
+
+  pub fn is_symlink_available(&self) -> bool {
+    self.deploy_capabilities().symlink
+  }
+
+  fn hash_index_path(&self) -> Option<PathBuf> {
+    self
+      .data_path
+      .as_ref()
+      .map(|path| path.join("hash_index.json"))
+  }
+
+  /// Stored and deployed files of a managed addon, data chunks included.
+  fn addon_file_pairs(
+    &self,
+    addon: &DeployedAddon,
+  ) -> Result<(Vec<PathBuf>, Vec<PathBuf>), AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+    let storage = self.storage()?;
+
+    let stored_files = std::iter::once(addon.file_name.clone())
+      .chain(storage.chunk_files(&addon.file_name))
+      .map(|file_name| storage.addon_path(&file_name))
+      .collect();
+
+    let deployed_files = std::iter::once(addon.deployed_file_name.clone())
+      .chain(vpk::find_chunk_files(
+        &addons_game_folder_path,
+        &addon.deployed_file_name,
+      ))
+      .map(|file_name| addons_game_folder_path.join(file_name))
+      .collect();
+
+    Ok((stored_files, deployed_files))
+  }
+
+  fn drift_state(
+    &self,
+    index: &mut HashIndex,
+    addon: &DeployedAddon,
+  ) -> Result<(DriftState, Option<AddonLocation>), AddonError> {
+    if !self.storage()?.contains(&addon.file_name) {
+      return Ok((DriftState::Orphaned, None));
+    }
+
+    let (stored_files, deployed_files) = self.addon_file_pairs(addon)?;
+
+    // Symlinks can't drift, there is only one file.
+    if is_same_file(&stored_files[0], &deployed_files[0]) {
+      return Ok((DriftState::InSync, None));
+    }
+
+    let stored_hash = index
+      .hash_files(&stored_files)
+      .map_err(|e| AddonError::CouldNotReadInstallFolder(stored_files[0].clone(), e))?;
+    let deployed_hash = index
+      .hash_files(&deployed_files)
+      .map_err(|e| AddonError::CouldNotReadAddonFolder(deployed_files[0].clone(), e))?;
+
+    if stored_hash == deployed_hash {
+      index.set_synced_hash(&addon.file_name, stored_hash);
+
+      return Ok((DriftState::InSync, None));
+    }
+
+    Ok(match index.synced_hash(&addon.file_name) {
+      Some(synced_hash) if synced_hash == stored_hash => {
+        (DriftState::Stale, Some(AddonLocation::Mounted))
+      }
+      Some(synced_hash) if synced_hash == deployed_hash => {
+        (DriftState::Stale, Some(AddonLocation::Stored))
+      }
+      _ => (DriftState::Diverged, None),
+    })
+  }
+
+  /// Compares the deployed copy of every managed addon with the stored one.
+  /// Hashes are cached in the app data folder, so only changed files are read
+  /// again.
+  pub fn verify_deployment(&self) -> Result<Vec<AddonDrift>, AddonError> {
+    let hash_index_path = self.hash_index_path();
+
+    let mut index = hash_index_path
+      .as_deref()
+      .map(HashIndex::load)
+      .unwrap_or_default();
+
+    let mut drifts = vec![];
+
+    for addon in self.scan_mounted_addons()? {
+      if !addon.managed {
+        continue;
+      }
+
+      let (state, changed) = self.drift_state(&mut index, &addon)?;
+
+      drifts.push(AddonDrift {
+        file_name: addon.file_name,
+        deployed_file_name: addon.deployed_file_name,
+        state,
+        changed,
+      });
+    }
+
+    // The index is only a cache, the result is correct without it.
+    if let Some(hash_index_path) = hash_index_path {
+      let _ = index.save(&hash_index_path);
+    }
+
+    Ok(drifts)
+  }
+
+  /// Replaces one copy of a managed addon with the other, `from` is the copy
+  /// that is kept.
+  pub fn resync_addon(&self, addon_file_name: &str, from: AddonLocation) -> Result<(), AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+    let storage = self.storage()?;
+
+    let addon = self
+      .scan_mounted_addons()?
+      .into_iter()
+      .find(|addon| addon.managed && addon.file_name == addon_file_name)
+      .ok_or_else(|| AddonError::AddonIsNotMounted(addon_file_name.to_string()))?;
+
+    let (stored_files, deployed_files) = self.addon_file_pairs(&addon)?;
+
+    if is_same_file(&stored_files[0], &deployed_files[0]) {
+      return Ok(());
+    }
+
+    let (source_files, replaced_files) = match from {
+      AddonLocation::Stored => {
+        if !storage.contains(addon_file_name) {
+          return Err(AddonError::AddonIsNotInstalled(addon_file_name.to_string()));
+        }
+
+        (stored_files, deployed_files)
+      }
+      AddonLocation::Mounted => (deployed_files, stored_files),
+    };
+
+    // Removed first, writing into a hard link would change both copies.
+    for file_path in &replaced_files {
+      match std::fs::remove_file(file_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+          return Err(match from {
+            AddonLocation::Stored => AddonError::CouldNotWriteAddonFolder(file_path.clone(), e),
+            AddonLocation::Mounted => AddonError::CouldNotWriteInstallFolder(file_path.clone(), e),
+          });
+        }
+        _ => {}
+      }
+    }
+
+    let destination_file_name = |index: usize| match (from, index) {
+      (AddonLocation::Stored, 0) => Some(addons_game_folder_path.join(&addon.deployed_file_name)),
+      (AddonLocation::Stored, index) => {
+        vpk::chunk_file_name(&addon.deployed_file_name, index as u16 - 1)
+          .map(|file_name| addons_game_folder_path.join(file_name))
+      }
+      (AddonLocation::Mounted, 0) => Some(storage.addon_path(addon_file_name)),
+      (AddonLocation::Mounted, index) => vpk::chunk_file_name(addon_file_name, index as u16 - 1)
+        .map(|file_name| storage.addon_path(&file_name)),
+    };
+
+    let files = source_files
+      .iter()
+      .enumerate()
+      .filter_map(|(index, source)| {
+        destination_file_name(index).map(|destination| (source.clone(), destination))
+      })
+      .collect::<Vec<_>>();
+
+    match from {
+      AddonLocation::Stored => self.deploy_files(files)?,
+      AddonLocation::Mounted => self
+        .copy_job
+        .copy_files(&files)
+        .map_err(|e| AddonError::from_copy_error(e, AddonError::CouldNotWriteInstallFolder))?,
+    }
+
+    // The stored addon may have different contents now.
+    if from == AddonLocation::Mounted {
+      if let Ok(classification) = self.classify_stored_addon(storage, addon_file_name) {
+        self.update_addon_metadata([(addon_file_name.to_string(), json!(classification))]);
+      }
+    }
+
+    if let Some(hash_index_path) = self.hash_index_path() {
+      let mut index = HashIndex::load(&hash_index_path);
+
+      if let Ok(hash) = index.hash_files(&source_files) {
+        index.set_synced_hash(addon_file_name, hash);
+
+        let _ = index.save(&hash_index_path);
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn addon_contents(
+    &self,
+    addon_file_name: &str,
+    location: AddonLocation,
+  ) -> Result<AddonContents, AddonError> {
+    let addon_path = match location {
+      AddonLocation::Stored => self.storage()?.addon_path(addon_file_name),
+      AddonLocation::Mounted => {
+        let mounted_addons = self.scan_mounted_addons()?;
+
+        // Stored names first, addons placed by hand go by their deployed name.
+        let mounted_addon = mounted_addons
+          .iter()
+          .find(|mounted_addon| mounted_addon.managed && mounted_addon.file_name == addon_file_name)
+          .or_else(|| {
+            mounted_addons.iter().find(|mounted_addon| {
+              !mounted_addon.managed && mounted_addon.deployed_file_name == addon_file_name
+            })
+          })
+          .ok_or_else(|| AddonError::AddonIsNotMounted(addon_file_name.to_string()))?;
+
+        self
+          .game
+          .as_ref()
+          .ok_or(AddonError::NoGamePath)?
+          .addons_path()
+          .join(&mounted_addon.deployed_file_name)
+      }
+    };
+
+    if !addon_path.exists() {
+      return Err(match location {
+        AddonLocation::Stored => AddonError::AddonIsNotInstalled(addon_file_name.to_string()),
+        AddonLocation::Mounted => AddonError::AddonIsNotMounted(addon_file_name.to_string()),
+      });
+    }
+
+    let directory = vpk::VpkDirectory::open(&addon_path).map_err(AddonError::CouldNotReadAddon)?;
+
+    let total_size = directory.total_size();
+
+    let files = directory
+      .entries
+      .iter()
+      .map(|entry| AddonFileEntry {
+        size: entry.size(),
+        crc: entry.crc,
+        archive_index: entry.archive_index,
+        preload_bytes: entry.preload.len() as u16,
+        path: entry.path.clone(),
+      })
+      .collect::<Vec<_>>();
+
+    Ok(AddonContents {
+      version: directory.header.version,
+      file_count: files.len(),
+      files,
+      total_size,
+    })
+  }
+
+  /// Computes conflicts between mounted addons. When `candidate` (file name,
+  /// path and prospective slot of a stored addon) is given, only conflicts it
+  /// would introduce are reported.
+  fn compute_conflicts(
+    &self,
+    candidate: Option<(&str, &Path, Option<u32>)>,
+  ) -> Result<ConflictReport, AddonError> {
+    let addons_game_folder_path = self.addons_game_folder_path()?;
+
+    let mut addons = vec![];
+    let mut unreadable = vec![];
+
+    for mounted_addon in self.scan_mounted_addons()? {
+      if candidate.is_some_and(|(candidate_name, _, _)| {
+        mounted_addon.managed && candidate_name == mounted_addon.file_name
+      }) {
+        continue;
+      }
+
+      match read_addon_files(&addons_game_folder_path.join(&mounted_addon.deployed_file_name)) {
+        Ok(files) => addons.push(AddonFiles {
+          addon: mounted_addon.file_name,
+          slot: mounted_addon.slot,
+          files,
+        }),
+        Err(_) => unreadable.push(mounted_addon.file_name),
+      }
+    }
+
+    if let Some((candidate_name, candidate_path, candidate_slot)) = candidate {
+      let files = read_addon_files(candidate_path).map_err(AddonError::CouldNotReadAddon)?;
+
+      addons.push(AddonFiles {
+        addon: candidate_name.to_string(),
+        slot: candidate_slot,
+        files,
+      });
+    }
+
+    conflicts::sort_by_priority(&mut addons);
+
+    let mut conflicts = conflicts::find_conflicts(&addons);
+
+    if let Some((candidate_name, _, _)) = candidate {
+      conflicts.retain(|conflict| conflict.addons.iter().any(|addon| addon == candidate_name));
+    }
+
+    Ok(ConflictReport {
+      conflicts,
+      unreadable,
+    })
+  }
+
+  pub fn detect_conflicts(
+    &self,
+    preview_addon_file_name: Option<&str>,
+  ) -> Result<ConflictReport, AddonError> {
+    let Some(preview_addon_file_name) = preview_addon_file_name else {
+      return self.compute_conflicts(None);
+    };
+
+    let preview_addon_path = self.storage()?.addon_path(preview_addon_file_name);
+
+    if !preview_addon_path.exists() {
+      return Err(AddonError::AddonIsNotInstalled(
+        preview_addon_file_name.to_string(),
+      ));
+    }
+
+    let mounted_addons = self.scan_mounted_addons()?;
+
+    let preview_slot = match mounted_addons.iter().find(|mounted_addon| {
+      mounted_addon.managed && mounted_addon.file_name == preview_addon_file_name
+    }) {
+      Some(mounted_addon) => mounted_addon.slot,
+      None => load_order::next_free_slot(
+        &mounted_addons
+          .iter()
+          .filter_map(|mounted_addon| mounted_addon.slot)
+          .collect::<Vec<_>>(),
+      ),
+    };
+
+    self.compute_conflicts(Some((
+      preview_addon_file_name,
+      &preview_addon_path,
+      preview_slot,
+    )))
+  }
+
+  pub fn conflict_policy(&self) -> ConflictPolicy {
+    self
+      .read_config("conflict_policy")
+      .unwrap_or(ConflictPolicy::Warn)
+  }
+
+  pub fn set_conflict_policy(&self, conflict_policy: ConflictPolicy) {
+    self.write_config("conflict_policy", conflict_policy);
+  }
+
+  pub fn list_profiles(&self) -> Vec<Profile> {
+    self.read_config("profiles").unwrap_or_default()
+  }
+
+  fn write_profiles(&self, profiles: &[Profile]) {
+    self.write_config("profiles", profiles);
+  }
+
+  pub fn create_profile(&self, name: &str, addons: Vec<String>) -> Result<(), ProfileError> {
+    let name = validate_profile_name(name)?;
+
+    let mut profiles = self.list_profiles();
+
+    if profiles.iter().any(|profile| profile.name == name) {
+      return Err(ProfileError::ProfileAlreadyExists);
+    }
+
+    profiles.push(Profile { name, addons });
+
+    self.write_profiles(&profiles);
+
+    Ok(())
+  }
+
+  /// Replaces the addons of a profile, optionally renaming it.
+  pub fn update_profile(
+    &self,
+    name: &str,
+    addons: Vec<String>,
+    new_name: Option<&str>,
+  ) -> Result<(), ProfileError> {
+    let mut profiles = self.list_profiles();
+
+    if let Some(new_name) = new_name {
+      let new_name = validate_profile_name(new_name)?;
+
+      if new_name != name && profiles.iter().any(|profile| profile.name == new_name) {
+        return Err(ProfileError::ProfileAlreadyExists);
+      }
+    }
+
+    let profile = profiles
+      .iter_mut()
+      .find(|profile| profile.name == name)
+      .ok_or(ProfileError::ProfileNotFound)?;
+
+    if let Some(new_name) = new_name {
+      profile.name = validate_profile_name(new_name)?;
+    }
+
+    profile.addons = addons;
+
+    self.write_profiles(&profiles);
+
+    Ok(())
+  }
+
+  pub fn delete_profile(&self, name: &str) -> Result<(), ProfileError> {
+    let mut profiles = self.list_profiles();
+
+    let profile_count = profiles.len();
+
+    profiles.retain(|profile| profile.name != name);
+
+    if profiles.len() == profile_count {
+      return Err(ProfileError::ProfileNotFound);
+    }
+
+    self.write_profiles(&profiles);
+
+    Ok(())
+  }
+
+  /// Mounts exactly the addons of a profile, in profile order. Only addons that
+  /// differ from the current state are mounted or unmounted, and addons not
+  /// deployed by the manager are left alone. Profile addons that are no longer
+  /// stored are skipped and reported, any other failure rolls the whole profile
+  /// back.
+  pub fn apply_profile(&self, name: &str) -> Result<ProfileApplyReport, ProfileError> {
+    let profile = self
+      .list_profiles()
+      .into_iter()
+      .find(|profile| profile.name == name)
+      .ok_or(ProfileError::ProfileNotFound)?;
+
+    let transaction = self
+      .begin_transaction(
+        Operation::ApplyProfile {
+          profile: profile.name.clone(),
+        },
+        vec![],
+      )
+      .map_err(ProfileError::Addon)?;
+
+    let result = self.apply_profile_addons(&profile);
+
+    self
+      .finish_transaction(&transaction, result)
+      .map_err(ProfileError::Addon)
+  }
+
+  fn apply_profile_addons(&self, profile: &Profile) -> Result<ProfileApplyReport, AddonError> {
+    let storage = self.storage()?;
+    let managed_addons = self.list_mounted_addons()?;
+    let delta = profiles::delta(&profile.addons, &managed_addons);
+
+    let mut results = vec![];
+
+    for addon in delta.unmount {
+      self.unmount_addon(&addon)?;
+
+      results.push(ProfileAddonResult {
+        addon,
+        action: ProfileAction::Unmount,
+        error: None,
+        conflicts: vec![],
+      });
+    }
+
+    for addon in delta.mount {
+      // Profiles keep the names of addons deleted from the storage since.
+      if !storage.contains(&addon) {
+        let error = AddonError::AddonIsNotInstalled(addon.clone()).payload();
+
+        results.push(ProfileAddonResult {
+          addon,
+          action: ProfileAction::Mount,
+          error: Some(error),
+          conflicts: vec![],
+        });
+
+        continue;
+      }
+
+      let conflicts = self.mount_addon(&addon)?;
+
+      results.push(ProfileAddonResult {
+        addon,
+        action: ProfileAction::Mount,
+        error: None,
+        conflicts,
+      });
+    }
+
+    let managed_addons = self.list_mounted_addons()?;
+    let order = profiles::load_order(&profile.addons, &managed_addons);
+
+    if order != managed_addons {
+      self.reorder_mounted_addons(order)?;
+    }
+
+    Ok(ProfileApplyReport { results })
+  }
 }
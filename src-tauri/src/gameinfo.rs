@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+
+use crate::keyvalues::Block;
+
+pub const SEARCH_PATHS_BLOCK: [&str; 3] = ["GameInfo", "FileSystem", "SearchPaths"];
+
+const ADDONS_GAME_PATH: &str = "citadel/addons";
+const BASE_GAME_PATHS: [&str; 2] = ["citadel", "core"];
+const MOD_PATH: &str = "citadel";
+const WRITE_PATH: &str = "citadel";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SearchPathsState {
+  Vanilla,
+  Modded,
+  Custom,
+}
+
+/// A difference between the `SearchPaths` block and what the manager expects.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub enum SearchPathsDeviation {
+  /// An entry that is neither part of the vanilla file nor added by the patch.
+  ExtraEntry { key: String, value: String },
+  /// A vanilla `Game` path is missing.
+  MissingGamePath { value: String },
+  /// Vanilla `Game` paths are present but in a different order.
+  GamePathsReordered,
+  /// `Mod` or `Write` is set to a value other than the one the patch uses.
+  UnexpectedValue {
+    key: String,
+    expected: String,
+    actual: String,
+  },
+  /// `citadel/addons` comes after `citadel`, so addons can't override game files.
+  AddonsPathAfterGamePath,
+  /// Some, but not all, of the patch entries are present.
+  MissingPatchEntry { key: String, value: String },
+}
+
+/// Entries inserted by [`patch`], so that [`unpatch`] removes those and not
+/// the same entries the file already had.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PatchedEntries {
+  #[serde(rename = "addonsPath")]
+  pub addons_path: bool,
+  #[serde(rename = "mod")]
+  pub mod_path: bool,
+  pub write: bool,
+}
+
+impl PatchedEntries {
+  /// Entries inserted by either patch.
+  pub fn union(self, other: PatchedEntries) -> PatchedEntries {
+    PatchedEntries {
+      addons_path: self.addons_path || other.addons_path,
+      mod_path: self.mod_path || other.mod_path,
+      write: self.write || other.write,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchPathsReport {
+  pub state: SearchPathsState,
+  pub deviations: Vec<SearchPathsDeviation>,
+}
+
+fn entries(block: &Block) -> Vec<(&str, Option<&str>)> {
+  block
+    .nodes()
+    .iter()
+    .map(|node| (node.key(), node.value()))
+    .collect()
+}
+
+fn position_of(block: &Block, key: &str, value: &str) -> Option<usize> {
+  block
+    .nodes()
+    .iter()
+    .position(|node| node.key().eq_ignore_ascii_case(key) && node.value() == Some(value))
+}
+
+/// Compares the `SearchPaths` block against the vanilla and patched layouts.
+pub fn analyze(block: &Block) -> SearchPathsReport {
+  let mut deviations = vec![];
+
+  let mut base_game_paths = vec![];
+  let mut addons_paths = 0;
+
+  let mut mod_values = vec![];
+  let mut write_values = vec![];
+
+  for (key, value) in entries(block) {
+    let Some(value) = value else {
+      deviations.push(SearchPathsDeviation::ExtraEntry {
+        key: key.to_string(),
+        value: "{...}".to_string(),
+      });
+
+      continue;
+    };
+
+    if key.eq_ignore_ascii_case("Game") {
+      if value == ADDONS_GAME_PATH {
+        addons_paths += 1;
+      } else if BASE_GAME_PATHS.contains(&value) && !base_game_paths.contains(&value) {
+        base_game_paths.push(value);
+      } else {
+        deviations.push(SearchPathsDeviation::ExtraEntry {
+          key: key.to_string(),
+          value: value.to_string(),
+        });
+      }
+    } else if key.eq_ignore_ascii_case("Mod") {
+      mod_values.push(value);
+    } else if key.eq_ignore_ascii_case("Write") {
+      write_values.push(value);
+    } else {
+      deviations.push(SearchPathsDeviation::ExtraEntry {
+        key: key.to_string(),
+        value: value.to_string(),
+      });
+    }
+  }
+
+  for game_path in BASE_GAME_PATHS {
+    if !base_game_paths.contains(&game_path) {
+      deviations.push(SearchPathsDeviation::MissingGamePath {
+        value: game_path.to_string(),
+      });
+    }
+  }
+
+  if base_game_paths.len() == BASE_GAME_PATHS.len() && base_game_paths != BASE_GAME_PATHS {
+    deviations.push(SearchPathsDeviation::GamePathsReordered);
+  }
+
+  if addons_paths > 1 {
+    deviations.push(SearchPathsDeviation::ExtraEntry {
+      key: "Game".to_string(),
+      value: ADDONS_GAME_PATH.to_string(),
+    });
+  }
+
+  for (key, values, expected) in [
+    ("Mod", &mod_values, MOD_PATH),
+    ("Write", &write_values, WRITE_PATH),
+  ] {
+    for (index, value) in values.iter().enumerate() {
+      if index > 0 {
+        deviations.push(SearchPathsDeviation::ExtraEntry {
+          key: key.to_string(),
+          value: value.to_string(),
+        });
+      } else if *value != expected {
+        deviations.push(SearchPathsDeviation::UnexpectedValue {
+          key: key.to_string(),
+          expected: expected.to_string(),
+          actual: value.to_string(),
+        });
+      }
+    }
+  }
+
+  let addons_position = position_of(block, "Game", ADDONS_GAME_PATH);
+  let citadel_position = position_of(block, "Game", BASE_GAME_PATHS[0]);
+
+  if let (Some(addons_position), Some(citadel_position)) = (addons_position, citadel_position) {
+    if addons_position > citadel_position {
+      deviations.push(SearchPathsDeviation::AddonsPathAfterGamePath);
+    }
+  }
+
+  let patch_entries = [
+    ("Game", ADDONS_GAME_PATH, addons_paths > 0),
+    ("Mod", MOD_PATH, mod_values.first() == Some(&MOD_PATH)),
+    (
+      "Write",
+      WRITE_PATH,
+      write_values.first() == Some(&WRITE_PATH),
+    ),
+  ];
+
+  let applied = patch_entries
+    .iter()
+    .filter(|(_, _, present)| *present)
+    .count();
+
+  if applied > 0 && applied < patch_entries.len() {
+    for (key, value, present) in patch_entries {
+      if !present {
+        deviations.push(SearchPathsDeviation::MissingPatchEntry {
+          key: key.to_string(),
+          value: value.to_string(),
+        });
+      }
+    }
+  }
+
+  let state = if !deviations.is_empty() {
+    SearchPathsState::Custom
+  } else if applied == 0 {
+    SearchPathsState::Vanilla
+  } else {
+    SearchPathsState::Modded
+  };
+
+  SearchPathsReport { state, deviations }
+}
+
+/// Adds the entries needed to load addons on top of the existing block:
+/// `citadel/addons` before `citadel` in `Game`, plus `Mod` and `Write` when
+/// they are missing. Entries that are already there are left untouched, an
+/// existing `citadel/addons` is only moved. Returns the inserted entries.
+pub fn patch(block: &mut Block) -> PatchedEntries {
+  let mut patched = PatchedEntries::default();
+
+  let addons_position = position_of(block, "Game", ADDONS_GAME_PATH);
+  let citadel_position = position_of(block, "Game", BASE_GAME_PATHS[0]);
+
+  match (addons_position, citadel_position) {
+    (Some(addons_position), Some(citadel_position)) if addons_position > citadel_position => {
+      block.remove(addons_position);
+      block.insert(citadel_position, "Game", ADDONS_GAME_PATH);
+    }
+    (None, Some(citadel_position)) => {
+      block.insert(citadel_position, "Game", ADDONS_GAME_PATH);
+
+      patched.addons_path = true;
+    }
+    (None, None) => {
+      let first_game_position = block
+        .nodes()
+        .iter()
+        .position(|node| node.key().eq_ignore_ascii_case("Game"))
+        .unwrap_or(block.nodes().len());
+
+      block.insert(first_game_position, "Game", ADDONS_GAME_PATH);
+
+      patched.addons_path = true;
+    }
+    _ => {}
+  }
+
+  if block.find("Mod").is_none() {
+    block.insert(0, "Mod", MOD_PATH);
+
+    patched.mod_path = true;
+  }
+
+  if block.find("Write").is_none() {
+    let mod_position = block
+      .nodes()
+      .iter()
+      .position(|node| node.key().eq_ignore_ascii_case("Mod"))
+      .map_or(0, |position| position + 1);
+
+    block.insert(mod_position, "Write", WRITE_PATH);
+
+    patched.write = true;
+  }
+
+  patched
+}
+
+/// Removes the `patched` entries inserted by [`patch`], leaving everything
+/// else in place, including the same entries when the file had them before.
+pub fn unpatch(block: &mut Block, patched: PatchedEntries) {
+  for (key, value, inserted) in [
+    ("Game", ADDONS_GAME_PATH, patched.addons_path),
+    ("Mod", MOD_PATH, patched.mod_path),
+    ("Write", WRITE_PATH, patched.write),
+  ] {
+    if let Some(position) = inserted.then(|| position_of(block, key, value)).flatten() {
+      block.remove(position);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::keyvalues::Document;
+
+  const VANILLA: &str = include_str!("../tests/fixtures/gameinfo/vanilla.gi");
+  const MODDED: &str = include_str!("../tests/fixtures/gameinfo/modded.gi");
+
+  fn search_paths(document: &mut Document) -> &mut Block {
+    document
+      .root_mut()
+      .find_block_mut(&SEARCH_PATHS_BLOCK)
+      .unwrap()
+  }
+
+  #[test]
+  fn reports_shipped_files() {
+    let mut vanilla = Document::parse(VANILLA).unwrap();
+    let mut modded = Document::parse(MODDED).unwrap();
+
+    assert_eq!(
+      analyze(search_paths(&mut vanilla)).state,
+      SearchPathsState::Vanilla
+    );
+    assert_eq!(
+      analyze(search_paths(&mut modded)).state,
+      SearchPathsState::Modded
+    );
+  }
+
+  #[test]
+  fn patch_and_unpatch_round_trip() {
+    let mut document = Document::parse(VANILLA).unwrap();
+
+    let patched = patch(search_paths(&mut document));
+
+    assert_eq!(
+      analyze(search_paths(&mut document)).state,
+      SearchPathsState::Modded
+    );
+
+    let patched = patched.union(patch(search_paths(&mut document)));
+
+    unpatch(search_paths(&mut document), patched);
+
+    assert_eq!(document.to_string(), VANILLA);
+  }
+
+  #[test]
+  fn unpatches_only_inserted_entries() {
+    let mut document = Document::parse(MODDED).unwrap();
+
+    let patched = patch(search_paths(&mut document));
+
+    assert_eq!(patched, PatchedEntries::default());
+
+    unpatch(search_paths(&mut document), patched);
+
+    assert_eq!(document.to_string(), MODDED);
+
+    // `Mod` was there before, only `Game` and `Write` are removed again.
+    let source = VANILLA.replace(
+      "\t\t\tGame\t\t\t\tcitadel\n",
+      "\t\t\tMod\t\t\t\tcitadel\n\t\t\tGame\t\t\t\tcitadel\n",
+    );
+    let mut document = Document::parse(&source).unwrap();
+
+    let patched = patch(search_paths(&mut document));
+
+    assert_eq!(
+      patched,
+      PatchedEntries {
+        addons_path: true,
+        mod_path: false,
+        write: true,
+      }
+    );
+
+    unpatch(search_paths(&mut document), patched);
+
+    assert_eq!(document.to_string(), source);
+  }
+
+  #[test]
+  fn keeps_custom_entries() {
+    let source = VANILLA.replace(
+      "\t\t\tGame\t\t\t\tcore\n",
+      "\t\t\tGame\t\t\t\tcore\n\t\t\tGame\t\t\t\tcitadel_extra\n",
+    );
+    let mut document = Document::parse(&source).unwrap();
+
+    let report = analyze(search_paths(&mut document));
+
+    assert_eq!(report.state, SearchPathsState::Custom);
+    assert_eq!(
+      report.deviations,
+      vec![SearchPathsDeviation::ExtraEntry {
+        key: "Game".to_string(),
+        value: "citadel_extra".to_string()
+      }]
+    );
+
+    let patched = patch(search_paths(&mut document));
+
+    let block = search_paths(&mut document);
+
+    assert_eq!(
+      block.values("Game"),
+      vec!["citadel/addons", "citadel", "core", "citadel_extra"]
+    );
+
+    unpatch(block, patched);
+
+    assert_eq!(document.to_string(), source);
+  }
+}
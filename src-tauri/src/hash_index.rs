@@ -0,0 +1,150 @@
+use std::{
+  collections::BTreeMap,
+  fs::File,
+  io::{self, Read},
+  path::{Path, PathBuf},
+  time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::backups;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct FileHash {
+  size: u64,
+  modified: SystemTime,
+  hash: String,
+}
+
+/// Cache of file hashes, a file is only hashed again when its size or
+/// modification time changed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HashIndex {
+  files: BTreeMap<PathBuf, FileHash>,
+  /// Hash both copies of a deployed addon had when they were last in sync, by
+  /// stored file name.
+  #[serde(rename = "syncedHashes")]
+  synced_hashes: BTreeMap<String, String>,
+}
+
+impl HashIndex {
+  /// Reads the index, an unreadable index is treated as empty.
+  pub fn load(path: &Path) -> Self {
+    std::fs::read(path)
+      .ok()
+      .and_then(|content| serde_json::from_slice(&content).ok())
+      .unwrap_or_default()
+  }
+
+  /// Writes the index, leaving out files that no longer exist.
+  pub fn save(&mut self, path: &Path) -> io::Result<()> {
+    self.files.retain(|file_path, _| file_path.exists());
+
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    let content =
+      serde_json::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    backups::write_atomic(path, &content)
+  }
+
+  pub fn hash_file(&mut self, path: &Path) -> io::Result<String> {
+    let metadata = std::fs::metadata(path)?;
+
+    let size = metadata.len();
+    let modified = metadata.modified()?;
+
+    if let Some(file_hash) = self.files.get(path) {
+      if file_hash.size == size && file_hash.modified == modified {
+        return Ok(file_hash.hash.clone());
+      }
+    }
+
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+
+    loop {
+      match file.read(&mut buffer) {
+        Ok(0) => break,
+        Ok(read) => hasher.update(&buffer[..read]),
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+        Err(e) => return Err(e),
+      }
+    }
+
+    let hash = format!("{:x}", hasher.finalize());
+
+    self.files.insert(
+      path.to_path_buf(),
+      FileHash {
+        size,
+        modified,
+        hash: hash.clone(),
+      },
+    );
+
+    Ok(hash)
+  }
+
+  /// Hash of several files together, in order, for addons with data chunks.
+  pub fn hash_files(&mut self, paths: &[PathBuf]) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    for path in paths {
+      hasher.update(self.hash_file(path)?);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+  }
+
+  pub fn synced_hash(&self, addon_file_name: &str) -> Option<&str> {
+    self.synced_hashes.get(addon_file_name).map(String::as_str)
+  }
+
+  pub fn set_synced_hash(&mut self, addon_file_name: &str, hash: String) {
+    self.synced_hashes.insert(addon_file_name.to_string(), hash);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reuses_hashes_of_unchanged_files() {
+    let folder =
+      std::env::temp_dir().join(format!("citadel-cm-hash-index-test-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    let file_path = folder.join("hud.vpk");
+    let index_path = folder.join("hash_index.json");
+
+    std::fs::write(&file_path, b"hud").unwrap();
+
+    let mut index = HashIndex::load(&index_path);
+
+    let hash = index.hash_file(&file_path).unwrap();
+
+    index.save(&index_path).unwrap();
+
+    let mut index = HashIndex::load(&index_path);
+
+    // A cached hash is trusted as long as size and modification time match.
+    index.files.get_mut(&file_path).unwrap().hash = "cached".to_string();
+
+    assert_eq!(index.hash_file(&file_path).unwrap(), "cached");
+
+    index.files.get_mut(&file_path).unwrap().size = 0;
+
+    assert_eq!(index.hash_file(&file_path).unwrap(), hash);
+
+    std::fs::remove_dir_all(folder).unwrap();
+  }
+}
@@ -0,0 +1,103 @@
+use std::{
+  io,
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backups;
+use crate::game::DeployMethod;
+
+/// An operation that changes several files and has to be undone as a whole
+/// when it fails halfway.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum Operation {
+  SetDeployMethod {
+    from: DeployMethod,
+    to: DeployMethod,
+  },
+  ApplyProfile {
+    profile: String,
+  },
+  MigrateStorage {
+    from: PathBuf,
+    to: PathBuf,
+  },
+}
+
+/// Intent record of a running operation, written before the operation
+/// touches any file and removed once it is done. A journal left behind means
+/// the operation was interrupted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Transaction {
+  pub operation: Operation,
+  /// Managed addons installed before the operation, highest priority first.
+  pub mounted: Vec<String>,
+  /// Files the operation is going to create, removed on rollback.
+  #[serde(rename = "createdFiles")]
+  pub created_files: Vec<PathBuf>,
+}
+
+/// Reads the journal, `None` when no operation is in progress.
+pub fn read(path: &Path) -> io::Result<Option<Transaction>> {
+  match std::fs::read(path) {
+    Ok(content) => serde_json::from_slice(&content)
+      .map(Some)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e),
+  }
+}
+
+pub fn write(path: &Path, transaction: &Transaction) -> io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+
+  let content = serde_json::to_vec_pretty(transaction)
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+  backups::write_atomic(path, &content)
+}
+
+pub fn clear(path: &Path) -> io::Result<()> {
+  match std::fs::remove_file(path) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trips_and_clears() {
+    let path = std::env::temp_dir()
+      .join(format!("citadel-cm-journal-test-{}", std::process::id()))
+      .join("journal.json");
+
+    assert_eq!(read(&path).unwrap(), None);
+
+    let transaction = Transaction {
+      operation: Operation::SetDeployMethod {
+        from: DeployMethod::Copy,
+        to: DeployMethod::Symlink,
+      },
+      mounted: vec!["hud.vpk".to_string()],
+      created_files: vec![],
+    };
+
+    write(&path, &transaction).unwrap();
+
+    assert_eq!(read(&path).unwrap(), Some(transaction));
+
+    clear(&path).unwrap();
+    clear(&path).unwrap();
+
+    assert_eq!(read(&path).unwrap(), None);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+  }
+}
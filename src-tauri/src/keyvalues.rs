@@ -0,0 +1,555 @@
+//! Lossless KeyValues1 document model.
+//!
+//! Every byte of the source (comments, whitespace, line endings, quoting) is kept
+//! alongside the parsed nodes, so an unmodified document is written back exactly
+//! as it was read, and edits only touch the nodes they change.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum KeyValuesError {
+  UnexpectedEndOfFile,
+  UnexpectedCharacter { line: usize, character: char },
+  UnterminatedString { line: usize },
+}
+
+impl fmt::Display for KeyValuesError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      KeyValuesError::UnexpectedEndOfFile => write!(f, "Unexpected end of file"),
+      KeyValuesError::UnexpectedCharacter { line, character } => {
+        write!(f, "Unexpected character '{}' on line {}", character, line)
+      }
+      KeyValuesError::UnterminatedString { line } => {
+        write!(f, "Unterminated string starting on line {}", line)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+  text: String,
+  quoted: bool,
+}
+
+impl Token {
+  fn write(&self, out: &mut String) {
+    if self.quoted {
+      out.push('"');
+      out.push_str(&self.text);
+      out.push('"');
+    } else {
+      out.push_str(&self.text);
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+  String(Token),
+  Block(Block),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+  /// Whitespace and comments before the key.
+  leading: String,
+  key: Token,
+  /// Whitespace and comments between the key and the value.
+  separator: String,
+  value: Value,
+  /// Trailing `[$CONDITION]` with the whitespace before it.
+  condition: Option<(String, String)>,
+}
+
+impl Node {
+  pub fn key(&self) -> &str {
+    &self.key.text
+  }
+
+  pub fn value(&self) -> Option<&str> {
+    match &self.value {
+      Value::String(token) => Some(&token.text),
+      Value::Block(_) => None,
+    }
+  }
+
+  pub fn block(&self) -> Option<&Block> {
+    match &self.value {
+      Value::String(_) => None,
+      Value::Block(block) => Some(block),
+    }
+  }
+
+  pub fn block_mut(&mut self) -> Option<&mut Block> {
+    match &mut self.value {
+      Value::String(_) => None,
+      Value::Block(block) => Some(block),
+    }
+  }
+
+  /// Replaces a string value, keeping its quoting style.
+  pub fn set_value(&mut self, value: &str) {
+    match &mut self.value {
+      Value::String(token) => token.text = value.to_string(),
+      Value::Block(_) => {
+        self.value = Value::String(Token {
+          text: value.to_string(),
+          quoted: self.key.quoted,
+        })
+      }
+    }
+  }
+
+  fn write(&self, out: &mut String) {
+    out.push_str(&self.leading);
+    self.key.write(out);
+    out.push_str(&self.separator);
+
+    match &self.value {
+      Value::String(token) => token.write(out),
+      Value::Block(block) => {
+        out.push('{');
+        block.write(out);
+        out.push('}');
+      }
+    }
+
+    if let Some((whitespace, condition)) = &self.condition {
+      out.push_str(whitespace);
+      out.push_str(condition);
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+  nodes: Vec<Node>,
+  /// Whitespace and comments after the last node.
+  trailing: String,
+}
+
+impl Block {
+  pub fn nodes(&self) -> &[Node] {
+    &self.nodes
+  }
+
+  /// Finds the first node with the given key. Keys are case-insensitive.
+  pub fn find(&self, key: &str) -> Option<&Node> {
+    self
+      .nodes
+      .iter()
+      .find(|node| node.key().eq_ignore_ascii_case(key))
+  }
+
+  pub fn find_mut(&mut self, key: &str) -> Option<&mut Node> {
+    self
+      .nodes
+      .iter_mut()
+      .find(|node| node.key().eq_ignore_ascii_case(key))
+  }
+
+  /// Follows nested blocks by key, e.g. `["GameInfo", "FileSystem", "SearchPaths"]`.
+  pub fn find_block(&self, path: &[&str]) -> Option<&Block> {
+    path
+      .iter()
+      .try_fold(self, |block, key| block.find(key)?.block())
+  }
+
+  pub fn find_block_mut(&mut self, path: &[&str]) -> Option<&mut Block> {
+    path
+      .iter()
+      .try_fold(self, |block, key| block.find_mut(key)?.block_mut())
+  }
+
+  /// Values of all string nodes with the given key, in document order.
+  pub fn values(&self, key: &str) -> Vec<&str> {
+    self
+      .nodes
+      .iter()
+      .filter(|node| node.key().eq_ignore_ascii_case(key))
+      .filter_map(Node::value)
+      .collect()
+  }
+
+  /// Inserts a string node at `index`, copying indentation, spacing and quoting
+  /// from the neighbouring node.
+  pub fn insert(&mut self, index: usize, key: &str, value: &str) {
+    let neighbour = self
+      .nodes
+      .get(index)
+      .or_else(|| index.checked_sub(1).and_then(|i| self.nodes.get(i)));
+
+    let (leading, separator, quoted) = match neighbour {
+      Some(neighbour) => (
+        line_indentation(&neighbour.leading).to_string(),
+        neighbour.separator.clone(),
+        neighbour.key.quoted,
+      ),
+      None => (
+        format!("{}\t", line_indentation(&self.trailing)),
+        "\t".to_string(),
+        false,
+      ),
+    };
+
+    self.nodes.insert(
+      index,
+      Node {
+        leading,
+        key: Token {
+          text: key.to_string(),
+          quoted,
+        },
+        separator,
+        value: Value::String(Token {
+          text: value.to_string(),
+          quoted,
+        }),
+        condition: None,
+      },
+    );
+  }
+
+  pub fn remove(&mut self, index: usize) -> Node {
+    self.nodes.remove(index)
+  }
+
+  fn write(&self, out: &mut String) {
+    for node in &self.nodes {
+      node.write(out);
+    }
+
+    out.push_str(&self.trailing);
+  }
+}
+
+/// The last line break of `trivia` together with the indentation after it.
+fn line_indentation(trivia: &str) -> &str {
+  match trivia.rfind('\n') {
+    Some(i) if i > 0 && trivia.as_bytes()[i - 1] == b'\r' => &trivia[i - 1..],
+    Some(i) => &trivia[i..],
+    None => trivia,
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+  root: Block,
+}
+
+impl Document {
+  pub fn parse(source: &str) -> Result<Self, KeyValuesError> {
+    let mut parser = Parser {
+      source,
+      position: 0,
+    };
+
+    let root = parser.parse_block(false)?;
+
+    Ok(Document { root })
+  }
+
+  pub fn root(&self) -> &Block {
+    &self.root
+  }
+
+  pub fn root_mut(&mut self) -> &mut Block {
+    &mut self.root
+  }
+}
+
+impl fmt::Display for Document {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut out = String::new();
+
+    self.root.write(&mut out);
+
+    f.write_str(&out)
+  }
+}
+
+struct Parser<'a> {
+  source: &'a str,
+  position: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn peek(&self) -> Option<u8> {
+    self.source.as_bytes().get(self.position).copied()
+  }
+
+  fn line(&self) -> usize {
+    self.source[..self.position].matches('\n').count() + 1
+  }
+
+  fn unexpected_character(&self) -> KeyValuesError {
+    match self.source[self.position..].chars().next() {
+      Some(character) => KeyValuesError::UnexpectedCharacter {
+        line: self.line(),
+        character,
+      },
+      None => KeyValuesError::UnexpectedEndOfFile,
+    }
+  }
+
+  /// Consumes whitespace and `//` comments.
+  fn parse_trivia(&mut self) -> String {
+    let start = self.position;
+    let bytes = self.source.as_bytes();
+
+    loop {
+      match self.peek() {
+        Some(b) if b.is_ascii_whitespace() => self.position += 1,
+        Some(b'/') if bytes.get(self.position + 1) == Some(&b'/') => {
+          while let Some(b) = self.peek() {
+            if b == b'\n' || b == b'\r' {
+              break;
+            }
+
+            self.position += 1;
+          }
+        }
+        _ => break,
+      }
+    }
+
+    self.source[start..self.position].to_string()
+  }
+
+  fn parse_token(&mut self) -> Result<Token, KeyValuesError> {
+    match self.peek() {
+      None => Err(KeyValuesError::UnexpectedEndOfFile),
+      Some(b'"') => {
+        let line = self.line();
+        let start = self.position + 1;
+
+        self.position = start;
+
+        loop {
+          match self.peek() {
+            None => return Err(KeyValuesError::UnterminatedString { line }),
+            Some(b'\\') => self.position += 2,
+            Some(b'"') => break,
+            Some(_) => self.position += 1,
+          }
+        }
+
+        let text = self.source[start..self.position].to_string();
+
+        self.position += 1;
+
+        Ok(Token { text, quoted: true })
+      }
+      Some(b'{') | Some(b'}') => Err(self.unexpected_character()),
+      Some(_) => {
+        let start = self.position;
+
+        while let Some(b) = self.peek() {
+          if b.is_ascii_whitespace() || matches!(b, b'"' | b'{' | b'}') {
+            break;
+          }
+
+          self.position += 1;
+        }
+
+        Ok(Token {
+          text: self.source[start..self.position].to_string(),
+          quoted: false,
+        })
+      }
+    }
+  }
+
+  fn parse_condition(&mut self) -> Option<(String, String)> {
+    let start = self.position;
+    let whitespace = self.parse_trivia();
+
+    if self.peek() == Some(b'[') {
+      if let Some(len) = self.source[self.position..].find(']') {
+        let condition = self.source[self.position..=self.position + len].to_string();
+
+        self.position += len + 1;
+
+        return Some((whitespace, condition));
+      }
+    }
+
+    self.position = start;
+
+    None
+  }
+
+  fn parse_block(&mut self, nested: bool) -> Result<Block, KeyValuesError> {
+    let mut nodes = vec![];
+
+    loop {
+      let leading = self.parse_trivia();
+
+      match self.peek() {
+        None if nested => return Err(KeyValuesError::UnexpectedEndOfFile),
+        None => {
+          return Ok(Block {
+            nodes,
+            trailing: leading,
+          })
+        }
+        Some(b'}') if nested => {
+          self.position += 1;
+
+          return Ok(Block {
+            nodes,
+            trailing: leading,
+          });
+        }
+        Some(_) => {
+          let key = self.parse_token()?;
+          let separator = self.parse_trivia();
+
+          let value = if self.peek() == Some(b'{') {
+            self.position += 1;
+
+            Value::Block(self.parse_block(true)?)
+          } else {
+            Value::String(self.parse_token()?)
+          };
+
+          let condition = self.parse_condition();
+
+          nodes.push(Node {
+            leading,
+            key,
+            separator,
+            value,
+            condition,
+          });
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const VANILLA: &str = include_str!("../tests/fixtures/gameinfo/vanilla.gi");
+  const MODDED: &str = include_str!("../tests/fixtures/gameinfo/modded.gi");
+
+  const SEARCH_PATHS: [&str; 3] = ["GameInfo", "FileSystem", "SearchPaths"];
+
+  fn round_trip(source: &str) {
+    let document = Document::parse(source).unwrap();
+
+    assert_eq!(document.to_string(), source);
+  }
+
+  #[test]
+  fn round_trips_shipped_files() {
+    round_trip(VANILLA);
+    round_trip(MODDED);
+  }
+
+  #[test]
+  fn round_trips_crlf_and_missing_trailing_newline() {
+    round_trip(&VANILLA.replace('\n', "\r\n"));
+    round_trip(MODDED.trim_end());
+  }
+
+  #[test]
+  fn reads_search_paths() {
+    let vanilla = Document::parse(VANILLA).unwrap();
+    let search_paths = vanilla.root().find_block(&SEARCH_PATHS).unwrap();
+
+    assert_eq!(search_paths.values("Game"), vec!["citadel", "core"]);
+    assert!(search_paths.find("Mod").is_none());
+
+    let modded = Document::parse(MODDED).unwrap();
+    let search_paths = modded.root().find_block(&SEARCH_PATHS).unwrap();
+
+    assert_eq!(
+      search_paths.values("Game"),
+      vec!["citadel/addons", "citadel", "core"]
+    );
+    assert_eq!(search_paths.values("mod"), vec!["citadel"]);
+  }
+
+  #[test]
+  fn editing_search_paths_only_touches_that_block() {
+    let mut document = Document::parse(VANILLA).unwrap();
+    let search_paths = document.root_mut().find_block_mut(&SEARCH_PATHS).unwrap();
+
+    search_paths.insert(0, "Game", "citadel/addons");
+    search_paths.insert(0, "Write", "citadel");
+    search_paths.insert(0, "Mod", "citadel");
+
+    let expected = VANILLA.replace(
+      "\t\t\tGame\t\t\t\tcitadel\n",
+      "\t\t\tMod\t\t\t\tcitadel\n\t\t\tWrite\t\t\t\tcitadel\n\t\t\tGame\t\t\t\tcitadel/addons\n\t\t\tGame\t\t\t\tcitadel\n",
+    );
+
+    assert_eq!(document.to_string(), expected);
+
+    let search_paths = document.root_mut().find_block_mut(&SEARCH_PATHS).unwrap();
+
+    search_paths.remove(2);
+    search_paths.remove(1);
+    search_paths.remove(0);
+
+    assert_eq!(document.to_string(), VANILLA);
+  }
+
+  #[test]
+  fn keeps_crlf_when_inserting() {
+    let source = VANILLA.replace('\n', "\r\n");
+    let mut document = Document::parse(&source).unwrap();
+
+    document
+      .root_mut()
+      .find_block_mut(&SEARCH_PATHS)
+      .unwrap()
+      .insert(0, "Game", "citadel/addons");
+
+    let output = document.to_string();
+
+    assert_eq!(output.matches('\n').count(), output.matches("\r\n").count());
+  }
+
+  #[test]
+  fn keeps_duplicate_keys_and_conditions() {
+    let source = "a\n{\n\tk 1\n\tk 2 [$WIN32]\n\t\"k\" \"3\"\n}\n";
+    let document = Document::parse(source).unwrap();
+
+    assert_eq!(
+      document
+        .root()
+        .find("a")
+        .unwrap()
+        .block()
+        .unwrap()
+        .values("k"),
+      vec!["1", "2", "3"]
+    );
+    assert_eq!(document.to_string(), source);
+  }
+
+  #[test]
+  fn rejects_malformed_documents() {
+    assert!(matches!(
+      Document::parse("a\n{\n\tb c\n"),
+      Err(KeyValuesError::UnexpectedEndOfFile)
+    ));
+    assert!(matches!(
+      Document::parse("a \"b\n"),
+      Err(KeyValuesError::UnterminatedString { line: 1 })
+    ));
+    assert!(matches!(
+      Document::parse("a\n}"),
+      Err(KeyValuesError::UnexpectedCharacter {
+        line: 2,
+        character: '}'
+      })
+    ));
+  }
+}
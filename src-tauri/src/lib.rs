@@ -1,43 +1,103 @@
-use std::{path::PathBuf, sync::Mutex};
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
 
-use game::find_game_path;
+use game::GameInstall;
 use tauri::Manager;
 use tauri_plugin_store::StoreExt;
 
-mod game;
+mod app_manifest;
+mod archive;
+mod backups;
+pub mod categories;
+mod commands;
+pub mod config;
+mod conflicts;
+pub mod copy_job;
+pub mod error;
+pub mod game;
+pub mod gameinfo;
+mod hash_index;
+pub mod journal;
+pub mod keyvalues;
+mod load_order;
+pub mod migration;
+mod platform;
+mod profiles;
+pub mod storage;
+pub mod thumbnails;
+pub mod vpk;
+pub mod vtex;
+pub mod watcher;
 
-struct AppState {
-  path: Option<PathBuf>,
+pub struct AppState {
+  /// Game folder, `None` until found or set by hand.
+  path: Mutex<Option<PathBuf>>,
   install_path: Mutex<Option<PathBuf>>,
+  watcher: Mutex<Option<watcher::Watcher>>,
+  /// Cancel tokens of running copy jobs by job id.
+  copy_jobs: Mutex<HashMap<String, copy_job::CancelToken>>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-  let game_path = find_game_path();
-
   tauri::Builder::default()
     .plugin(tauri_plugin_store::Builder::new().build())
     .plugin(tauri_plugin_dialog::init())
     .manage(AppState {
-      path: game_path,
+      path: Mutex::new(None),
       install_path: Mutex::new(None),
+      watcher: Mutex::new(None),
+      copy_jobs: Mutex::new(HashMap::new()),
     })
     .invoke_handler(tauri::generate_handler![
-      game::get_game_path,
-      game::get_search_paths_state,
-      game::mod_search_paths,
-      game::reset_search_paths,
-      game::get_install_path,
-      game::set_install_path,
-      game::list_installed_addons,
-      game::list_mounted_addons,
-      game::install_addon,
-      game::uninstall_addon,
-      game::mount_addon,
-      game::unmount_addon,
-      game::set_deploy_method,
-      game::is_symlink_available,
-      game::get_deploy_method
+      commands::get_game_path,
+      commands::list_game_installs,
+      commands::set_game_path,
+      commands::detect_game_path,
+      commands::get_search_paths_state,
+      commands::get_search_paths_report,
+      commands::mod_search_paths,
+      commands::reset_search_paths,
+      commands::list_game_info_backups,
+      commands::diff_game_info_backup,
+      commands::restore_game_info_backup,
+      commands::check_game_update,
+      commands::get_game_update_status,
+      commands::set_auto_repatch,
+      commands::get_auto_repatch,
+      commands::get_install_path,
+      commands::plan_storage_migration,
+      commands::set_install_path,
+      commands::list_installed_addons,
+      commands::list_mounted_addons,
+      commands::classify_addons,
+      commands::get_addon_thumbnail,
+      commands::install_addon,
+      commands::list_archive_addons,
+      commands::import_archive,
+      commands::uninstall_addon,
+      commands::mount_addon,
+      commands::unmount_addon,
+      commands::unmount_unmanaged_addon,
+      commands::set_deploy_method,
+      commands::is_symlink_available,
+      commands::get_deploy_capabilities,
+      commands::get_deploy_method,
+      commands::get_addon_contents,
+      commands::detect_conflicts,
+      commands::set_conflict_policy,
+      commands::get_conflict_policy,
+      commands::get_load_order,
+      commands::reorder_mounted_addons,
+      commands::list_profiles,
+      commands::create_profile,
+      commands::update_profile,
+      commands::delete_profile,
+      commands::apply_profile,
+      commands::cancel_job,
+      commands::verify_deployment,
+      commands::resync_addon,
+      commands::repair_links,
+      commands::adopt_unmanaged_addons
     ])
     .setup(|app| {
       if cfg!(debug_assertions) {
@@ -66,6 +126,15 @@ pub fn run() {
         *install_path = Some(PathBuf::from(&config_install_path));
       }
 
+      *app.state::<AppState>().path.lock().unwrap() =
+        GameInstall::resolve(&config_store).map(|game| game.path);
+
+      commands::recover_interrupted_operation(app.handle());
+
+      commands::restart_watcher(app.handle());
+
+      commands::watch_game_updates(app.handle().clone());
+
       Ok(())
     })
     .run(tauri::generate_context!())
@@ -0,0 +1,253 @@
+use std::{
+  io,
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Highest slot that fits the two digit `pakNN_dir.vpk` naming scheme.
+pub const MAX_SLOT: u32 = 99;
+
+/// A stored addon deployed to the game under a `pakNN_dir.vpk` slot.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MountedAddon {
+  pub addon: String,
+  pub slot: u32,
+}
+
+impl MountedAddon {
+  pub fn deployed_file_name(&self) -> String {
+    slot_file_name(self.slot)
+  }
+}
+
+/// Returns the load slot of an addon named `pakNN_dir.vpk`.
+///
+/// Deadlock only loads addons following this pattern, in ascending slot order,
+/// and the first pak providing a file wins, so lower numbers take priority.
+pub fn pak_slot(file_name: &str) -> Option<u32> {
+  let number = file_name.strip_prefix("pak")?.strip_suffix("_dir.vpk")?;
+
+  if number.len() != 2 || !number.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+
+  number.parse().ok().filter(|&slot| slot > 0)
+}
+
+pub fn slot_file_name(slot: u32) -> String {
+  format!("pak{:02}_dir.vpk", slot)
+}
+
+/// Picks a slot for a newly mounted addon. New addons get the lowest priority,
+/// so the slot after the highest occupied one is preferred, falling back to
+/// the first gap once the end of the range is reached.
+pub fn next_free_slot(occupied: &[u32]) -> Option<u32> {
+  let highest = occupied.iter().copied().max().unwrap_or(0);
+
+  if highest < MAX_SLOT {
+    return Some(highest + 1);
+  }
+
+  (1..=MAX_SLOT).find(|slot| !occupied.contains(slot))
+}
+
+/// Assigns `count` slots in ascending order, skipping `reserved` ones.
+pub fn assign_slots(count: usize, reserved: &[u32]) -> Option<Vec<u32>> {
+  let slots = (1..=MAX_SLOT)
+    .filter(|slot| !reserved.contains(slot))
+    .take(count)
+    .collect::<Vec<_>>();
+
+  if slots.len() == count {
+    Some(slots)
+  } else {
+    None
+  }
+}
+
+/// Renames files inside `folder` as a single operation.
+///
+/// Files are first moved to temporary names so that swapping slots between
+/// addons never overwrites anything. If any rename fails, every file is moved
+/// back to its original name and the error is returned with the path that
+/// could not be renamed.
+pub fn rename_all(folder: &Path, renames: &[(String, String)]) -> Result<(), (PathBuf, io::Error)> {
+  let renames = renames
+    .iter()
+    .filter(|(from, to)| from != to)
+    .map(|(from, to)| (from.as_str(), format!("{}.reorder", from), to.as_str()))
+    .collect::<Vec<_>>();
+
+  let mut moved_to_temp = vec![];
+
+  for (from, temp, _) in &renames {
+    if let Err(e) = std::fs::rename(folder.join(from), folder.join(temp)) {
+      for (from, temp) in moved_to_temp.iter().rev() {
+        let _ = std::fs::rename(folder.join(temp), folder.join(from));
+      }
+
+      return Err((folder.join(from), e));
+    }
+
+    moved_to_temp.push((*from, temp.as_str()));
+  }
+
+  let mut moved_to_final: Vec<(&str, &str)> = vec![];
+
+  for (_, temp, to) in &renames {
+    let result = if folder.join(to).symlink_metadata().is_ok() {
+      Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", to),
+      ))
+    } else {
+      std::fs::rename(folder.join(temp), folder.join(to))
+    };
+
+    if let Err(e) = result {
+      for (temp, to) in moved_to_final.iter().rev() {
+        let _ = std::fs::rename(folder.join(to), folder.join(temp));
+      }
+
+      for (from, temp) in moved_to_temp.iter().rev() {
+        let _ = std::fs::rename(folder.join(temp), folder.join(from));
+      }
+
+      return Err((folder.join(to), e));
+    }
+
+    moved_to_final.push((temp.as_str(), to));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn folder(name: &str, files: &[&str]) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!(
+      "citadel-cm-load-order-{}-{}",
+      name,
+      std::process::id()
+    ));
+
+    _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    for file in files {
+      std::fs::write(folder.join(file), file).unwrap();
+    }
+
+    folder
+  }
+
+  fn read(folder: &Path, file_name: &str) -> String {
+    std::fs::read_to_string(folder.join(file_name)).unwrap()
+  }
+
+  fn renames(renames: &[(&str, &str)]) -> Vec<(String, String)> {
+    renames
+      .iter()
+      .map(|(from, to)| (from.to_string(), to.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn picks_slots_until_they_run_out() {
+    assert_eq!(next_free_slot(&[]), Some(1));
+    assert_eq!(next_free_slot(&[1, 5]), Some(6));
+    // The end of the range is taken, so the first gap is used.
+    assert_eq!(next_free_slot(&[2, MAX_SLOT]), Some(1));
+
+    let all = (1..=MAX_SLOT).collect::<Vec<_>>();
+
+    assert_eq!(next_free_slot(&all), None);
+
+    assert_eq!(assign_slots(3, &[2]), Some(vec![1, 3, 4]));
+    assert_eq!(
+      assign_slots(MAX_SLOT as usize, &[]).map(|s| s.len()),
+      Some(99)
+    );
+    assert_eq!(assign_slots(MAX_SLOT as usize, &[50]), None);
+  }
+
+  #[test]
+  fn swaps_slots_with_their_chunks() {
+    let folder = folder(
+      "swap",
+      &[
+        "pak01_dir.vpk",
+        "pak01_000.vpk",
+        "pak01_001.vpk",
+        "pak02_dir.vpk",
+      ],
+    );
+
+    rename_all(
+      &folder,
+      &renames(&[
+        ("pak01_dir.vpk", "pak02_dir.vpk"),
+        ("pak01_000.vpk", "pak02_000.vpk"),
+        ("pak01_001.vpk", "pak02_001.vpk"),
+        ("pak02_dir.vpk", "pak01_dir.vpk"),
+      ]),
+    )
+    .unwrap();
+
+    assert_eq!(read(&folder, "pak02_dir.vpk"), "pak01_dir.vpk");
+    assert_eq!(read(&folder, "pak02_000.vpk"), "pak01_000.vpk");
+    assert_eq!(read(&folder, "pak02_001.vpk"), "pak01_001.vpk");
+    assert_eq!(read(&folder, "pak01_dir.vpk"), "pak02_dir.vpk");
+    assert!(!folder.join("pak01_000.vpk").exists());
+    assert_eq!(std::fs::read_dir(&folder).unwrap().count(), 4);
+
+    std::fs::remove_dir_all(&folder).unwrap();
+  }
+
+  #[test]
+  fn rolls_back_when_a_rename_fails() {
+    let files = [
+      "pak01_dir.vpk",
+      "pak01_000.vpk",
+      "pak02_dir.vpk",
+      "pak03_dir.vpk",
+    ];
+    let folder = folder("rollback", &files);
+
+    // Moving to the temporary names fails on the missing file.
+    assert!(rename_all(
+      &folder,
+      &renames(&[
+        ("pak01_dir.vpk", "pak04_dir.vpk"),
+        ("pak05_dir.vpk", "pak06_dir.vpk"),
+      ]),
+    )
+    .is_err());
+
+    // Moving to the final names fails on a file that isn't renamed away.
+    assert_eq!(
+      rename_all(
+        &folder,
+        &renames(&[
+          ("pak01_dir.vpk", "pak02_dir.vpk"),
+          ("pak01_000.vpk", "pak02_000.vpk"),
+          ("pak02_dir.vpk", "pak03_dir.vpk"),
+        ]),
+      )
+      .map_err(|(path, e)| (path, e.kind()))
+      .unwrap_err(),
+      (folder.join("pak03_dir.vpk"), io::ErrorKind::AlreadyExists)
+    );
+
+    for file in files {
+      assert_eq!(read(&folder, file), file);
+    }
+
+    assert_eq!(std::fs::read_dir(&folder).unwrap().count(), files.len());
+
+    std::fs::remove_dir_all(&folder).unwrap();
+  }
+}
@@ -0,0 +1,258 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ErrorPayload;
+use crate::game::{AddonError, LinkRepair};
+use crate::hash_index::HashIndex;
+use crate::storage::AddonStorage;
+use crate::vpk;
+
+/// What to do with an addon when the new storage folder already has a
+/// different file with the same name.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationConflictPolicy {
+  /// Keep the file in the new folder, the addon stays in the old one.
+  #[default]
+  Skip,
+  Overwrite,
+  /// Move the addon under a free name.
+  Rename,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MigrationOptions {
+  #[serde(rename = "onConflict", default)]
+  pub on_conflict: MigrationConflictPolicy,
+  /// Delete the addons from the old folder once all of them were copied and
+  /// verified.
+  #[serde(rename = "deleteOld", default)]
+  pub delete_old: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationConflict {
+  /// The new folder already has the same addon, nothing has to be copied.
+  Identical,
+  Different,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct PlannedAddon {
+  #[serde(rename = "fileName")]
+  pub file_name: String,
+  /// The addon file followed by its data chunks.
+  pub files: Vec<String>,
+  pub size: u64,
+  pub conflict: Option<MigrationConflict>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct MigrationPlan {
+  pub addons: Vec<PlannedAddon>,
+  /// Bytes that have to be copied, identical addons left out.
+  #[serde(rename = "copyBytes")]
+  pub copy_bytes: u64,
+  /// Free space in the new folder, `None` when it could not be read.
+  #[serde(rename = "availableBytes")]
+  pub available_bytes: Option<u64>,
+  #[serde(rename = "enoughSpace")]
+  pub enough_space: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationOutcome {
+  Copied,
+  AlreadyPresent,
+  /// A different addon with the same name was kept in the new folder.
+  Skipped,
+  Overwritten,
+  Renamed,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct MigratedAddon {
+  #[serde(rename = "fileName")]
+  pub file_name: String,
+  #[serde(rename = "newFileName")]
+  pub new_file_name: String,
+  pub outcome: MigrationOutcome,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct FailedAddon {
+  #[serde(rename = "fileName")]
+  pub file_name: String,
+  pub error: ErrorPayload,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+  pub migrated: Vec<MigratedAddon>,
+  pub failed: Vec<FailedAddon>,
+  /// Symlinks pointed to the new folder.
+  pub relinked: Vec<LinkRepair>,
+  /// `false` when an addon failed and the migration was rolled back, the old
+  /// folder is still used then.
+  pub completed: bool,
+  #[serde(rename = "oldCopiesDeleted")]
+  pub old_copies_deleted: bool,
+}
+
+impl MigrationReport {
+  pub fn new(completed: bool) -> Self {
+    MigrationReport {
+      migrated: vec![],
+      failed: vec![],
+      relinked: vec![],
+      completed,
+      old_copies_deleted: false,
+    }
+  }
+}
+
+/// Lists the addons in `storage` and how each of them compares to the files
+/// in `target`.
+pub fn plan(
+  storage: &AddonStorage,
+  target: &Path,
+  index: &mut HashIndex,
+) -> Result<MigrationPlan, AddonError> {
+  let mut addons = vec![];
+
+  for file_name in storage.list()? {
+    let files = std::iter::once(file_name.clone())
+      .chain(storage.chunk_files(&file_name))
+      .collect::<Vec<_>>();
+
+    let mut size = 0;
+
+    for file in &files {
+      let path = storage.addon_path(file);
+
+      size += std::fs::metadata(&path)
+        .map_err(|e| AddonError::CouldNotReadInstallFolder(path, e))?
+        .len();
+    }
+
+    let conflict = if files.iter().any(|file| target.join(file).exists()) {
+      let target_files = std::iter::once(file_name.clone())
+        .chain(vpk::find_chunk_files(target, &file_name))
+        .collect::<Vec<_>>();
+
+      let source_hash = index.hash_files(
+        &files
+          .iter()
+          .map(|file| storage.addon_path(file))
+          .collect::<Vec<_>>(),
+      );
+      let target_hash = index.hash_files(
+        &target_files
+          .iter()
+          .map(|file| target.join(file))
+          .collect::<Vec<_>>(),
+      );
+
+      match (source_hash, target_hash) {
+        (Ok(source_hash), Ok(target_hash))
+          if target_files == files && source_hash == target_hash =>
+        {
+          Some(MigrationConflict::Identical)
+        }
+        _ => Some(MigrationConflict::Different),
+      }
+    } else {
+      None
+    };
+
+    addons.push(PlannedAddon {
+      file_name,
+      files,
+      size,
+      conflict,
+    });
+  }
+
+  addons.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+  let copy_bytes = addons
+    .iter()
+    .filter(|addon| addon.conflict != Some(MigrationConflict::Identical))
+    .map(|addon| addon.size)
+    .sum();
+
+  let available_bytes = fs4::available_space(target).ok();
+
+  Ok(MigrationPlan {
+    addons,
+    copy_bytes,
+    available_bytes,
+    enough_space: available_bytes.map_or(true, |available_bytes| available_bytes >= copy_bytes),
+  })
+}
+
+/// A name like `hud (2).vpk` that is not taken in `folder`. `_dir.vpk` names
+/// keep their suffix, so data chunks can still be found next to them.
+pub fn free_file_name(folder: &Path, file_name: &str) -> String {
+  let (stem, suffix) = match file_name.strip_suffix("_dir.vpk") {
+    Some(stem) => (stem, "_dir.vpk"),
+    None => (file_name.strip_suffix(".vpk").unwrap_or(file_name), ".vpk"),
+  };
+
+  (2..)
+    .map(|number| format!("{} ({}){}", stem, number, suffix))
+    .find(|candidate| {
+      folder.join(candidate).symlink_metadata().is_err()
+        && vpk::find_chunk_files(folder, candidate).is_empty()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn plans_conflicts_and_free_names() {
+    let root =
+      std::env::temp_dir().join(format!("citadel-cm-migration-test-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&root);
+
+    let storage = AddonStorage::new(root.join("old"));
+    let target = root.join("new");
+
+    std::fs::create_dir_all(&storage.path).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+
+    std::fs::write(storage.addon_path("hud.vpk"), b"hud").unwrap();
+    std::fs::write(storage.addon_path("maps_dir.vpk"), b"maps").unwrap();
+    std::fs::write(storage.addon_path("maps_000.vpk"), b"chunk").unwrap();
+    std::fs::write(storage.addon_path("skins.vpk"), b"skins").unwrap();
+
+    std::fs::write(target.join("hud.vpk"), b"hud").unwrap();
+    std::fs::write(target.join("maps_dir.vpk"), b"other maps").unwrap();
+
+    let plan = plan(&storage, &target, &mut HashIndex::default()).unwrap();
+
+    assert_eq!(
+      plan
+        .addons
+        .iter()
+        .map(|addon| (addon.file_name.as_str(), addon.conflict))
+        .collect::<Vec<_>>(),
+      vec![
+        ("hud.vpk", Some(MigrationConflict::Identical)),
+        ("maps_dir.vpk", Some(MigrationConflict::Different)),
+        ("skins.vpk", None),
+      ]
+    );
+    assert_eq!(plan.addons[1].files, vec!["maps_dir.vpk", "maps_000.vpk"]);
+    assert_eq!(plan.copy_bytes, 4 + 5 + 5);
+
+    assert_eq!(free_file_name(&target, "maps_dir.vpk"), "maps (2)_dir.vpk");
+    assert_eq!(free_file_name(&target, "hud.vpk"), "hud (2).vpk");
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -0,0 +1,80 @@
+use std::{fs::FileType, io, path::Path};
+
+#[cfg(windows)]
+mod imp {
+  use std::os::windows::fs::FileTypeExt;
+  use std::{fs::FileType, io, path::Path};
+
+  pub fn symlink_file(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+  }
+
+  pub fn is_symlink_file(_path: &Path, file_type: &FileType) -> bool {
+    file_type.is_symlink_file()
+  }
+}
+
+#[cfg(unix)]
+mod imp {
+  use std::{fs::FileType, io, path::Path};
+
+  pub fn symlink_file(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+  }
+
+  pub fn is_symlink_file(path: &Path, file_type: &FileType) -> bool {
+    // Unix has no separate file/directory symlink types, so look at the target.
+    // Dangling links are reported as file links, same as on Windows.
+    file_type.is_symlink() && !path.is_dir()
+  }
+}
+
+/// Creates a symlink at `link` pointing to the file at `original`.
+pub fn symlink_file(original: &Path, link: &Path) -> io::Result<()> {
+  imp::symlink_file(original, link)
+}
+
+/// Whether the directory entry at `path` with the given (non-followed) file type
+/// is a symlink to a file.
+pub fn is_symlink_file(path: &Path, file_type: &FileType) -> bool {
+  imp::is_symlink_file(path, file_type)
+}
+
+/// Creates a hard link at `link` to the file at `original`. Both have to be on
+/// the same volume.
+pub fn hard_link_file(original: &Path, link: &Path) -> io::Result<()> {
+  std::fs::hard_link(original, link)
+}
+
+/// Creates a copy-on-write clone of `original` at `link`. Fails on filesystems
+/// without block cloning (only Btrfs, XFS, APFS and ReFS have it) and across
+/// volumes.
+pub fn reflink_file(original: &Path, link: &Path) -> io::Result<()> {
+  reflink_copy::reflink(original, link)
+}
+
+/// Whether `link_file` can link a file in `source_folder` into
+/// `target_folder`, found out by linking a temporary file.
+pub fn probe_link(
+  source_folder: &Path,
+  target_folder: &Path,
+  link_file: fn(&Path, &Path) -> io::Result<()>,
+) -> bool {
+  let probe_file_name = format!(".citadel-cm-probe-{}", std::process::id());
+
+  let source = source_folder.join(&probe_file_name);
+  let target = target_folder.join(&probe_file_name);
+
+  if std::fs::write(&source, b"probe").is_err() {
+    return false;
+  }
+
+  let _ = std::fs::remove_file(&target);
+
+  let linked = link_file(&source, &target).is_ok();
+
+  let _ = std::fs::remove_file(&target);
+  let _ = std::fs::remove_file(&source);
+
+  linked
+}
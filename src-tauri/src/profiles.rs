@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// A named set of stored addons, highest priority first.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Profile {
+  pub name: String,
+  pub addons: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProfileDelta {
+  pub unmount: Vec<String>,
+  pub mount: Vec<String>,
+}
+
+/// Computes which addons have to be unmounted and mounted to go from
+/// `mounted` to `profile_addons`. Addons present in both are left in place.
+pub fn delta(profile_addons: &[String], mounted: &[String]) -> ProfileDelta {
+  let mut unmount = vec![];
+  let mut mount = vec![];
+
+  for addon in mounted {
+    if !profile_addons.contains(addon) && !unmount.contains(addon) {
+      unmount.push(addon.clone());
+    }
+  }
+
+  for addon in profile_addons {
+    if !mounted.contains(addon) && !mount.contains(addon) {
+      mount.push(addon.clone());
+    }
+  }
+
+  ProfileDelta { unmount, mount }
+}
+
+/// Load order after applying a profile: the profile's addons in profile order,
+/// followed by any other addon that is still mounted.
+pub fn load_order(profile_addons: &[String], mounted: &[String]) -> Vec<String> {
+  let mut order: Vec<String> = vec![];
+
+  for addon in profile_addons.iter().chain(mounted) {
+    if mounted.contains(addon) && !order.contains(addon) {
+      order.push(addon.clone());
+    }
+  }
+
+  order
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  #[test]
+  fn only_touches_changed_addons() {
+    let profile = names(&["hud.vpk", "skins.vpk", "sounds.vpk"]);
+    let mounted = names(&["sounds.vpk", "maps.vpk", "hud.vpk"]);
+
+    assert_eq!(
+      delta(&profile, &mounted),
+      ProfileDelta {
+        unmount: names(&["maps.vpk"]),
+        mount: names(&["skins.vpk"]),
+      }
+    );
+
+    assert_eq!(
+      load_order(&profile, &names(&["sounds.vpk", "hud.vpk", "skins.vpk"])),
+      profile
+    );
+  }
+}
@@ -0,0 +1,165 @@
+use std::path::{Path, PathBuf};
+
+use crate::copy_job::CopyJob;
+use crate::game::AddonError;
+use crate::vpk;
+
+/// Folder in the storage holding cached addon thumbnails.
+const THUMBNAILS_FOLDER: &str = ".thumbnails";
+
+/// Appended to the names of files being stored until they are complete.
+const STORING_SUFFIX: &str = ".storing";
+
+/// The addon storage folder, where addons are kept while not installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddonStorage {
+  pub path: PathBuf,
+}
+
+fn is_vpk_file(path: &Path) -> bool {
+  path
+    .extension()
+    .is_some_and(|extension| extension.eq_ignore_ascii_case("vpk"))
+}
+
+impl AddonStorage {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    AddonStorage { path: path.into() }
+  }
+
+  pub fn addon_path(&self, addon_file_name: &str) -> PathBuf {
+    self.path.join(addon_file_name)
+  }
+
+  /// Cached thumbnail of a stored addon, see [`crate::thumbnails`].
+  pub fn thumbnail_path(&self, addon_file_name: &str) -> PathBuf {
+    self
+      .path
+      .join(THUMBNAILS_FOLDER)
+      .join(format!("{}.png", addon_file_name))
+  }
+
+  pub fn contains(&self, addon_file_name: &str) -> bool {
+    self.addon_path(addon_file_name).exists()
+  }
+
+  /// Data chunk files of a stored `_dir.vpk` addon, in index order.
+  pub fn chunk_files(&self, addon_file_name: &str) -> Vec<String> {
+    vpk::find_chunk_files(&self.path, addon_file_name)
+  }
+
+  /// Lists stored addons. Data chunks are not listed on their own.
+  pub fn list(&self) -> Result<Vec<String>, AddonError> {
+    Ok(
+      self
+        .files()?
+        .into_iter()
+        .filter(|file_name| !vpk::is_chunk_file(&self.path, file_name))
+        .collect(),
+    )
+  }
+
+  /// Copies an addon file, along with its data chunks, into the storage under
+  /// `addon_file_name`.
+  pub fn store(
+    &self,
+    file_path: &Path,
+    addon_file_name: &str,
+    copy_job: &CopyJob,
+  ) -> Result<(), AddonError> {
+    let source_folder_path = file_path.parent().ok_or(AddonError::InvalidAddonFile)?;
+
+    let source_file_name = file_path
+      .file_name()
+      .ok_or(AddonError::InvalidAddonFile)?
+      .to_string_lossy();
+
+    let mut files = vec![(file_path.to_path_buf(), self.addon_path(addon_file_name))];
+
+    for (index, chunk_file_name) in vpk::find_chunk_files(source_folder_path, &source_file_name)
+      .into_iter()
+      .enumerate()
+    {
+      // Chunks are looked up next to the `_dir.vpk` file by name, so they can
+      // only be stored along with a `_dir.vpk` name.
+      let stored_chunk_file_name =
+        vpk::chunk_file_name(addon_file_name, index as u16).ok_or(AddonError::InvalidAddonFile)?;
+
+      files.push((
+        source_folder_path.join(chunk_file_name),
+        self.addon_path(&stored_chunk_file_name),
+      ));
+    }
+
+    // Written under temporary names and renamed into place, so a stored addon
+    // being replaced is never rewritten. It may be hard linked into the game,
+    // and it stays intact when the copy fails.
+    let temp_files = files
+      .iter()
+      .map(|(source, destination)| {
+        let mut temp_path = destination.clone().into_os_string();
+
+        temp_path.push(STORING_SUFFIX);
+
+        (source.clone(), PathBuf::from(temp_path))
+      })
+      .collect::<Vec<_>>();
+
+    copy_job
+      .copy_files(&temp_files)
+      .map_err(|e| AddonError::from_copy_error(e, AddonError::CouldNotWriteInstallFolder))?;
+
+    for (index, ((_, temp_path), (_, destination))) in temp_files.iter().zip(&files).enumerate() {
+      if let Err(e) = std::fs::rename(temp_path, destination) {
+        for (_, temp_path) in &temp_files[index..] {
+          let _ = std::fs::remove_file(temp_path);
+        }
+
+        return Err(AddonError::CouldNotWriteInstallFolder(
+          destination.clone(),
+          e,
+        ));
+      }
+    }
+
+    let _ = std::fs::remove_file(self.thumbnail_path(addon_file_name));
+
+    Ok(())
+  }
+
+  /// Deletes a stored addon along with its data chunks.
+  pub fn remove(&self, addon_file_name: &str) -> Result<(), AddonError> {
+    let chunk_files = self.chunk_files(addon_file_name);
+
+    for file_name in std::iter::once(addon_file_name.to_string()).chain(chunk_files) {
+      let path = self.addon_path(&file_name);
+
+      std::fs::remove_file(&path).map_err(|e| AddonError::CouldNotWriteInstallFolder(path, e))?;
+    }
+
+    let _ = std::fs::remove_file(self.thumbnail_path(addon_file_name));
+
+    Ok(())
+  }
+
+  /// Names of all `.vpk` files in the storage, data chunks included.
+  pub fn files(&self) -> Result<Vec<String>, AddonError> {
+    let mut files = vec![];
+
+    let read_error = |e| AddonError::CouldNotReadInstallFolder(self.path.clone(), e);
+
+    for entry in std::fs::read_dir(&self.path).map_err(read_error)? {
+      let entry = entry.map_err(read_error)?;
+
+      let file_type = entry.file_type().map_err(read_error)?;
+
+      if file_type.is_file() && is_vpk_file(&entry.path()) {
+        if let Ok(file_name) = entry.file_name().into_string() {
+          files.push(file_name);
+        }
+      }
+    }
+
+    Ok(files)
+  }
+}
@@ -0,0 +1,298 @@
+//! Previews of addons: the image inside an addon that most likely shows what
+//! it changes, scaled down and encoded as PNG.
+
+use std::{
+  io::{self, Cursor},
+  path::Path,
+};
+
+use crate::{
+  vpk::{VpkDirectory, VpkError},
+  vtex::{Image, Texture},
+};
+
+/// Longer side of thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Images smaller than this are icons or lookup textures.
+const MIN_IMAGE_SIZE: u32 = 16;
+
+/// Images tried before giving up, best candidates first.
+const MAX_ATTEMPTS: usize = 8;
+
+/// Texture names that hold data rather than anything worth looking at.
+const DATA_TEXTURE_WORDS: [&str; 11] = [
+  "normal",
+  "rough",
+  "mask",
+  "metal",
+  "_ao",
+  "occlusion",
+  "spec",
+  "height",
+  "selfillum",
+  "noise",
+  "cube",
+];
+
+/// How likely the file at `path` shows what an addon is about, `None` when it
+/// isn't an image or holds data like normals.
+fn image_score(path: &str) -> Option<u32> {
+  let path = path.to_lowercase();
+  let file_name = path.rsplit('/').next().unwrap_or(&path);
+  let extension = file_name
+    .rsplit_once('.')
+    .map_or("", |(_, extension)| extension);
+
+  // Images kept as they are were put there to be looked at, like HUD art.
+  let mut score = match extension {
+    "png" | "jpg" | "jpeg" => 4,
+    "vtex_c" => 0,
+    _ => return None,
+  };
+
+  if DATA_TEXTURE_WORDS
+    .iter()
+    .any(|word| file_name.contains(word))
+  {
+    return None;
+  }
+
+  if path.starts_with("panorama/images/") {
+    score += 3;
+  }
+
+  if ["color", "albedo", "diffuse"]
+    .iter()
+    .any(|word| file_name.contains(word))
+  {
+    score += 2;
+  }
+
+  Some(score)
+}
+
+fn decode_png(data: &[u8]) -> Option<Image> {
+  let mut decoder = png::Decoder::new(Cursor::new(data));
+
+  // Palettes and 16 bit channels become 8 bit gray or RGB(A).
+  decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+  let mut reader = decoder.read_info().ok()?;
+  let mut buffer = vec![0; reader.output_buffer_size()];
+  let info = reader.next_frame(&mut buffer).ok()?;
+  let pixels = &buffer[..info.buffer_size()];
+
+  let pixels = match info.color_type {
+    png::ColorType::Rgba => pixels.to_vec(),
+    png::ColorType::Rgb => pixels
+      .chunks_exact(3)
+      .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+      .collect(),
+    png::ColorType::GrayscaleAlpha => pixels
+      .chunks_exact(2)
+      .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+      .collect(),
+    png::ColorType::Grayscale => pixels
+      .iter()
+      .flat_map(|gray| [*gray, *gray, *gray, 255])
+      .collect(),
+    png::ColorType::Indexed => return None,
+  };
+
+  Some(Image {
+    width: info.width,
+    height: info.height,
+    pixels,
+  })
+}
+
+fn decode_jpeg(data: &[u8]) -> Option<Image> {
+  let mut decoder = jpeg_decoder::Decoder::new(data);
+  let pixels = decoder.decode().ok()?;
+  let info = decoder.info()?;
+
+  let pixels = match info.pixel_format {
+    jpeg_decoder::PixelFormat::RGB24 => pixels
+      .chunks_exact(3)
+      .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+      .collect(),
+    jpeg_decoder::PixelFormat::L8 => pixels
+      .iter()
+      .flat_map(|gray| [*gray, *gray, *gray, 255])
+      .collect(),
+    _ => return None,
+  };
+
+  Some(Image {
+    width: info.width as u32,
+    height: info.height as u32,
+    pixels,
+  })
+}
+
+fn decode_image(path: &str, data: &[u8]) -> Option<Image> {
+  let path = path.to_lowercase();
+
+  if path.ends_with(".vtex_c") {
+    Texture::parse(data).ok()?.decode(THUMBNAIL_SIZE).ok()
+  } else if path.ends_with(".png") {
+    decode_png(data)
+  } else {
+    decode_jpeg(data)
+  }
+}
+
+/// Scales `image` down to fit in `size` x `size`, every pixel being the
+/// average of the pixels it covers. Smaller images are returned as they are.
+fn resize(image: Image, size: u32) -> Image {
+  let longer_side = image.width.max(image.height);
+
+  if longer_side <= size {
+    return image;
+  }
+
+  let width = (image.width as u64 * size as u64 / longer_side as u64).max(1) as u32;
+  let height = (image.height as u64 * size as u64 / longer_side as u64).max(1) as u32;
+
+  // Source pixels covered by row or column `index` of the thumbnail.
+  let span = |index: u32, size: u32, source_size: u32| {
+    let start = (index as u64 * source_size as u64 / size as u64) as u32;
+    let end = ((index as u64 + 1) * source_size as u64 / size as u64) as u32;
+
+    start..end.max(start + 1)
+  };
+
+  let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+
+  for y in 0..height {
+    let rows = span(y, height, image.height);
+
+    for x in 0..width {
+      let columns = span(x, width, image.width);
+      let mut sums = [0u64; 4];
+
+      for source_y in rows.clone() {
+        for source_x in columns.clone() {
+          let offset = (source_y as usize * image.width as usize + source_x as usize) * 4;
+
+          for (sum, channel) in sums.iter_mut().zip(&image.pixels[offset..offset + 4]) {
+            *sum += *channel as u64;
+          }
+        }
+      }
+
+      let count = rows.len() as u64 * columns.len() as u64;
+
+      pixels.extend(sums.iter().map(|sum| (sum / count) as u8));
+    }
+  }
+
+  Image {
+    width,
+    height,
+    pixels,
+  }
+}
+
+fn encode_png(image: &Image) -> io::Result<Vec<u8>> {
+  let mut png = vec![];
+  let mut encoder = png::Encoder::new(&mut png, image.width, image.height);
+
+  encoder.set_color(png::ColorType::Rgba);
+  encoder.set_depth(png::BitDepth::Eight);
+
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(&image.pixels))
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+  Ok(png)
+}
+
+/// Thumbnail of the VPK at `path` as PNG, made from the most representative
+/// image in it: embedded PNG and JPEG images first, then color textures,
+/// larger files winning ties. `None` when no image could be decoded.
+pub fn render_thumbnail(path: &Path) -> Result<Option<Vec<u8>>, VpkError> {
+  let directory = VpkDirectory::open(path)?;
+
+  let mut candidates = directory
+    .entries
+    .iter()
+    .filter_map(|entry| image_score(&entry.path).map(|score| (score, entry)))
+    .collect::<Vec<_>>();
+
+  candidates
+    .sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(b.size().cmp(&a.size())));
+
+  for (_, entry) in candidates.into_iter().take(MAX_ATTEMPTS) {
+    // A broken image shouldn't hide the others.
+    let Ok(data) = directory.read_entry(path, entry) else {
+      continue;
+    };
+
+    let Some(image) = decode_image(&entry.path, &data) else {
+      continue;
+    };
+
+    if image.width.max(image.height) < MIN_IMAGE_SIZE {
+      continue;
+    }
+
+    return Ok(Some(encode_png(&resize(image, THUMBNAIL_SIZE))?));
+  }
+
+  Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn prefers_color_images_and_skips_data_textures() {
+    let mut paths = [
+      "materials/models/heroes_staging/haze/haze_normal_psd_1a2b.vtex_c",
+      "materials/models/heroes_staging/haze/haze_png_3c4d.vtex_c",
+      "materials/models/heroes_staging/haze/haze_color_psd_5e6f.vtex_c",
+      "panorama/images/heroes/haze_card_psd.png",
+      "models/heroes_staging/haze/haze.vmdl_c",
+    ]
+    .into_iter()
+    .filter_map(|path| image_score(path).map(|score| (score, path)))
+    .collect::<Vec<_>>();
+
+    paths.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    assert_eq!(
+      paths.iter().map(|(_, path)| *path).collect::<Vec<_>>(),
+      vec![
+        "panorama/images/heroes/haze_card_psd.png",
+        "materials/models/heroes_staging/haze/haze_color_psd_5e6f.vtex_c",
+        "materials/models/heroes_staging/haze/haze_png_3c4d.vtex_c",
+      ]
+    );
+  }
+
+  #[test]
+  fn resizes_by_averaging() {
+    // 4x2, left half black, right half white.
+    let image = Image {
+      width: 4,
+      height: 2,
+      pixels: (0..8)
+        .flat_map(|i| if i % 4 < 2 { [0, 0, 0, 255] } else { [255; 4] })
+        .collect(),
+    };
+
+    let resized = resize(image, 2);
+
+    assert_eq!((resized.width, resized.height), (2, 1));
+    assert_eq!(resized.pixels, vec![0, 0, 0, 255, 255, 255, 255, 255]);
+
+    let encoded = encode_png(&resized).unwrap();
+    let decoded = decode_png(&encoded).unwrap();
+
+    assert_eq!(decoded.pixels, resized.pixels);
+  }
+}
@@ -0,0 +1,550 @@
+use std::{
+  fmt,
+  fs::File,
+  io::{self, BufReader, Read, Seek, SeekFrom},
+  path::Path,
+};
+
+const VPK_SIGNATURE: u32 = 0x55aa1234;
+const VPK_V1_HEADER_SIZE: u32 = 12;
+const VPK_V2_HEADER_SIZE: u32 = 28;
+const ENTRY_TERMINATOR: u16 = 0xffff;
+
+/// Archive index used by entries whose data is stored in the `_dir.vpk` file itself.
+pub const DIR_ARCHIVE_INDEX: u16 = 0x7fff;
+
+#[derive(Debug)]
+pub enum VpkError {
+  Io(io::Error),
+  InvalidSignature(u32),
+  UnsupportedVersion(u32),
+  UnexpectedEndOfTree,
+  InvalidEntryTerminator(u16),
+  /// Entry stored in a data chunk of a VPK that isn't a `*_dir.vpk` file.
+  MissingArchive(u16),
+}
+
+impl fmt::Display for VpkError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      VpkError::Io(e) => write!(f, "{}", e),
+      VpkError::InvalidSignature(signature) => {
+        write!(f, "Invalid VPK signature {:#010x}", signature)
+      }
+      VpkError::UnsupportedVersion(version) => {
+        write!(f, "Unsupported VPK version {}", version)
+      }
+      VpkError::UnexpectedEndOfTree => write!(f, "Unexpected end of VPK directory tree"),
+      VpkError::InvalidEntryTerminator(terminator) => {
+        write!(f, "Invalid VPK entry terminator {:#06x}", terminator)
+      }
+      VpkError::MissingArchive(index) => write!(f, "VPK data chunk {} not found", index),
+    }
+  }
+}
+
+impl From<io::Error> for VpkError {
+  fn from(e: io::Error) -> Self {
+    VpkError::Io(e)
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct VpkHeader {
+  pub version: u32,
+  pub tree_size: u32,
+  pub file_data_section_size: u32,
+  pub archive_md5_section_size: u32,
+  pub other_md5_section_size: u32,
+  pub signature_section_size: u32,
+}
+
+impl VpkHeader {
+  pub fn size(&self) -> u32 {
+    if self.version == 1 {
+      VPK_V1_HEADER_SIZE
+    } else {
+      VPK_V2_HEADER_SIZE
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct VpkEntry {
+  /// Full path inside the archive, e.g. `models/heroes/haze/haze.vmdl_c`.
+  pub path: String,
+  pub crc: u32,
+  pub preload: Vec<u8>,
+  pub archive_index: u16,
+  pub offset: u32,
+  pub length: u32,
+}
+
+impl VpkEntry {
+  /// Size of the file once extracted (preload bytes plus archive data).
+  pub fn size(&self) -> u64 {
+    self.preload.len() as u64 + self.length as u64
+  }
+
+  pub fn is_in_dir_file(&self) -> bool {
+    self.archive_index == DIR_ARCHIVE_INDEX
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct VpkDirectory {
+  pub header: VpkHeader,
+  pub entries: Vec<VpkEntry>,
+}
+
+impl VpkDirectory {
+  pub fn open(path: &Path) -> Result<Self, VpkError> {
+    let file = File::open(path)?;
+
+    Self::read(BufReader::new(file))
+  }
+
+  pub fn read<R: Read>(mut reader: R) -> Result<Self, VpkError> {
+    let signature = read_u32(&mut reader)?;
+
+    if signature != VPK_SIGNATURE {
+      return Err(VpkError::InvalidSignature(signature));
+    }
+
+    let version = read_u32(&mut reader)?;
+    let tree_size = read_u32(&mut reader)?;
+
+    let header = match version {
+      1 => VpkHeader {
+        version,
+        tree_size,
+        file_data_section_size: 0,
+        archive_md5_section_size: 0,
+        other_md5_section_size: 0,
+        signature_section_size: 0,
+      },
+      2 => VpkHeader {
+        version,
+        tree_size,
+        file_data_section_size: read_u32(&mut reader)?,
+        archive_md5_section_size: read_u32(&mut reader)?,
+        other_md5_section_size: read_u32(&mut reader)?,
+        signature_section_size: read_u32(&mut reader)?,
+      },
+      _ => return Err(VpkError::UnsupportedVersion(version)),
+    };
+
+    // The size comes from the file, so the tree is read as far as it goes
+    // instead of allocating whatever the header claims up front.
+    let mut tree = vec![];
+    reader.take(tree_size as u64).read_to_end(&mut tree)?;
+
+    if tree.len() != tree_size as usize {
+      return Err(VpkError::UnexpectedEndOfTree);
+    }
+
+    let entries = parse_tree(&tree)?;
+
+    Ok(VpkDirectory { header, entries })
+  }
+
+  /// Offset in the `_dir.vpk` file where data of entries with [`DIR_ARCHIVE_INDEX`] starts.
+  pub fn data_offset(&self) -> u64 {
+    self.header.size() as u64 + self.header.tree_size as u64
+  }
+
+  pub fn total_size(&self) -> u64 {
+    self.entries.iter().map(VpkEntry::size).sum()
+  }
+
+  /// Contents of `entry`. `path` is the VPK file the directory was read from,
+  /// data chunks are looked up next to it.
+  pub fn read_entry(&self, path: &Path, entry: &VpkEntry) -> Result<Vec<u8>, VpkError> {
+    let mut data = entry.preload.clone();
+
+    if entry.length == 0 {
+      return Ok(data);
+    }
+
+    let file = if entry.is_in_dir_file() {
+      let mut file = File::open(path)?;
+
+      file.seek(SeekFrom::Start(self.data_offset() + entry.offset as u64))?;
+
+      file
+    } else {
+      let chunk_file_name = path
+        .file_name()
+        .and_then(|file_name| chunk_file_name(&file_name.to_string_lossy(), entry.archive_index))
+        .ok_or(VpkError::MissingArchive(entry.archive_index))?;
+
+      let mut file = File::open(path.with_file_name(chunk_file_name))?;
+
+      file.seek(SeekFrom::Start(entry.offset as u64))?;
+
+      file
+    };
+
+    let preload_size = data.len();
+
+    file.take(entry.length as u64).read_to_end(&mut data)?;
+
+    if data.len() - preload_size != entry.length as usize {
+      return Err(VpkError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    Ok(data)
+  }
+}
+
+/// Name of a data chunk belonging to a `*_dir.vpk` file, e.g. `pak01_dir.vpk`
+/// and index 0 give `pak01_000.vpk`.
+pub fn chunk_file_name(dir_file_name: &str, index: u16) -> Option<String> {
+  let stem = dir_file_name.strip_suffix("_dir.vpk")?;
+
+  Some(format!("{}_{:03}.vpk", stem, index))
+}
+
+/// Name of the `*_dir.vpk` file a `*_NNN.vpk` data chunk would belong to.
+pub fn chunk_dir_file_name(chunk_file_name: &str) -> Option<String> {
+  let (stem, index) = chunk_file_name.strip_suffix(".vpk")?.rsplit_once('_')?;
+
+  if index.len() != 3 || !index.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+
+  Some(format!("{}_dir.vpk", stem))
+}
+
+/// Whether `file_name` is a data chunk of a `*_dir.vpk` file present in `folder`.
+pub fn is_chunk_file(folder: &Path, file_name: &str) -> bool {
+  // Dangling symlinks count too, so broken chunk links aren't taken for addons.
+  chunk_dir_file_name(file_name)
+    .is_some_and(|dir_file_name| folder.join(dir_file_name).symlink_metadata().is_ok())
+}
+
+/// Data chunks of `dir_file_name` present in `folder`, in index order.
+pub fn find_chunk_files(folder: &Path, dir_file_name: &str) -> Vec<String> {
+  (0..DIR_ARCHIVE_INDEX)
+    .map_while(|index| chunk_file_name(dir_file_name, index))
+    .take_while(|chunk_file_name| folder.join(chunk_file_name).symlink_metadata().is_ok())
+    .collect()
+}
+
+struct TreeCursor<'a> {
+  data: &'a [u8],
+  position: usize,
+}
+
+impl<'a> TreeCursor<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], VpkError> {
+    let end = self
+      .position
+      .checked_add(len)
+      .filter(|&end| end <= self.data.len())
+      .ok_or(VpkError::UnexpectedEndOfTree)?;
+
+    let bytes = &self.data[self.position..end];
+    self.position = end;
+
+    Ok(bytes)
+  }
+
+  fn read_string(&mut self) -> Result<String, VpkError> {
+    let rest = &self.data[self.position..];
+    let len = rest
+      .iter()
+      .position(|&b| b == 0)
+      .ok_or(VpkError::UnexpectedEndOfTree)?;
+
+    let value = String::from_utf8_lossy(&rest[..len]).to_string();
+    self.position += len + 1;
+
+    Ok(value)
+  }
+
+  fn read_u16(&mut self) -> Result<u16, VpkError> {
+    let bytes = self.take(2)?;
+
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn read_u32(&mut self) -> Result<u32, VpkError> {
+    let bytes = self.take(4)?;
+
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+}
+
+fn parse_tree(tree: &[u8]) -> Result<Vec<VpkEntry>, VpkError> {
+  let mut cursor = TreeCursor {
+    data: tree,
+    position: 0,
+  };
+
+  let mut entries = vec![];
+
+  loop {
+    let extension = cursor.read_string()?;
+    if extension.is_empty() {
+      break;
+    }
+
+    loop {
+      let directory = cursor.read_string()?;
+      if directory.is_empty() {
+        break;
+      }
+
+      loop {
+        let file_name = cursor.read_string()?;
+        if file_name.is_empty() {
+          break;
+        }
+
+        let crc = cursor.read_u32()?;
+        let preload_bytes = cursor.read_u16()?;
+        let archive_index = cursor.read_u16()?;
+        let offset = cursor.read_u32()?;
+        let length = cursor.read_u32()?;
+        let terminator = cursor.read_u16()?;
+
+        if terminator != ENTRY_TERMINATOR {
+          return Err(VpkError::InvalidEntryTerminator(terminator));
+        }
+
+        let preload = cursor.take(preload_bytes as usize)?.to_vec();
+
+        entries.push(VpkEntry {
+          path: join_entry_path(&directory, &file_name, &extension),
+          crc,
+          preload,
+          archive_index,
+          offset,
+          length,
+        });
+      }
+    }
+  }
+
+  Ok(entries)
+}
+
+/// A single space is used by the format for "no directory" and "no extension".
+fn join_entry_path(directory: &str, file_name: &str, extension: &str) -> String {
+  let mut path = String::new();
+
+  if directory != " " {
+    path.push_str(directory);
+    path.push('/');
+  }
+
+  path.push_str(file_name);
+
+  if extension != " " {
+    path.push('.');
+    path.push_str(extension);
+  }
+
+  path
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, VpkError> {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes)?;
+
+  Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Directory tree with a single extension and folder.
+  fn tree(extension: &str, folder: &str, files: &[(&str, &[u8], u16, u32, u32)]) -> Vec<u8> {
+    let mut tree = vec![];
+
+    for string in [extension, folder] {
+      tree.extend_from_slice(string.as_bytes());
+      tree.push(0);
+    }
+
+    for (name, preload, archive_index, offset, length) in files {
+      tree.extend_from_slice(name.as_bytes());
+      tree.push(0);
+      tree.extend_from_slice(&0u32.to_le_bytes());
+      tree.extend_from_slice(&(preload.len() as u16).to_le_bytes());
+      tree.extend_from_slice(&archive_index.to_le_bytes());
+      tree.extend_from_slice(&offset.to_le_bytes());
+      tree.extend_from_slice(&length.to_le_bytes());
+      tree.extend_from_slice(&ENTRY_TERMINATOR.to_le_bytes());
+      tree.extend_from_slice(preload);
+    }
+
+    tree.extend_from_slice(&[0, 0, 0]);
+
+    tree
+  }
+
+  fn vpk(version: u32, tree: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut vpk = vec![];
+
+    vpk.extend_from_slice(&VPK_SIGNATURE.to_le_bytes());
+    vpk.extend_from_slice(&version.to_le_bytes());
+    vpk.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+
+    if version == 2 {
+      vpk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+      vpk.extend_from_slice(&[0; 12]);
+    }
+
+    vpk.extend_from_slice(tree);
+    vpk.extend_from_slice(data);
+
+    vpk
+  }
+
+  #[test]
+  fn reads_v1_and_v2_directories() {
+    let tree = tree(
+      "vmat_c",
+      "materials/hud",
+      &[("a", b"pre", DIR_ARCHIVE_INDEX, 0, 4), ("b", b"", 0, 16, 8)],
+    );
+
+    for version in [1, 2] {
+      let directory = VpkDirectory::read(vpk(version, &tree, b"data").as_slice()).unwrap();
+
+      assert_eq!(directory.header.version, version);
+      assert_eq!(
+        directory.data_offset(),
+        directory.header.size() as u64 + tree.len() as u64
+      );
+      assert_eq!(directory.entries.len(), 2);
+      assert_eq!(directory.entries[0].path, "materials/hud/a.vmat_c");
+      assert_eq!(directory.entries[0].preload, b"pre");
+      assert!(directory.entries[0].is_in_dir_file());
+      assert_eq!(directory.entries[0].size(), 7);
+      assert!(!directory.entries[1].is_in_dir_file());
+      assert_eq!(directory.total_size(), 15);
+    }
+
+    let directory = VpkDirectory::read(vpk(2, &tree, b"data").as_slice()).unwrap();
+
+    assert_eq!(directory.header.file_data_section_size, 4);
+  }
+
+  #[test]
+  fn reads_entries_from_the_dir_file_and_chunks() {
+    let folder = std::env::temp_dir().join(format!("citadel-cm-vpk-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    let tree = tree(
+      "txt",
+      " ",
+      &[
+        ("dir", b"pre", DIR_ARCHIVE_INDEX, 2, 3),
+        ("chunk", b"", 1, 1, 5),
+        ("broken", b"", 1, 4, 100),
+      ],
+    );
+    let path = folder.join("pak01_dir.vpk");
+
+    std::fs::write(&path, vpk(1, &tree, b"xxdir")).unwrap();
+    std::fs::write(folder.join("pak01_001.vpk"), b"_chunk").unwrap();
+
+    let directory = VpkDirectory::open(&path).unwrap();
+
+    assert_eq!(
+      directory.read_entry(&path, &directory.entries[0]).unwrap(),
+      b"predir"
+    );
+    assert_eq!(
+      directory.read_entry(&path, &directory.entries[1]).unwrap(),
+      b"chunk"
+    );
+    assert!(matches!(
+      directory.read_entry(&path, &directory.entries[2]),
+      Err(VpkError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+    ));
+
+    // Chunks can only be found next to `*_dir.vpk` files.
+    let renamed = folder.join("hud.vpk");
+
+    std::fs::rename(&path, &renamed).unwrap();
+
+    assert!(matches!(
+      directory.read_entry(&renamed, &directory.entries[1]),
+      Err(VpkError::MissingArchive(1))
+    ));
+
+    std::fs::remove_dir_all(&folder).unwrap();
+  }
+
+  #[test]
+  fn rejects_malformed_directories() {
+    let mut bad_signature = vpk(1, &[0], b"");
+
+    bad_signature[0] = 0;
+
+    assert!(matches!(
+      VpkDirectory::read(bad_signature.as_slice()),
+      Err(VpkError::InvalidSignature(0x55aa1200))
+    ));
+    assert!(matches!(
+      VpkDirectory::read(vpk(3, &[0], b"").as_slice()),
+      Err(VpkError::UnsupportedVersion(3))
+    ));
+
+    // A huge tree size is not trusted, only the bytes actually there are read.
+    let mut truncated = vpk(1, &tree("txt", " ", &[("a", b"", 0, 0, 1)]), b"");
+
+    truncated[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(matches!(
+      VpkDirectory::read(truncated.as_slice()),
+      Err(VpkError::UnexpectedEndOfTree)
+    ));
+
+    let tree = tree("txt", " ", &[("a", b"", 0, 0, 1)]);
+
+    assert!(matches!(
+      VpkDirectory::read(vpk(1, &tree[..tree.len() - 6], b"").as_slice()),
+      Err(VpkError::UnexpectedEndOfTree)
+    ));
+
+    let mut bad_terminator = tree.clone();
+    let terminator = bad_terminator.len() - 5;
+
+    bad_terminator[terminator] = 0;
+
+    assert!(matches!(
+      VpkDirectory::read(vpk(1, &bad_terminator, b"").as_slice()),
+      Err(VpkError::InvalidEntryTerminator(0xff00))
+    ));
+  }
+
+  #[test]
+  fn names_chunks_of_dir_files() {
+    assert_eq!(
+      chunk_file_name("pak01_dir.vpk", 7).as_deref(),
+      Some("pak01_007.vpk")
+    );
+    assert_eq!(chunk_file_name("hud.vpk", 0), None);
+
+    for index in [0, 42, 999] {
+      let chunk = chunk_file_name("my_addon_dir.vpk", index).unwrap();
+
+      assert_eq!(
+        chunk_dir_file_name(&chunk).as_deref(),
+        Some("my_addon_dir.vpk")
+      );
+    }
+
+    assert_eq!(chunk_dir_file_name("pak01_dir.vpk"), None);
+    assert_eq!(chunk_dir_file_name("pak01_0001.vpk"), None);
+    assert_eq!(chunk_dir_file_name("pak01_00a.vpk"), None);
+  }
+}
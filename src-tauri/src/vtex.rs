@@ -0,0 +1,984 @@
+//! Compiled Source 2 textures (`.vtex_c`): the resource container, the texture
+//! header and decoding of the common pixel formats to RGBA on the CPU.
+
+use std::fmt;
+
+const RESOURCE_HEADER_VERSION: u16 = 12;
+const TEXTURE_VERSION: u16 = 1;
+
+const FLAG_CUBE_TEXTURE: u16 = 0x10;
+const FLAG_VOLUME_TEXTURE: u16 = 0x20;
+const FLAG_TEXTURE_ARRAY: u16 = 0x40;
+
+const EXTRA_DATA_FILL_TO_POWER_OF_TWO: u32 = 3;
+const EXTRA_DATA_COMPRESSED_MIP_SIZE: u32 = 4;
+
+#[derive(Debug)]
+pub enum TextureError {
+  NotAResource,
+  UnsupportedVersion(u16),
+  MissingDataBlock,
+  UnexpectedEnd,
+  UnsupportedFormat(u8),
+  /// Cube maps, volume textures and texture arrays.
+  UnsupportedKind,
+  InvalidCompressedMip,
+}
+
+impl fmt::Display for TextureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TextureError::NotAResource => write!(f, "Not a compiled resource"),
+      TextureError::UnsupportedVersion(version) => {
+        write!(f, "Unsupported texture version {}", version)
+      }
+      TextureError::MissingDataBlock => write!(f, "Texture has no DATA block"),
+      TextureError::UnexpectedEnd => write!(f, "Unexpected end of texture"),
+      TextureError::UnsupportedFormat(format) => {
+        write!(f, "Unsupported texture format {}", format)
+      }
+      TextureError::UnsupportedKind => {
+        write!(
+          f,
+          "Cube maps, volume textures and texture arrays are not supported"
+        )
+      }
+      TextureError::InvalidCompressedMip => write!(f, "Invalid compressed mip level"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+  Dxt1,
+  Dxt5,
+  Rgba8888,
+  Bc7,
+  Bgra8888,
+  Other(u8),
+}
+
+impl TextureFormat {
+  fn from_id(id: u8) -> Self {
+    match id {
+      1 => TextureFormat::Dxt1,
+      2 => TextureFormat::Dxt5,
+      4 => TextureFormat::Rgba8888,
+      20 => TextureFormat::Bc7,
+      28 => TextureFormat::Bgra8888,
+      id => TextureFormat::Other(id),
+    }
+  }
+
+  /// Bytes of a 4x4 block for block compressed formats.
+  fn block_size(self) -> Option<usize> {
+    match self {
+      TextureFormat::Dxt1 => Some(8),
+      TextureFormat::Dxt5 | TextureFormat::Bc7 => Some(16),
+      _ => None,
+    }
+  }
+
+  fn mip_size(self, width: u32, height: u32) -> Result<usize, TextureError> {
+    let (width, height) = (width as usize, height as usize);
+
+    if let Some(block_size) = self.block_size() {
+      return Ok((width + 3) / 4 * ((height + 3) / 4) * block_size);
+    }
+
+    match self {
+      TextureFormat::Other(id) => Err(TextureError::UnsupportedFormat(id)),
+      _ => Ok(width * height * 4),
+    }
+  }
+}
+
+/// Decoded image, RGBA row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Texture<'a> {
+  pub width: u32,
+  pub height: u32,
+  pub format: TextureFormat,
+  mip_count: u32,
+  /// Size of the image when it was padded to a power of two.
+  visible_size: Option<(u32, u32)>,
+  /// Stored size of each mip level, largest level first, when they are LZ4
+  /// compressed.
+  compressed_mip_sizes: Option<Vec<usize>>,
+  /// Mip levels, smallest first.
+  data: &'a [u8],
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<u16, TextureError> {
+  data
+    .get(position..position + 2)
+    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    .ok_or(TextureError::UnexpectedEnd)
+}
+
+fn read_u32(data: &[u8], position: usize) -> Result<u32, TextureError> {
+  data
+    .get(position..position + 4)
+    .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    .ok_or(TextureError::UnexpectedEnd)
+}
+
+/// Reads an offset stored relative to its own position.
+fn read_offset(data: &[u8], position: usize) -> Result<usize, TextureError> {
+  Ok(position + read_u32(data, position)? as usize)
+}
+
+impl<'a> Texture<'a> {
+  pub fn parse(resource: &'a [u8]) -> Result<Self, TextureError> {
+    if read_u16(resource, 4)? != RESOURCE_HEADER_VERSION {
+      return Err(TextureError::NotAResource);
+    }
+
+    let block_offset = read_offset(resource, 8)?;
+    let block_count = read_u32(resource, 12)? as usize;
+
+    let mut data_block = None;
+
+    for index in 0..block_count {
+      let position = block_offset + index * 12;
+
+      let kind = resource
+        .get(position..position + 4)
+        .ok_or(TextureError::UnexpectedEnd)?;
+
+      if kind == b"DATA" {
+        data_block = Some((
+          read_offset(resource, position + 4)?,
+          read_u32(resource, position + 8)? as usize,
+        ));
+      }
+    }
+
+    let (offset, size) = data_block.ok_or(TextureError::MissingDataBlock)?;
+
+    let version = read_u16(resource, offset)?;
+
+    if version != TEXTURE_VERSION {
+      return Err(TextureError::UnsupportedVersion(version));
+    }
+
+    let flags = read_u16(resource, offset + 2)?;
+    let depth = read_u16(resource, offset + 24)?;
+
+    if flags & (FLAG_CUBE_TEXTURE | FLAG_VOLUME_TEXTURE | FLAG_TEXTURE_ARRAY) != 0 || depth > 1 {
+      return Err(TextureError::UnsupportedKind);
+    }
+
+    let format_and_mips = resource
+      .get(offset + 26..offset + 28)
+      .ok_or(TextureError::UnexpectedEnd)?;
+
+    let mut texture = Texture {
+      width: read_u16(resource, offset + 20)? as u32,
+      height: read_u16(resource, offset + 22)? as u32,
+      format: TextureFormat::from_id(format_and_mips[0]),
+      // A 65535 pixels wide texture has 17 levels, more are never valid.
+      mip_count: (format_and_mips[1] as u32).clamp(1, 17),
+      visible_size: None,
+      compressed_mip_sizes: None,
+      // Pixel data follows the DATA block.
+      data: resource
+        .get(offset + size..)
+        .ok_or(TextureError::UnexpectedEnd)?,
+    };
+
+    let extra_data_offset = read_offset(resource, offset + 32)?;
+    let extra_data_count = read_u32(resource, offset + 36)? as usize;
+
+    for index in 0..extra_data_count {
+      let position = extra_data_offset + index * 12;
+      let extra_data = read_offset(resource, position + 4)?;
+
+      match read_u32(resource, position)? {
+        EXTRA_DATA_FILL_TO_POWER_OF_TWO => {
+          let width = read_u16(resource, extra_data + 2)? as u32;
+          let height = read_u16(resource, extra_data + 4)? as u32;
+
+          if width > 0 && height > 0 && width <= texture.width && height <= texture.height {
+            texture.visible_size = Some((width, height));
+          }
+        }
+        // Only set when the mips are actually compressed.
+        EXTRA_DATA_COMPRESSED_MIP_SIZE if read_u32(resource, extra_data)? == 1 => {
+          let sizes_offset = read_offset(resource, extra_data + 4)?;
+          let size_count = read_u32(resource, extra_data + 8)? as usize;
+
+          texture.compressed_mip_sizes = Some(
+            (0..size_count)
+              .map(|index| read_u32(resource, sizes_offset + index * 4).map(|size| size as usize))
+              .collect::<Result<_, _>>()?,
+          );
+        }
+        _ => {}
+      }
+    }
+
+    Ok(texture)
+  }
+
+  fn mip_dimensions(&self, level: u32) -> (u32, u32) {
+    ((self.width >> level).max(1), (self.height >> level).max(1))
+  }
+
+  fn stored_mip_size(&self, level: u32) -> Result<usize, TextureError> {
+    match &self.compressed_mip_sizes {
+      Some(sizes) => sizes
+        .get(level as usize)
+        .copied()
+        .ok_or(TextureError::InvalidCompressedMip),
+      None => {
+        let (width, height) = self.mip_dimensions(level);
+
+        self.format.mip_size(width, height)
+      }
+    }
+  }
+
+  /// Decodes the smallest mip level that is at least `min_size` pixels on its
+  /// longer side, or the largest level for smaller textures.
+  pub fn decode(&self, min_size: u32) -> Result<Image, TextureError> {
+    let level = (0..self.mip_count)
+      .rev()
+      .find(|level| {
+        let (width, height) = self.mip_dimensions(*level);
+
+        width.max(height) >= min_size
+      })
+      .unwrap_or(0);
+
+    let mut offset = 0;
+
+    for smaller_level in (level + 1..self.mip_count).rev() {
+      offset += self.stored_mip_size(smaller_level)?;
+    }
+
+    let (width, height) = self.mip_dimensions(level);
+
+    let size = self.format.mip_size(width, height)?;
+    let stored_size = self.stored_mip_size(level)?;
+
+    let stored = self
+      .data
+      .get(offset..offset + stored_size)
+      .ok_or(TextureError::UnexpectedEnd)?;
+
+    let decompressed;
+
+    let data = if stored_size < size {
+      decompressed = lz4_flex::block::decompress(stored, size)
+        .map_err(|_| TextureError::InvalidCompressedMip)?;
+
+      &decompressed
+    } else {
+      stored
+    };
+
+    let image = Image {
+      width,
+      height,
+      pixels: decode_pixels(self.format, width, height, data)?,
+    };
+
+    Ok(match self.visible_size {
+      Some((visible_width, visible_height)) => crop(
+        image,
+        ((visible_width + (1 << level) - 1) >> level).min(width),
+        ((visible_height + (1 << level) - 1) >> level).min(height),
+      ),
+      None => image,
+    })
+  }
+}
+
+fn crop(image: Image, width: u32, height: u32) -> Image {
+  if width == image.width && height == image.height {
+    return image;
+  }
+
+  let row_size = width as usize * 4;
+
+  let pixels = image
+    .pixels
+    .chunks_exact(image.width as usize * 4)
+    .take(height as usize)
+    .flat_map(|row| &row[..row_size])
+    .copied()
+    .collect();
+
+  Image {
+    width,
+    height,
+    pixels,
+  }
+}
+
+fn decode_pixels(
+  format: TextureFormat,
+  width: u32,
+  height: u32,
+  data: &[u8],
+) -> Result<Vec<u8>, TextureError> {
+  let size = format.mip_size(width, height)?;
+
+  let data = data.get(..size).ok_or(TextureError::UnexpectedEnd)?;
+
+  let (width, height) = (width as usize, height as usize);
+
+  let Some(block_size) = format.block_size() else {
+    let mut pixels = data.to_vec();
+
+    if format == TextureFormat::Bgra8888 {
+      for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+      }
+    }
+
+    return Ok(pixels);
+  };
+
+  let blocks_wide = (width + 3) / 4;
+
+  let mut pixels = vec![0; width * height * 4];
+
+  for (block_index, block) in data.chunks_exact(block_size).enumerate() {
+    let decoded = match format {
+      TextureFormat::Dxt1 => decode_dxt1_block(block),
+      TextureFormat::Dxt5 => decode_dxt5_block(block),
+      _ => decode_bc7_block(block),
+    };
+
+    let (block_x, block_y) = (block_index % blocks_wide * 4, block_index / blocks_wide * 4);
+
+    for (index, color) in decoded.iter().enumerate() {
+      let (x, y) = (block_x + index % 4, block_y + index / 4);
+
+      if x < width && y < height {
+        let position = (y * width + x) * 4;
+
+        pixels[position..position + 4].copy_from_slice(color);
+      }
+    }
+  }
+
+  Ok(pixels)
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+  let (r, g, b) = ((color >> 11) & 31, (color >> 5) & 63, color & 31);
+
+  [
+    (r << 3 | r >> 2) as u8,
+    (g << 2 | g >> 4) as u8,
+    (b << 3 | b >> 2) as u8,
+  ]
+}
+
+/// Color part of DXT1 and DXT5 blocks. DXT5 always uses four colors.
+fn decode_color_block(block: &[u8], four_colors: bool) -> [[u8; 4]; 16] {
+  let color_0 = u16::from_le_bytes([block[0], block[1]]);
+  let color_1 = u16::from_le_bytes([block[2], block[3]]);
+
+  let (rgb_0, rgb_1) = (rgb565(color_0), rgb565(color_1));
+
+  let mix = |weight_0: u32, weight_1: u32, divisor: u32| {
+    let mut color = [0, 0, 0, 255];
+
+    for channel in 0..3 {
+      color[channel] =
+        ((rgb_0[channel] as u32 * weight_0 + rgb_1[channel] as u32 * weight_1) / divisor) as u8;
+    }
+
+    color
+  };
+
+  let palette = if four_colors || color_0 > color_1 {
+    [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+  } else {
+    [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0, 0, 0, 0]]
+  };
+
+  let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+  let mut pixels = [[0; 4]; 16];
+
+  for (index, pixel) in pixels.iter_mut().enumerate() {
+    *pixel = palette[(indices >> (index * 2) & 3) as usize];
+  }
+
+  pixels
+}
+
+fn decode_dxt1_block(block: &[u8]) -> [[u8; 4]; 16] {
+  decode_color_block(block, false)
+}
+
+fn decode_dxt5_block(block: &[u8]) -> [[u8; 4]; 16] {
+  let (alpha_0, alpha_1) = (block[0] as u32, block[1] as u32);
+
+  let mut palette = [alpha_0 as u8, alpha_1 as u8, 0, 0, 0, 0, 0, 255];
+
+  if alpha_0 > alpha_1 {
+    for index in 1..7 {
+      palette[index as usize + 1] = (((7 - index) * alpha_0 + index * alpha_1) / 7) as u8;
+    }
+  } else {
+    for index in 1..5 {
+      palette[index as usize + 1] = (((5 - index) * alpha_0 + index * alpha_1) / 5) as u8;
+    }
+  }
+
+  let indices = block[2..8]
+    .iter()
+    .rev()
+    .fold(0u64, |indices, byte| indices << 8 | *byte as u64);
+
+  let mut pixels = decode_color_block(&block[8..], true);
+
+  for (index, pixel) in pixels.iter_mut().enumerate() {
+    pixel[3] = palette[(indices >> (index * 3) & 7) as usize];
+  }
+
+  pixels
+}
+
+struct Bc7Mode {
+  subsets: usize,
+  partition_bits: u32,
+  rotation_bits: u32,
+  index_selection_bits: u32,
+  color_bits: u32,
+  alpha_bits: u32,
+  /// One P-bit per endpoint.
+  endpoint_p_bits: bool,
+  /// One P-bit per subset.
+  shared_p_bits: bool,
+  index_bits: u32,
+  /// Separate alpha (or color, see the index selection) indices.
+  secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+  Bc7Mode {
+    subsets: 3,
+    partition_bits: 4,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 4,
+    alpha_bits: 0,
+    endpoint_p_bits: true,
+    shared_p_bits: false,
+    index_bits: 3,
+    secondary_index_bits: 0,
+  },
+  Bc7Mode {
+    subsets: 2,
+    partition_bits: 6,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 6,
+    alpha_bits: 0,
+    endpoint_p_bits: false,
+    shared_p_bits: true,
+    index_bits: 3,
+    secondary_index_bits: 0,
+  },
+  Bc7Mode {
+    subsets: 3,
+    partition_bits: 6,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 5,
+    alpha_bits: 0,
+    endpoint_p_bits: false,
+    shared_p_bits: false,
+    index_bits: 2,
+    secondary_index_bits: 0,
+  },
+  Bc7Mode {
+    subsets: 2,
+    partition_bits: 6,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 7,
+    alpha_bits: 0,
+    endpoint_p_bits: true,
+    shared_p_bits: false,
+    index_bits: 2,
+    secondary_index_bits: 0,
+  },
+  Bc7Mode {
+    subsets: 1,
+    partition_bits: 0,
+    rotation_bits: 2,
+    index_selection_bits: 1,
+    color_bits: 5,
+    alpha_bits: 6,
+    endpoint_p_bits: false,
+    shared_p_bits: false,
+    index_bits: 2,
+    secondary_index_bits: 3,
+  },
+  Bc7Mode {
+    subsets: 1,
+    partition_bits: 0,
+    rotation_bits: 2,
+    index_selection_bits: 0,
+    color_bits: 7,
+    alpha_bits: 8,
+    endpoint_p_bits: false,
+    shared_p_bits: false,
+    index_bits: 2,
+    secondary_index_bits: 2,
+  },
+  Bc7Mode {
+    subsets: 1,
+    partition_bits: 0,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 7,
+    alpha_bits: 7,
+    endpoint_p_bits: true,
+    shared_p_bits: false,
+    index_bits: 4,
+    secondary_index_bits: 0,
+  },
+  Bc7Mode {
+    subsets: 2,
+    partition_bits: 6,
+    rotation_bits: 0,
+    index_selection_bits: 0,
+    color_bits: 5,
+    alpha_bits: 5,
+    endpoint_p_bits: true,
+    shared_p_bits: false,
+    index_bits: 2,
+    secondary_index_bits: 0,
+  },
+];
+
+/// Two subset partitions, bit `n` is the subset of pixel `n`.
+const BC7_PARTITIONS_2: [u16; 64] = [
+  0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+  0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+  0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+  0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+  0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+  0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Three subset partitions, subset of each pixel.
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+  [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+  [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+  [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+  [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+  [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+  [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+  [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+  [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+  [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+  [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+  [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+  [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+  [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+  [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+  [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+  [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+  [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+  [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+  [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+  [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+  [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+  [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+  [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+  [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+  [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+  [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+  [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+  [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+  [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+  [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+  [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+  [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+  [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+  [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+  [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+  [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+  [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+  [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+  [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+  [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+  [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+  [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+  [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+  [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+  [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+  [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+  [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+  [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+  [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+  [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+  [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+  [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+  [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+  [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+  [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+  [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Pixel whose index of the second subset is stored with one bit less.
+const BC7_ANCHORS_2: [u8; 64] = [
+  15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+  2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2, 2,
+  15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+  3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+  15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5, 10,
+  8, 13, 15, 12, 3, 3,
+];
+
+const BC7_ANCHORS_3_THIRD: [u8; 64] = [
+  15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+  10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15,
+  15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct BitReader {
+  bits: u128,
+  position: u32,
+}
+
+impl BitReader {
+  fn read(&mut self, count: u32) -> u32 {
+    let value = self.bits.checked_shr(self.position).unwrap_or(0) as u32 & ((1 << count) - 1);
+
+    self.position += count;
+
+    value
+  }
+}
+
+fn bc7_interpolate(endpoint_0: u8, endpoint_1: u8, index: u32, index_bits: u32) -> u8 {
+  let weight = match index_bits {
+    2 => BC7_WEIGHTS_2[index as usize],
+    3 => BC7_WEIGHTS_3[index as usize],
+    _ => BC7_WEIGHTS_4[index as usize],
+  };
+
+  (((64 - weight) * endpoint_0 as u32 + weight * endpoint_1 as u32 + 32) >> 6) as u8
+}
+
+fn decode_bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+  let mut bytes = [0; 16];
+
+  bytes.copy_from_slice(block);
+
+  let bits = u128::from_le_bytes(bytes);
+
+  // The mode is the position of the lowest set bit, reserved modes decode to
+  // transparent black.
+  let mode_index = bits.trailing_zeros();
+
+  let Some(mode) = BC7_MODES.get(mode_index as usize) else {
+    return [[0; 4]; 16];
+  };
+
+  let mut reader = BitReader {
+    bits,
+    position: mode_index + 1,
+  };
+
+  let partition = reader.read(mode.partition_bits) as usize;
+  let rotation = reader.read(mode.rotation_bits);
+  let index_selection = reader.read(mode.index_selection_bits);
+
+  let endpoint_count = mode.subsets * 2;
+
+  let mut raw_endpoints = [[0u32; 4]; 6];
+
+  for channel in 0..3 {
+    for endpoint in raw_endpoints.iter_mut().take(endpoint_count) {
+      endpoint[channel] = reader.read(mode.color_bits);
+    }
+  }
+
+  for endpoint in raw_endpoints.iter_mut().take(endpoint_count) {
+    endpoint[3] = reader.read(mode.alpha_bits);
+  }
+
+  let mut p_bits = [0; 6];
+
+  if mode.endpoint_p_bits {
+    for p_bit in p_bits.iter_mut().take(endpoint_count) {
+      *p_bit = reader.read(1);
+    }
+  }
+
+  if mode.shared_p_bits {
+    for subset in 0..mode.subsets {
+      let p_bit = reader.read(1);
+
+      p_bits[subset * 2] = p_bit;
+      p_bits[subset * 2 + 1] = p_bit;
+    }
+  }
+
+  let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+
+  let mut endpoints = [[255u8; 4]; 6];
+
+  for (index, endpoint) in endpoints.iter_mut().enumerate().take(endpoint_count) {
+    for (channel, value) in endpoint.iter_mut().enumerate() {
+      let bits = if channel == 3 {
+        mode.alpha_bits
+      } else {
+        mode.color_bits
+      };
+
+      if bits == 0 {
+        continue;
+      }
+
+      let (raw, bits) = if has_p_bits {
+        (raw_endpoints[index][channel] << 1 | p_bits[index], bits + 1)
+      } else {
+        (raw_endpoints[index][channel], bits)
+      };
+
+      let raw = raw << (8 - bits);
+
+      *value = (raw | raw >> bits) as u8;
+    }
+  }
+
+  let subset_of = |pixel: usize| match mode.subsets {
+    1 => 0,
+    2 => (BC7_PARTITIONS_2[partition] >> pixel & 1) as usize,
+    _ => BC7_PARTITIONS_3[partition][pixel] as usize,
+  };
+
+  let is_anchor = |pixel: usize| {
+    pixel == 0
+      || match mode.subsets {
+        2 => BC7_ANCHORS_2[partition] as usize == pixel,
+        3 => {
+          BC7_ANCHORS_3_SECOND[partition] as usize == pixel
+            || BC7_ANCHORS_3_THIRD[partition] as usize == pixel
+        }
+        _ => false,
+      }
+  };
+
+  let mut indices = [0; 16];
+
+  for (pixel, index) in indices.iter_mut().enumerate() {
+    *index = reader.read(mode.index_bits - is_anchor(pixel) as u32);
+  }
+
+  let mut secondary_indices = [0; 16];
+
+  if mode.secondary_index_bits > 0 {
+    for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+      *index = reader.read(mode.secondary_index_bits - (pixel == 0) as u32);
+    }
+  }
+
+  let mut pixels = [[0; 4]; 16];
+
+  for (pixel, color) in pixels.iter_mut().enumerate() {
+    let subset = subset_of(pixel);
+
+    let (endpoint_0, endpoint_1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+    let ((color_index, color_bits), (alpha_index, alpha_bits)) =
+      match (mode.secondary_index_bits, index_selection) {
+        (0, _) => (
+          (indices[pixel], mode.index_bits),
+          (indices[pixel], mode.index_bits),
+        ),
+        (_, 0) => (
+          (indices[pixel], mode.index_bits),
+          (secondary_indices[pixel], mode.secondary_index_bits),
+        ),
+        _ => (
+          (secondary_indices[pixel], mode.secondary_index_bits),
+          (indices[pixel], mode.index_bits),
+        ),
+      };
+
+    for channel in 0..3 {
+      color[channel] = bc7_interpolate(
+        endpoint_0[channel],
+        endpoint_1[channel],
+        color_index,
+        color_bits,
+      );
+    }
+
+    color[3] = bc7_interpolate(endpoint_0[3], endpoint_1[3], alpha_index, alpha_bits);
+
+    if rotation > 0 {
+      color.swap(3, rotation as usize - 1);
+    }
+  }
+
+  pixels
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Resource with a single DATA block holding a texture header, followed by
+  /// `mips`, smallest first.
+  fn texture_resource(format: u8, width: u16, height: u16, mips: &[&[u8]]) -> Vec<u8> {
+    let mut resource = vec![];
+
+    resource.extend_from_slice(&0u32.to_le_bytes());
+    resource.extend_from_slice(&RESOURCE_HEADER_VERSION.to_le_bytes());
+    resource.extend_from_slice(&0u16.to_le_bytes());
+    // Block list right after the header.
+    resource.extend_from_slice(&8u32.to_le_bytes());
+    resource.extend_from_slice(&1u32.to_le_bytes());
+
+    resource.extend_from_slice(b"DATA");
+    // DATA right after the block list, relative to this field.
+    resource.extend_from_slice(&8u32.to_le_bytes());
+    resource.extend_from_slice(&40u32.to_le_bytes());
+
+    resource.extend_from_slice(&TEXTURE_VERSION.to_le_bytes());
+    resource.extend_from_slice(&0u16.to_le_bytes());
+    resource.extend_from_slice(&[0; 16]);
+    resource.extend_from_slice(&width.to_le_bytes());
+    resource.extend_from_slice(&height.to_le_bytes());
+    resource.extend_from_slice(&1u16.to_le_bytes());
+    resource.push(format);
+    resource.push(mips.len() as u8);
+    resource.extend_from_slice(&0u32.to_le_bytes());
+    resource.extend_from_slice(&8u32.to_le_bytes());
+    resource.extend_from_slice(&0u32.to_le_bytes());
+
+    for mip in mips {
+      resource.extend_from_slice(mip);
+    }
+
+    resource
+  }
+
+  #[test]
+  fn decodes_the_smallest_large_enough_mip() {
+    let large = [[255, 0, 0, 255]; 16].concat();
+    let small = [[0, 0, 255, 128]; 4].concat();
+
+    let resource = texture_resource(28, 4, 4, &[&small, &large]);
+    let texture = Texture::parse(&resource).unwrap();
+
+    assert_eq!(texture.format, TextureFormat::Bgra8888);
+    assert_eq!(
+      texture.decode(2).unwrap(),
+      Image {
+        width: 2,
+        height: 2,
+        pixels: [[255, 0, 0, 128]; 4].concat(),
+      }
+    );
+    assert_eq!(
+      texture.decode(3).unwrap().pixels,
+      [[0, 0, 255, 255]; 16].concat()
+    );
+  }
+
+  #[test]
+  fn decodes_block_compressed_formats() {
+    // Red and blue, every pixel but the first uses the second color.
+    let dxt1 = [0x00, 0xf8, 0x1f, 0x00, 0x54, 0x55, 0x55, 0x55];
+
+    let pixels = decode_dxt1_block(&dxt1);
+
+    assert_eq!(pixels[0], [255, 0, 0, 255]);
+    assert_eq!(pixels[1], [0, 0, 255, 255]);
+
+    let mut dxt5 = vec![255, 0, 0b001, 0, 0, 0, 0, 0];
+
+    dxt5.extend_from_slice(&dxt1);
+
+    let pixels = decode_dxt5_block(&dxt5);
+
+    assert_eq!(pixels[0], [255, 0, 0, 0]);
+    assert_eq!(pixels[1], [0, 0, 255, 255]);
+
+    // Mode 6 with both endpoints 0x7f and P-bit 1 on every channel, so every
+    // pixel is opaque white.
+    let mut bits: u128 = 1 << 6;
+    let mut position = 7;
+
+    for _ in 0..8 {
+      bits |= 0x7f << position;
+      position += 7;
+    }
+
+    bits |= 0b11 << position;
+
+    let pixels = decode_bc7_block(&bits.to_le_bytes());
+
+    assert!(pixels.iter().all(|pixel| *pixel == [255; 4]));
+
+    // Mode 1, partition 0: black left half, right half from almost black to
+    // white. Every index is at its maximum, anchors have one bit less.
+    let mut bits: u128 = 0b10;
+    let mut position = 8;
+
+    for _ in 0..3 {
+      for value in [0u128, 0, 0, 63] {
+        bits |= value << position;
+        position += 6;
+      }
+    }
+
+    bits |= 0b10 << position;
+    bits |= u128::MAX << (position + 2);
+
+    let pixels = decode_bc7_block(&bits.to_le_bytes());
+
+    assert_eq!(pixels[0], [0, 0, 0, 255]);
+    assert_eq!(pixels[14], [255; 4]);
+    assert_eq!(pixels[15], [109, 109, 109, 255]);
+
+    let resource = texture_resource(1, 4, 4, &[&dxt1]);
+
+    assert_eq!(
+      Texture::parse(&resource)
+        .unwrap()
+        .decode(256)
+        .unwrap()
+        .pixels[4..8],
+      [0, 0, 255, 255]
+    );
+  }
+}
@@ -0,0 +1,279 @@
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::{Duration, SystemTime},
+};
+
+use notify_debouncer_mini::{
+  new_debouncer,
+  notify::{self, RecommendedWatcher, RecursiveMode},
+  DebounceEventResult, Debouncer,
+};
+use serde::Serialize;
+
+use crate::game::{AddonLocation, GameInstall};
+use crate::gameinfo::SearchPathsState;
+use crate::storage::AddonStorage;
+use crate::vpk;
+
+/// Changes arriving within this time of each other are reported together.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A change found in the watched folders.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum WatchEvent {
+  AddonAdded {
+    location: AddonLocation,
+    /// File name in the watched folder, `pakNN_dir.vpk` for installed addons.
+    #[serde(rename = "fileName")]
+    file_name: String,
+  },
+  AddonRemoved {
+    location: AddonLocation,
+    #[serde(rename = "fileName")]
+    file_name: String,
+  },
+  AddonChanged {
+    location: AddonLocation,
+    #[serde(rename = "fileName")]
+    file_name: String,
+  },
+  /// `None` when gameinfo.gi could not be read.
+  SearchPathsStateChanged { state: Option<SearchPathsState> },
+}
+
+/// Size and modification time of every addon in a folder. Data chunks are
+/// left out, they come and go together with their `_dir.vpk` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FolderSnapshot(BTreeMap<String, (u64, Option<SystemTime>)>);
+
+impl FolderSnapshot {
+  pub fn read(folder: &Path) -> Self {
+    let mut files = BTreeMap::new();
+
+    if let Ok(entries) = std::fs::read_dir(folder) {
+      for entry in entries.flatten() {
+        let Ok(file_name) = entry.file_name().into_string() else {
+          continue;
+        };
+
+        if !file_name.ends_with(".vpk") || vpk::is_chunk_file(folder, &file_name) {
+          continue;
+        }
+
+        // Follows symlinks, so a replaced link target counts as a change.
+        if let Ok(metadata) = std::fs::metadata(entry.path()) {
+          if metadata.is_file() {
+            files.insert(file_name, (metadata.len(), metadata.modified().ok()));
+          }
+        }
+      }
+    }
+
+    FolderSnapshot(files)
+  }
+
+  /// Addon events turning `self` into `new`.
+  pub fn diff(&self, new: &FolderSnapshot, location: AddonLocation) -> Vec<WatchEvent> {
+    let mut events = vec![];
+
+    for (file_name, old_file) in &self.0 {
+      match new.0.get(file_name) {
+        None => events.push(WatchEvent::AddonRemoved {
+          location,
+          file_name: file_name.clone(),
+        }),
+        Some(new_file) if new_file != old_file => events.push(WatchEvent::AddonChanged {
+          location,
+          file_name: file_name.clone(),
+        }),
+        Some(_) => {}
+      }
+    }
+
+    for file_name in new.0.keys() {
+      if !self.0.contains_key(file_name) {
+        events.push(WatchEvent::AddonAdded {
+          location,
+          file_name: file_name.clone(),
+        });
+      }
+    }
+
+    events
+  }
+}
+
+struct WatchState {
+  storage: Option<(PathBuf, FolderSnapshot)>,
+  addons: Option<(PathBuf, FolderSnapshot)>,
+  game_info: Option<(GameInstall, Option<SearchPathsState>)>,
+}
+
+fn search_paths_state(game: &GameInstall) -> Option<SearchPathsState> {
+  game.search_paths_report().ok().map(|report| report.state)
+}
+
+impl WatchState {
+  /// Rescans whatever the changed paths belong to and returns the differences.
+  fn update(&mut self, changed_paths: &[PathBuf]) -> Vec<WatchEvent> {
+    let mut events = vec![];
+
+    let touches = |folder: &Path| {
+      changed_paths
+        .iter()
+        .any(|path| path == folder || path.parent() == Some(folder))
+    };
+
+    for (folder, location) in [
+      (&mut self.storage, AddonLocation::Stored),
+      (&mut self.addons, AddonLocation::Mounted),
+    ] {
+      if let Some((path, snapshot)) = folder {
+        if touches(path) {
+          let new_snapshot = FolderSnapshot::read(path);
+
+          events.extend(snapshot.diff(&new_snapshot, location));
+
+          *snapshot = new_snapshot;
+        }
+      }
+    }
+
+    if let Some((game, state)) = &mut self.game_info {
+      let game_info_path = game.game_info_path();
+
+      if changed_paths.iter().any(|path| path == &game_info_path) {
+        let new_state = search_paths_state(game);
+
+        if new_state != *state {
+          events.push(WatchEvent::SearchPathsStateChanged { state: new_state });
+
+          *state = new_state;
+        }
+      }
+    }
+
+    events
+  }
+}
+
+/// Watches the addon storage, the game addons folder and gameinfo.gi. Stops
+/// when dropped.
+pub struct Watcher {
+  _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl Watcher {
+  /// Starts watching and calls `on_events` with every debounced batch of
+  /// changes, from a background thread.
+  pub fn start(
+    game: Option<&GameInstall>,
+    storage: Option<&AddonStorage>,
+    on_events: impl Fn(Vec<WatchEvent>) + Send + 'static,
+  ) -> Result<Self, notify::Error> {
+    let addons_path = game.and_then(|game| game.create_addons_folder_if_not_exists().ok());
+
+    let state = Mutex::new(WatchState {
+      storage: storage.map(|storage| (storage.path.clone(), FolderSnapshot::read(&storage.path))),
+      addons: addons_path
+        .as_ref()
+        .map(|path| (path.clone(), FolderSnapshot::read(path))),
+      game_info: game.map(|game| (game.clone(), search_paths_state(game))),
+    });
+
+    let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, move |result: DebounceEventResult| {
+      let Ok(debounced_events) = result else {
+        return;
+      };
+
+      let changed_paths = debounced_events
+        .into_iter()
+        .map(|event| event.path)
+        .collect::<Vec<_>>();
+
+      let events = state.lock().unwrap().update(&changed_paths);
+
+      if !events.is_empty() {
+        on_events(events);
+      }
+    })?;
+
+    if let Some(storage) = storage {
+      debouncer
+        .watcher()
+        .watch(&storage.path, RecursiveMode::NonRecursive)?;
+    }
+
+    if let Some(addons_path) = addons_path.as_ref() {
+      debouncer
+        .watcher()
+        .watch(addons_path, RecursiveMode::NonRecursive)?;
+    }
+
+    // The folder is watched instead of the file, gameinfo.gi is replaced on
+    // every write.
+    if let Some(game_info_folder) = game
+      .map(|game| game.game_info_path())
+      .as_deref()
+      .and_then(Path::parent)
+    {
+      debouncer
+        .watcher()
+        .watch(game_info_folder, RecursiveMode::NonRecursive)?;
+    }
+
+    Ok(Watcher {
+      _debouncer: debouncer,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn diffs_folder_snapshots() {
+    let folder =
+      std::env::temp_dir().join(format!("citadel-cm-watcher-test-{}", std::process::id()));
+
+    _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    std::fs::write(folder.join("hud.vpk"), b"hud").unwrap();
+    std::fs::write(folder.join("maps.vpk"), b"maps").unwrap();
+    std::fs::write(folder.join("notes.txt"), b"notes").unwrap();
+
+    let old = FolderSnapshot::read(&folder);
+
+    std::fs::remove_file(folder.join("maps.vpk")).unwrap();
+    std::fs::write(folder.join("hud.vpk"), b"new hud").unwrap();
+    std::fs::write(folder.join("skins_dir.vpk"), b"skins").unwrap();
+    std::fs::write(folder.join("skins_000.vpk"), b"chunk").unwrap();
+
+    let new = FolderSnapshot::read(&folder);
+
+    assert_eq!(
+      old.diff(&new, AddonLocation::Stored),
+      vec![
+        WatchEvent::AddonChanged {
+          location: AddonLocation::Stored,
+          file_name: "hud.vpk".to_string(),
+        },
+        WatchEvent::AddonRemoved {
+          location: AddonLocation::Stored,
+          file_name: "maps.vpk".to_string(),
+        },
+        WatchEvent::AddonAdded {
+          location: AddonLocation::Stored,
+          file_name: "skins_dir.vpk".to_string(),
+        },
+      ]
+    );
+
+    std::fs::remove_dir_all(folder).unwrap();
+  }
+}
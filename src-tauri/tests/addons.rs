@@ -0,0 +1,936 @@
+//! End to end tests of addon and search path management against synthetic
+//! Deadlock installs.
+
+mod common;
+
+use app_lib::{
+  categories::{AddonCategory, AddonFilter},
+  config::ConfigStore,
+  game::{
+    AddonError, AddonLocation, AdoptOutcome, ConflictPolicy, DeployMethod, DriftState,
+    InstallAddonInfo, ProfileError, SearchPathsError,
+  },
+  gameinfo::SearchPathsState,
+  journal::{self, Operation, Transaction},
+  vpk::VpkDirectory,
+};
+use common::{vpk_bytes, FakeInstall, GameInfoVariant, CUSTOM_ENTRY};
+use serde_json::json;
+
+#[test]
+fn installs_mounts_unmounts_and_uninstalls() {
+  let install = FakeInstall::new("lifecycle", GameInfoVariant::Vanilla);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c", "panorama/hud.css"]);
+
+  assert_eq!(context.list_installed_addons().unwrap(), vec!["hud.vpk"]);
+
+  let directory = VpkDirectory::open(&install.storage.addon_path("hud.vpk")).unwrap();
+
+  assert_eq!(
+    directory
+      .entries
+      .iter()
+      .map(|entry| entry.path.as_str())
+      .collect::<Vec<_>>(),
+    vec!["panorama/hud.css", "materials/hud.vmat_c"]
+  );
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  assert_eq!(install.addons_folder_files(), vec!["pak01_dir.vpk"]);
+  assert_eq!(
+    std::fs::read(install.game.addons_path().join("pak01_dir.vpk")).unwrap(),
+    std::fs::read(install.storage.addon_path("hud.vpk")).unwrap()
+  );
+  assert_eq!(context.list_mounted_addons().unwrap(), vec!["hud.vpk"]);
+
+  assert!(matches!(
+    context.mount_addon("hud.vpk"),
+    Err(AddonError::AddonAlreadyMounted(addon)) if addon == "hud.vpk"
+  ));
+  assert!(matches!(
+    context.uninstall_addon("hud.vpk"),
+    Err(AddonError::CannotDeleteMountedAddon(_))
+  ));
+
+  context.unmount_addon("hud.vpk").unwrap();
+
+  assert!(install.addons_folder_files().is_empty());
+  assert!(context.list_mounted_addons().unwrap().is_empty());
+
+  context.uninstall_addon("hud.vpk").unwrap();
+
+  assert!(context.list_installed_addons().unwrap().is_empty());
+}
+
+#[test]
+fn unmounting_an_unstored_addon_moves_it_to_storage() {
+  let install = FakeInstall::new("unmount-unstored", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  std::fs::remove_file(install.storage.addon_path("hud.vpk")).unwrap();
+
+  context.unmount_addon("hud.vpk").unwrap();
+
+  assert!(install.addons_folder_files().is_empty());
+  assert_eq!(context.list_installed_addons().unwrap(), vec!["hud.vpk"]);
+}
+
+#[test]
+fn keeps_stored_and_hand_placed_addons_with_the_same_name_apart() {
+  let install = FakeInstall::new("name-collision", GameInfoVariant::Modded);
+  let context = install.context();
+  let hand_placed = vpk_bytes(&[("materials/hand_placed.vmat_c", b"by hand")]);
+
+  install.install("pak01_dir.vpk", &["materials/hud.vmat_c"]);
+
+  std::fs::write(
+    install.game.addons_path().join("pak01_dir.vpk"),
+    &hand_placed,
+  )
+  .unwrap();
+
+  assert!(context.list_mounted_addons().unwrap().is_empty());
+
+  context.mount_addon("pak01_dir.vpk").unwrap();
+
+  assert_eq!(
+    install.addons_folder_files(),
+    vec!["pak01_dir.vpk", "pak02_dir.vpk"]
+  );
+  assert_eq!(
+    context.list_mounted_addons().unwrap(),
+    vec!["pak01_dir.vpk"]
+  );
+
+  context.unmount_addon("pak01_dir.vpk").unwrap();
+
+  // Only the managed copy was removed, the hand placed one is untouched.
+  assert_eq!(install.addons_folder_files(), vec!["pak01_dir.vpk"]);
+  assert_eq!(
+    std::fs::read(install.game.addons_path().join("pak01_dir.vpk")).unwrap(),
+    hand_placed
+  );
+
+  context.uninstall_addon("pak01_dir.vpk").unwrap();
+  install.install("pak01_dir.vpk", &["materials/hud.vmat_c"]);
+
+  assert_eq!(
+    context.unmount_unmanaged_addon("pak01_dir.vpk").unwrap(),
+    Some("pak01 (2)_dir.vpk".to_string())
+  );
+  assert!(install.addons_folder_files().is_empty());
+  assert_eq!(
+    std::fs::read(install.storage.addon_path("pak01 (2)_dir.vpk")).unwrap(),
+    hand_placed
+  );
+  assert_ne!(
+    std::fs::read(install.storage.addon_path("pak01_dir.vpk")).unwrap(),
+    hand_placed
+  );
+}
+
+#[test]
+fn keeps_an_unstored_addon_installed_when_it_cant_be_stored() {
+  let install = FakeInstall::new("unmount-failed-copy", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  // Copying back into the storage fails even for root, it isn't a folder.
+  std::fs::remove_dir_all(&install.storage.path).unwrap();
+  std::fs::write(&install.storage.path, b"").unwrap();
+
+  let error = context.unmount_addon("hud.vpk").unwrap_err();
+
+  assert!(matches!(error, AddonError::CouldNotWriteInstallFolder(..)));
+  assert_eq!(
+    error.payload().params["path"],
+    json!(install.storage.addon_path("hud.vpk"))
+  );
+  assert_eq!(install.addons_folder_files(), vec!["pak01_dir.vpk"]);
+  assert_eq!(context.list_mounted_addons().unwrap(), vec!["hud.vpk"]);
+
+  std::fs::remove_file(&install.storage.path).unwrap();
+  std::fs::create_dir_all(&install.storage.path).unwrap();
+}
+
+#[test]
+fn fails_instead_of_copying_when_reflinks_fail() {
+  let install = FakeInstall::new("reflink-fallback", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  // Picked without probing, as if the storage had moved to another volume.
+  install
+    .config
+    .set("deploy_method", json!(DeployMethod::Reflink));
+
+  if context.deploy_capabilities().reflink {
+    context.mount_addon("hud.vpk").unwrap();
+  } else {
+    assert!(matches!(
+      context.mount_addon("hud.vpk"),
+      Err(AddonError::CouldNotLinkAddon(path, _))
+        if path == install.game.addons_path().join("pak01_dir.vpk")
+    ));
+    assert!(install.addons_folder_files().is_empty());
+    assert!(context.list_mounted_addons().unwrap().is_empty());
+  }
+}
+
+#[test]
+fn storing_again_replaces_hard_linked_addons() {
+  let install = FakeInstall::new("restore-hardlink", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.set_deploy_method(DeployMethod::Hardlink).unwrap();
+  context.mount_addon("hud.vpk").unwrap();
+
+  let deployed_path = install.game.addons_path().join("pak01_dir.vpk");
+
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::MetadataExt;
+
+    assert_eq!(
+      std::fs::metadata(&deployed_path).unwrap().ino(),
+      std::fs::metadata(install.storage.addon_path("hud.vpk"))
+        .unwrap()
+        .ino()
+    );
+  }
+
+  let deployed = std::fs::read(&deployed_path).unwrap();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c", "sounds/new.vsnd_c"]);
+
+  // The deployed file is the previous version, not a half rewritten one.
+  assert_eq!(std::fs::read(&deployed_path).unwrap(), deployed);
+  assert_ne!(
+    std::fs::read(install.storage.addon_path("hud.vpk")).unwrap(),
+    deployed
+  );
+  assert_eq!(context.list_installed_addons().unwrap(), vec!["hud.vpk"]);
+}
+
+#[test]
+fn refuses_conflicting_addons() {
+  let install = FakeInstall::new("conflicts", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+  install.install(
+    "other_hud.vpk",
+    &["materials/hud.vmat_c", "sounds/a.vsnd_c"],
+  );
+
+  assert!(context.mount_addon("hud.vpk").unwrap().is_empty());
+  assert_eq!(context.mount_addon("other_hud.vpk").unwrap().len(), 1);
+
+  context.unmount_addon("other_hud.vpk").unwrap();
+  context.set_conflict_policy(ConflictPolicy::Refuse);
+
+  let error = context.mount_addon("other_hud.vpk").unwrap_err();
+
+  assert!(matches!(
+    &error,
+    AddonError::AddonWouldConflict(conflicts) if conflicts.len() == 1
+  ));
+  assert_eq!(
+    json!(error.payload().params),
+    json!({
+      "count": 1,
+      "paths": ["materials/hud.vmat_c"],
+      "addons": ["hud.vpk", "other_hud.vpk"],
+    })
+  );
+  assert_eq!(install.addons_folder_files(), vec!["pak01_dir.vpk"]);
+}
+
+#[test]
+fn applies_profiles_in_order() {
+  let install = FakeInstall::new("profile", GameInfoVariant::Modded);
+  let context = install.context();
+
+  for name in ["a", "b", "c"] {
+    install.install(
+      &format!("{}.vpk", name),
+      &[&format!("materials/{}.vmat_c", name)],
+    );
+  }
+
+  context.mount_addon("c.vpk").unwrap();
+  context.mount_addon("a.vpk").unwrap();
+  context
+    .create_profile(
+      "Casual",
+      vec![
+        "b.vpk".to_string(),
+        "deleted.vpk".to_string(),
+        "a.vpk".to_string(),
+      ],
+    )
+    .unwrap();
+
+  let report = json!(context.apply_profile("Casual").unwrap());
+
+  assert_eq!(
+    report["results"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|result| (
+        result["addon"].clone(),
+        result["action"].clone(),
+        result["error"]["code"].clone()
+      ))
+      .collect::<Vec<_>>(),
+    vec![
+      (json!("c.vpk"), json!("Unmount"), json!(null)),
+      (json!("b.vpk"), json!("Mount"), json!(null)),
+      (
+        json!("deleted.vpk"),
+        json!("Mount"),
+        json!("AddonIsNotInstalled")
+      ),
+    ]
+  );
+  assert_eq!(
+    context.list_mounted_addons().unwrap(),
+    vec!["b.vpk", "a.vpk"]
+  );
+}
+
+#[test]
+fn rolls_back_profiles_on_the_first_failure() {
+  let install = FakeInstall::new("profile-rollback", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+  install.install("other_hud.vpk", &["materials/hud.vmat_c"]);
+  install.install("maps.vpk", &["maps/street.vmap_c"]);
+
+  context.mount_addon("maps.vpk").unwrap();
+  context.mount_addon("hud.vpk").unwrap();
+  context.set_conflict_policy(ConflictPolicy::Refuse);
+  context
+    .create_profile(
+      "Broken",
+      vec!["other_hud.vpk".to_string(), "hud.vpk".to_string()],
+    )
+    .unwrap();
+
+  let mounted_addons = context.list_mounted_addons().unwrap();
+  let addons_folder_files = install.addons_folder_files();
+
+  assert!(matches!(
+    context.apply_profile("Broken").unwrap_err(),
+    ProfileError::Addon(AddonError::AddonWouldConflict(conflicts)) if conflicts.len() == 1
+  ));
+  assert_eq!(context.list_mounted_addons().unwrap(), mounted_addons);
+  assert_eq!(install.addons_folder_files(), addons_folder_files);
+  assert!(journal::read(&install.root.join("data/journal.json"))
+    .unwrap()
+    .is_none());
+}
+
+#[test]
+fn detects_and_resyncs_drifted_copies() {
+  let install = FakeInstall::new("drift", GameInfoVariant::Modded);
+  let context = install.context();
+  let stored_path = install.storage.addon_path("hud.vpk");
+  let deployed_path = install.game.addons_path().join("pak01_dir.vpk");
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  let drift_state = || {
+    let drifts = context.verify_deployment().unwrap();
+
+    assert_eq!(drifts.len(), 1);
+
+    (drifts[0].state, drifts[0].changed)
+  };
+
+  assert_eq!(drift_state(), (DriftState::InSync, None));
+
+  std::fs::write(
+    &stored_path,
+    vpk_bytes(&[("materials/new_hud.vmat_c", b"")]),
+  )
+  .unwrap();
+
+  assert_eq!(
+    drift_state(),
+    (DriftState::Stale, Some(AddonLocation::Stored))
+  );
+
+  context
+    .resync_addon("hud.vpk", AddonLocation::Stored)
+    .unwrap();
+
+  assert_eq!(drift_state(), (DriftState::InSync, None));
+  assert_eq!(
+    std::fs::read(&deployed_path).unwrap(),
+    std::fs::read(&stored_path).unwrap()
+  );
+
+  std::fs::write(&stored_path, vpk_bytes(&[("materials/hud_2.vmat_c", b"")])).unwrap();
+  std::fs::write(
+    &deployed_path,
+    vpk_bytes(&[("materials/hud_3.vmat_c", b"")]),
+  )
+  .unwrap();
+
+  assert_eq!(drift_state(), (DriftState::Diverged, None));
+
+  std::fs::remove_file(&stored_path).unwrap();
+
+  assert_eq!(drift_state(), (DriftState::Orphaned, None));
+
+  context
+    .resync_addon("hud.vpk", AddonLocation::Mounted)
+    .unwrap();
+
+  assert_eq!(drift_state(), (DriftState::InSync, None));
+}
+
+#[cfg(unix)]
+#[test]
+fn switches_deploy_method_in_place_and_leaves_hand_placed_addons() {
+  let install = FakeInstall::new("switch-deploy-method", GameInfoVariant::Modded);
+  let context = install.context();
+  let addons_path = install.game.addons_path();
+  let hand_placed = vpk_bytes(&[("materials/hand_placed.vmat_c", b"by hand")]);
+  let is_symlink = |file_name: &str| {
+    addons_path
+      .join(file_name)
+      .symlink_metadata()
+      .unwrap()
+      .file_type()
+      .is_symlink()
+  };
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+  install.install("maps.vpk", &["maps/street.vmap_c"]);
+  install.install("pak03_dir.vpk", &["sounds/a.vsnd_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+  context.mount_addon("maps.vpk").unwrap();
+  context.unmount_addon("hud.vpk").unwrap();
+
+  // Named like a stored addon, after a free slot.
+  std::fs::write(addons_path.join("pak03_dir.vpk"), &hand_placed).unwrap();
+
+  let deployed = |context: &app_lib::game::Context| {
+    context
+      .scan_mounted_addons()
+      .unwrap()
+      .iter()
+      .map(|addon| {
+        let addon = json!(addon);
+
+        (addon["deployedFileName"].clone(), addon["managed"].clone())
+      })
+      .collect::<Vec<_>>()
+  };
+
+  let before = deployed(&context);
+
+  assert_eq!(
+    before,
+    vec![
+      (json!("pak02_dir.vpk"), json!(true)),
+      (json!("pak03_dir.vpk"), json!(false))
+    ]
+  );
+
+  context.set_deploy_method(DeployMethod::Symlink).unwrap();
+
+  assert_eq!(deployed(&context), before);
+  assert!(is_symlink("pak02_dir.vpk"));
+  assert!(!is_symlink("pak03_dir.vpk"));
+  assert_eq!(
+    std::fs::read(addons_path.join("pak03_dir.vpk")).unwrap(),
+    hand_placed
+  );
+  assert_ne!(
+    std::fs::read(install.storage.addon_path("pak03_dir.vpk")).unwrap(),
+    hand_placed
+  );
+
+  context.set_deploy_method(DeployMethod::Copy).unwrap();
+
+  assert_eq!(deployed(&context), before);
+  assert!(!is_symlink("pak02_dir.vpk"));
+}
+
+#[test]
+fn rolls_back_interrupted_deploy_method_switch() {
+  let install = FakeInstall::new("journal", GameInfoVariant::Modded);
+  let context = install.context();
+
+  for name in ["a", "b"] {
+    install.install(
+      &format!("{}.vpk", name),
+      &[&format!("materials/{}.vmat_c", name)],
+    );
+    context.mount_addon(&format!("{}.vpk", name)).unwrap();
+  }
+
+  // Interrupted after the journal was written and the first addon unmounted.
+  journal::write(
+    &install.root.join("data/journal.json"),
+    &Transaction {
+      operation: Operation::SetDeployMethod {
+        from: DeployMethod::Copy,
+        to: DeployMethod::Symlink,
+      },
+      mounted: vec!["a.vpk".to_string(), "b.vpk".to_string()],
+      created_files: vec![],
+    },
+  )
+  .unwrap();
+
+  context.unmount_addon("a.vpk").unwrap();
+  install
+    .config
+    .set("deploy_method", json!(DeployMethod::Symlink));
+
+  assert!(matches!(
+    context.set_deploy_method(DeployMethod::Copy),
+    Err(AddonError::OperationInProgress)
+  ));
+
+  assert!(matches!(
+    context.recover().unwrap(),
+    Some(Operation::SetDeployMethod { .. })
+  ));
+  assert_eq!(context.deploy_method(), DeployMethod::Copy);
+  assert_eq!(
+    context.list_mounted_addons().unwrap(),
+    vec!["a.vpk", "b.vpk"]
+  );
+  assert_eq!(context.recover().unwrap(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn adopts_unmanaged_addons() {
+  use std::os::unix::fs::MetadataExt;
+
+  let install = FakeInstall::new("adopt", GameInfoVariant::Modded);
+  let context = install.context();
+  let addons_path = install.game.addons_path();
+
+  install
+    .config
+    .set("deploy_method", json!(DeployMethod::Hardlink));
+
+  // Same content as the stored but not installed addon.
+  install.install("maps.vpk", &["maps/street.vmap_c"]);
+
+  std::fs::copy(
+    install.storage.addon_path("maps.vpk"),
+    addons_path.join("pak02_dir.vpk"),
+  )
+  .unwrap();
+  std::fs::write(
+    addons_path.join("pak01_dir.vpk"),
+    vpk_bytes(&[("materials/hud.vmat_c", b"hud")]),
+  )
+  .unwrap();
+  std::fs::write(
+    addons_path.join("loose.vpk"),
+    vpk_bytes(&[("materials/loose.vmat_c", b"loose")]),
+  )
+  .unwrap();
+
+  let mut adopted = context.adopt_unmanaged_addons(true).unwrap();
+
+  adopted.sort_by(|a, b| a.deployed_file_name.cmp(&b.deployed_file_name));
+
+  assert_eq!(
+    adopted
+      .iter()
+      .map(|addon| (addon.file_name.as_deref(), addon.outcome, addon.redeployed))
+      .collect::<Vec<_>>(),
+    vec![
+      (None, AdoptOutcome::NotLoadable, false),
+      (Some("pak01_dir.vpk"), AdoptOutcome::Stored, true),
+      (Some("maps.vpk"), AdoptOutcome::Matched, true),
+    ]
+  );
+  assert_eq!(
+    context
+      .scan_mounted_addons()
+      .unwrap()
+      .iter()
+      .map(|addon| json!(addon))
+      .filter(|addon| addon["managed"] == true)
+      .map(|addon| addon["fileName"].clone())
+      .collect::<Vec<_>>(),
+    vec![json!("pak01_dir.vpk"), json!("maps.vpk")]
+  );
+  for (stored, deployed) in [
+    ("pak01_dir.vpk", "pak01_dir.vpk"),
+    ("maps.vpk", "pak02_dir.vpk"),
+  ] {
+    let stored = std::fs::metadata(install.storage.addon_path(stored)).unwrap();
+    let deployed = std::fs::metadata(addons_path.join(deployed)).unwrap();
+
+    assert_eq!(deployed.ino(), stored.ino());
+  }
+
+  // Everything is managed now.
+  assert!(context
+    .adopt_unmanaged_addons(true)
+    .unwrap()
+    .iter()
+    .all(|addon| addon.outcome == AdoptOutcome::NotLoadable));
+}
+
+#[test]
+fn classifies_and_filters_addons() {
+  let install = FakeInstall::new("categories", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install(
+    "haze.vpk",
+    &[
+      "models/heroes_staging/haze/haze.vmdl_c",
+      "materials/models/heroes_staging/haze/haze.vmat_c",
+    ],
+  );
+  install.install("hud.vpk", &["panorama/layout/citadel_hud.vxml_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  let classifications = context.classify_addons(false).unwrap();
+
+  assert_eq!(
+    classifications["haze.vpk"].categories,
+    vec![AddonCategory::HeroSkin]
+  );
+  assert_eq!(classifications["haze.vpk"].heroes, vec!["haze"]);
+  assert_eq!(
+    classifications["hud.vpk"].categories,
+    vec![AddonCategory::Hud]
+  );
+
+  let stored = install.config.get("addons").unwrap();
+
+  assert_eq!(stored["haze.vpk"]["heroes"][0], "haze");
+
+  let hud_filter = AddonFilter {
+    category: Some(AddonCategory::Hud),
+    hero: None,
+  };
+
+  assert_eq!(
+    context
+      .filter_addons(context.list_installed_addons().unwrap(), &hud_filter)
+      .unwrap(),
+    vec!["hud.vpk"]
+  );
+  assert!(context
+    .filter_addons(
+      context.list_mounted_addons().unwrap(),
+      &AddonFilter {
+        category: None,
+        hero: Some("haze".to_string()),
+      },
+    )
+    .unwrap()
+    .is_empty());
+}
+
+#[test]
+fn imports_archives_without_overwriting_stored_addons() {
+  let install = FakeInstall::new("import", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("pak01_dir.vpk", &["materials/hud.vmat_c"]);
+
+  let stored = std::fs::read(install.storage.addon_path("pak01_dir.vpk")).unwrap();
+
+  context.mount_addon("pak01_dir.vpk").unwrap();
+
+  let archive =
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/archive/variants.zip");
+
+  // Both variants in the archive are named `pak01_dir.vpk` too.
+  assert_eq!(
+    context.import_archive(&archive, &[]).unwrap(),
+    vec!["pak01 (2)_dir.vpk", "pak01 (3)_dir.vpk"]
+  );
+  assert_eq!(
+    std::fs::read(install.storage.addon_path("pak01_dir.vpk")).unwrap(),
+    stored
+  );
+  assert_eq!(
+    install.storage.chunk_files("pak01 (2)_dir.vpk"),
+    vec!["pak01 (2)_000.vpk", "pak01 (2)_001.vpk"]
+  );
+  assert_eq!(
+    std::fs::read(install.storage.addon_path("pak01 (2)_000.vpk")).unwrap(),
+    b"chunk a 0"
+  );
+  assert!(install.storage.chunk_files("pak01 (3)_dir.vpk").is_empty());
+}
+
+#[test]
+fn imports_archived_addons_with_uppercase_extensions() {
+  let install = FakeInstall::new("import-uppercase", GameInfoVariant::Modded);
+  let context = install.context();
+  let archive = install.downloads.join("uppercase.zip");
+
+  let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+
+  writer
+    .start_file("Mod.VPK", zip::write::SimpleFileOptions::default())
+    .unwrap();
+  std::io::Write::write_all(&mut writer, &vpk_bytes(&[("materials/hud.vmat_c", b"hud")])).unwrap();
+  writer.finish().unwrap();
+
+  assert_eq!(
+    context.import_archive(&archive, &[]).unwrap(),
+    vec!["Mod.vpk"]
+  );
+  assert_eq!(context.list_installed_addons().unwrap(), vec!["Mod.vpk"]);
+
+  context.mount_addon("Mod.vpk").unwrap();
+
+  assert_eq!(context.list_mounted_addons().unwrap(), vec!["Mod.vpk"]);
+}
+
+/// Solid red RGB image as PNG.
+fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+  let mut png = vec![];
+  let mut encoder = png::Encoder::new(&mut png, width, height);
+
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .unwrap()
+    .write_image_data(&[255, 0, 0].repeat((width * height) as usize))
+    .unwrap();
+
+  png
+}
+
+#[test]
+fn extracts_and_caches_thumbnails() {
+  let install = FakeInstall::new("thumbnails", GameInfoVariant::Modded);
+  let context = install.context();
+
+  let card = png_bytes(512, 256);
+  let path = install.write_vpk(
+    "card.vpk",
+    &[
+      ("panorama/images/heroes/haze_card.png", &card),
+      ("materials/models/haze_normal.png", &png_bytes(1024, 1024)),
+    ],
+  );
+
+  context
+    .install_addon(InstallAddonInfo {
+      file_path: path.to_string_lossy().to_string(),
+      file_name: None,
+      display_name: None,
+    })
+    .unwrap();
+  install.install("sounds.vpk", &["sounds/a.vsnd_c"]);
+
+  let thumbnail = context.addon_thumbnail("card.vpk").unwrap().unwrap();
+
+  assert_eq!(thumbnail.path, install.storage.thumbnail_path("card.vpk"));
+  assert!(thumbnail.data_url.starts_with("data:image/png;base64,"));
+
+  let decoder = png::Decoder::new(std::fs::File::open(&thumbnail.path).unwrap());
+  let info = decoder.read_info().unwrap();
+
+  assert_eq!((info.info().width, info.info().height), (256, 128));
+
+  // Served from the cache from now on.
+  assert_eq!(
+    context
+      .addon_thumbnail("card.vpk")
+      .unwrap()
+      .unwrap()
+      .data_url,
+    thumbnail.data_url
+  );
+
+  assert!(context.addon_thumbnail("sounds.vpk").unwrap().is_none());
+  assert!(install.storage.thumbnail_path("sounds.vpk").exists());
+  assert!(matches!(
+    context.addon_thumbnail("missing.vpk"),
+    Err(AddonError::AddonIsNotInstalled(_))
+  ));
+
+  context.uninstall_addon("card.vpk").unwrap();
+
+  assert!(!thumbnail.path.exists());
+}
+
+#[test]
+fn reports_and_patches_every_game_info_variant() {
+  for (variant, state) in [
+    (GameInfoVariant::Vanilla, SearchPathsState::Vanilla),
+    (GameInfoVariant::Modded, SearchPathsState::Modded),
+    (GameInfoVariant::Custom, SearchPathsState::Custom),
+  ] {
+    let install = FakeInstall::new(&format!("search-paths-{:?}", variant), variant);
+    let context = install.context();
+
+    assert_eq!(context.search_paths_report().unwrap().state, state);
+
+    context.mod_search_paths().unwrap();
+
+    let patched_state = context.search_paths_report().unwrap().state;
+
+    context.reset_search_paths().unwrap();
+
+    let reset_state = context.search_paths_report().unwrap().state;
+
+    match variant {
+      GameInfoVariant::Custom => {
+        // Changes of the user are kept through patching and resetting.
+        assert_eq!(patched_state, SearchPathsState::Custom);
+        assert_eq!(reset_state, SearchPathsState::Custom);
+        assert!(install.game_info().contains(CUSTOM_ENTRY));
+      }
+      GameInfoVariant::Modded => {
+        // Modded before, resetting only removes what patching inserted.
+        assert_eq!(patched_state, SearchPathsState::Modded);
+        assert_eq!(reset_state, SearchPathsState::Modded);
+        assert_eq!(install.game_info(), GameInfoVariant::Modded.content());
+      }
+      GameInfoVariant::Vanilla => {
+        assert_eq!(patched_state, SearchPathsState::Modded);
+        assert_eq!(reset_state, SearchPathsState::Vanilla);
+        assert_eq!(install.game_info(), GameInfoVariant::Vanilla.content());
+      }
+    }
+  }
+}
+
+#[test]
+fn fails_cleanly_on_missing_files() {
+  let install = FakeInstall::new("missing", GameInfoVariant::Vanilla);
+  let context = install.context();
+
+  assert!(matches!(
+    context.install_addon(InstallAddonInfo {
+      file_path: install
+        .downloads
+        .join("missing.vpk")
+        .to_string_lossy()
+        .to_string(),
+      file_name: None,
+      display_name: None,
+    }),
+    Err(AddonError::InvalidAddonFile)
+  ));
+  assert!(matches!(
+    context.mount_addon("missing.vpk"),
+    Err(AddonError::AddonIsNotInstalled(_))
+  ));
+  assert!(matches!(
+    context.unmount_addon("missing.vpk"),
+    Err(AddonError::AddonIsNotMounted(_))
+  ));
+
+  // An addon deleted from the addons folder by hand is no longer installed.
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  std::fs::remove_file(install.game.addons_path().join("pak01_dir.vpk")).unwrap();
+
+  assert!(context.list_mounted_addons().unwrap().is_empty());
+  assert!(matches!(
+    context.unmount_addon("hud.vpk"),
+    Err(AddonError::AddonIsNotMounted(_))
+  ));
+
+  std::fs::remove_file(install.game.game_info_path()).unwrap();
+
+  assert!(matches!(
+    context.mod_search_paths(),
+    Err(SearchPathsError::CouldNotReadGameInfo(path, _)) if path == install.game.game_info_path()
+  ));
+}
+
+#[test]
+fn fails_cleanly_when_files_cant_be_written() {
+  let install = FakeInstall::new("unwritable", GameInfoVariant::Vanilla);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  // Paths taken by something of the wrong kind fail to be written even for
+  // root, unlike read-only permissions.
+  let deployed_path = install.game.addons_path().join("pak01_dir.vpk");
+
+  std::fs::create_dir_all(&deployed_path).unwrap();
+
+  assert!(matches!(
+    context.mount_addon("hud.vpk"),
+    Err(AddonError::CouldNotWriteAddonFolder(path, _)) if path == deployed_path
+  ));
+  assert!(context.list_mounted_addons().unwrap().is_empty());
+
+  std::fs::remove_dir(&deployed_path).unwrap();
+
+  assert!(install.addons_folder_files().is_empty());
+
+  let storage_path = install.root.join("storage-moved-away");
+
+  std::fs::rename(&install.storage.path, &storage_path).unwrap();
+  std::fs::write(&install.storage.path, b"").unwrap();
+
+  let path = install.write_vpk("skins.vpk", &[("models/skin.vmdl_c", b"skin")]);
+
+  assert!(matches!(
+    context.install_addon(InstallAddonInfo {
+      file_path: path.to_string_lossy().to_string(),
+      file_name: None,
+      display_name: None,
+    }),
+    Err(AddonError::CouldNotWriteInstallFolder(path, _)) if path.starts_with(&install.storage.path)
+  ));
+
+  std::fs::remove_file(&install.storage.path).unwrap();
+  std::fs::rename(&storage_path, &install.storage.path).unwrap();
+
+  assert_eq!(context.list_installed_addons().unwrap(), vec!["hud.vpk"]);
+
+  let game_info_temp_path = install.game.path.join("game/citadel/gameinfo.gi.tmp");
+
+  std::fs::create_dir_all(&game_info_temp_path).unwrap();
+
+  assert!(matches!(
+    context.mod_search_paths(),
+    Err(SearchPathsError::CouldNotWriteGameInfo(path, _)) if path == install.game.game_info_path()
+  ));
+
+  std::fs::remove_dir(&game_info_temp_path).unwrap();
+
+  assert_eq!(install.game_info(), GameInfoVariant::Vanilla.content());
+}
@@ -0,0 +1,134 @@
+//! Runs the `citadel-cm` binary against synthetic Deadlock installs and checks
+//! its JSON output and exit codes.
+
+// The settings folder is moved with `XDG_DATA_HOME`, which is only honored on
+// Linux.
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Command;
+
+use app_lib::config::{APP_IDENTIFIER, CONFIG_FILE_NAME};
+use common::{FakeInstall, GameInfoVariant};
+use serde_json::{json, Value};
+
+/// Points the command line at the game and storage folders of `install`.
+fn configure(install: &FakeInstall) {
+  let data_path = install.root.join("xdg").join(APP_IDENTIFIER);
+
+  std::fs::create_dir_all(&data_path).unwrap();
+  std::fs::write(
+    data_path.join(CONFIG_FILE_NAME),
+    json!({
+      "game_path": install.game.path,
+      "install_path": install.storage.path,
+    })
+    .to_string(),
+  )
+  .unwrap();
+}
+
+/// Runs the command line with `args`, returns its output and exit code.
+fn cli(install: &FakeInstall, args: &[&str]) -> (Value, i32) {
+  let output = Command::new(env!("CARGO_BIN_EXE_citadel-cm"))
+    .args(args)
+    .env("XDG_DATA_HOME", install.root.join("xdg"))
+    .output()
+    .unwrap();
+
+  let result = serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+    panic!(
+      "{:?} printed invalid JSON ({}): {}",
+      args,
+      e,
+      String::from_utf8_lossy(&output.stdout)
+    )
+  });
+
+  (result, output.status.code().unwrap())
+}
+
+#[test]
+fn installs_mounts_and_patches() {
+  let install = FakeInstall::new("cli-lifecycle", GameInfoVariant::Vanilla);
+
+  configure(&install);
+
+  let path = install.write_vpk("hud.vpk", &[("panorama/hud.css", b"hud")]);
+
+  assert_eq!(
+    cli(&install, &["install", path.to_str().unwrap()]),
+    (json!({ "ok": true, "result": null }), 0)
+  );
+
+  let (output, code) = cli(&install, &["mount", "hud.vpk"]);
+
+  assert_eq!(code, 0);
+  assert_eq!(output["result"]["conflicts"], json!([]));
+  assert_eq!(install.addons_folder_files(), vec!["pak01_dir.vpk"]);
+
+  let (output, code) = cli(&install, &["list"]);
+
+  assert_eq!(code, 0);
+  assert_eq!(output["result"]["stored"], json!(["hud.vpk"]));
+  assert_eq!(output["result"]["mounted"].as_array().unwrap().len(), 1);
+
+  assert_eq!(cli(&install, &["patch"]).1, 0);
+  assert_eq!(
+    cli(&install, &["status"]).0["result"]["searchPathsState"],
+    "Modded"
+  );
+
+  assert_eq!(cli(&install, &["unmount", "hud.vpk"]).1, 0);
+  assert!(install.addons_folder_files().is_empty());
+}
+
+#[test]
+fn exits_with_a_code_per_error() {
+  let install = FakeInstall::new("cli-errors", GameInfoVariant::Vanilla);
+
+  configure(&install);
+
+  for (args, code, error_code) in [
+    (&["mount", "missing.vpk"][..], 28, "AddonIsNotInstalled"),
+    (&["unmount", "missing.vpk"], 30, "AddonIsNotMounted"),
+    (&["uninstall", "missing.vpk"], 28, "AddonIsNotInstalled"),
+    (&["profile", "apply", "missing"], 40, "ProfileNotFound"),
+  ] {
+    let (output, exit_code) = cli(&install, args);
+
+    assert_eq!(exit_code, code, "{:?}", args);
+    assert_eq!(output["ok"], false);
+    assert_eq!(output["error"]["code"], error_code, "{:?}", args);
+    assert_eq!(
+      output["error"]["params"]["addon"].is_string(),
+      args[0] != "profile"
+    );
+  }
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+  let install = FakeInstall::new("cli-usage", GameInfoVariant::Vanilla);
+
+  configure(&install);
+
+  for args in [
+    &[][..],
+    &["frobnicate"],
+    &["mount"],
+    &["mount", "a.vpk", "b.vpk"],
+    &["list", "--category"],
+    &["list", "--category", "Hats"],
+    &["status", "extra"],
+    &["profile"],
+  ] {
+    let (output, exit_code) = cli(&install, args);
+
+    assert_eq!(exit_code, 2, "{:?}", args);
+    assert_eq!(output["error"]["code"], "InvalidArguments", "{:?}", args);
+  }
+
+  assert!(install.addons_folder_files().is_empty());
+}
@@ -0,0 +1,210 @@
+//! Synthetic Deadlock installs for the integration tests: a game folder with
+//! `game/citadel/gameinfo.gi`, an addon storage folder and an app data folder
+//! in a fresh temporary folder, plus generated VPK files.
+
+// Every test crate uses a different part of the fixtures.
+#![allow(dead_code)]
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use app_lib::{
+  config::FileConfigStore,
+  copy_job::CopyJob,
+  game::{Context, GameInstall, InstallAddonInfo},
+  storage::AddonStorage,
+  vpk,
+};
+
+const VPK_SIGNATURE: u32 = 0x55aa1234;
+
+pub const VANILLA: &str = include_str!("../fixtures/gameinfo/vanilla.gi");
+pub const MODDED: &str = include_str!("../fixtures/gameinfo/modded.gi");
+
+/// Search path entry of the custom variant, kept through patching.
+pub const CUSTOM_ENTRY: &str = "Game\t\t\t\tcitadel_custom";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameInfoVariant {
+  Vanilla,
+  Modded,
+  /// Vanilla with an extra search path.
+  Custom,
+}
+
+impl GameInfoVariant {
+  pub fn content(self) -> String {
+    match self {
+      GameInfoVariant::Vanilla => VANILLA.to_string(),
+      GameInfoVariant::Modded => MODDED.to_string(),
+      GameInfoVariant::Custom => VANILLA.replacen(
+        "Game\t\t\t\tcore\n",
+        &format!("Game\t\t\t\tcore\n\t\t\t{}\n", CUSTOM_ENTRY),
+        1,
+      ),
+    }
+  }
+}
+
+/// VPK v1 directory with every file stored in the `_dir.vpk` file itself.
+/// `files` are paths inside the VPK with their content.
+pub fn vpk_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+  // extension -> folder -> (name, content)
+  let mut tree_files = BTreeMap::<&str, BTreeMap<&str, Vec<(&str, &[u8])>>>::new();
+
+  for (path, content) in files {
+    let (folder, file_name) = path.rsplit_once('/').unwrap_or((" ", path));
+    let (name, extension) = file_name.rsplit_once('.').unwrap_or((file_name, " "));
+
+    tree_files
+      .entry(extension)
+      .or_default()
+      .entry(folder)
+      .or_default()
+      .push((name, content));
+  }
+
+  let mut tree = vec![];
+  let mut data = vec![];
+
+  let push_string = |tree: &mut Vec<u8>, string: &str| {
+    tree.extend_from_slice(string.as_bytes());
+    tree.push(0);
+  };
+
+  for (extension, folders) in &tree_files {
+    push_string(&mut tree, extension);
+
+    for (folder, names) in folders {
+      push_string(&mut tree, folder);
+
+      for (name, content) in names {
+        push_string(&mut tree, name);
+
+        tree.extend_from_slice(&0u32.to_le_bytes());
+        tree.extend_from_slice(&0u16.to_le_bytes());
+        tree.extend_from_slice(&vpk::DIR_ARCHIVE_INDEX.to_le_bytes());
+        tree.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        tree.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        tree.extend_from_slice(&0xffffu16.to_le_bytes());
+
+        data.extend_from_slice(content);
+      }
+
+      tree.push(0);
+    }
+
+    tree.push(0);
+  }
+
+  tree.push(0);
+
+  let mut content = vec![];
+
+  content.extend_from_slice(&VPK_SIGNATURE.to_le_bytes());
+  content.extend_from_slice(&1u32.to_le_bytes());
+  content.extend_from_slice(&(tree.len() as u32).to_le_bytes());
+  content.extend_from_slice(&tree);
+  content.extend_from_slice(&data);
+
+  content
+}
+
+pub struct FakeInstall {
+  pub root: PathBuf,
+  pub game: GameInstall,
+  pub storage: AddonStorage,
+  pub config: FileConfigStore,
+  /// Folder addons are installed from, like a downloads folder.
+  pub downloads: PathBuf,
+}
+
+impl FakeInstall {
+  pub fn new(name: &str, variant: GameInfoVariant) -> Self {
+    let root = std::env::temp_dir().join(format!(
+      "citadel-cm-integration-{}-{}",
+      name,
+      std::process::id()
+    ));
+
+    _ = std::fs::remove_dir_all(&root);
+
+    let game = GameInstall::new(root.join("Deadlock"));
+    let storage = AddonStorage::new(root.join("storage"));
+    let downloads = root.join("downloads");
+
+    std::fs::create_dir_all(game.path.join("game/citadel/addons")).unwrap();
+    std::fs::create_dir_all(&storage.path).unwrap();
+    std::fs::create_dir_all(&downloads).unwrap();
+    std::fs::write(game.game_info_path(), variant.content()).unwrap();
+
+    let config = FileConfigStore::open(&root.join("data/.config")).unwrap();
+
+    FakeInstall {
+      root,
+      game,
+      storage,
+      config,
+      downloads,
+    }
+  }
+
+  pub fn context(&self) -> Context<'_> {
+    Context {
+      game: Some(self.game.clone()),
+      storage: Some(self.storage.clone()),
+      data_path: Some(self.root.join("data")),
+      config: &self.config,
+      copy_job: CopyJob::default(),
+    }
+  }
+
+  /// Writes a VPK to the downloads folder and returns its path.
+  pub fn write_vpk(&self, file_name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    let path = self.downloads.join(file_name);
+
+    std::fs::write(&path, vpk_bytes(files)).unwrap();
+
+    path
+  }
+
+  /// Writes a VPK containing `files` to the downloads folder and installs it.
+  pub fn install(&self, file_name: &str, files: &[&str]) {
+    let files = files
+      .iter()
+      .map(|path| (*path, path.as_bytes()))
+      .collect::<Vec<_>>();
+
+    let path = self.write_vpk(file_name, &files);
+
+    self
+      .context()
+      .install_addon(InstallAddonInfo {
+        file_path: path.to_string_lossy().to_string(),
+        file_name: None,
+        display_name: None,
+      })
+      .unwrap();
+  }
+
+  pub fn game_info(&self) -> String {
+    std::fs::read_to_string(self.game.game_info_path()).unwrap()
+  }
+
+  /// Names of the files in the game addons folder.
+  pub fn addons_folder_files(&self) -> Vec<String> {
+    let mut files = std::fs::read_dir(self.game.addons_path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+      .collect::<Vec<_>>();
+
+    files.sort();
+
+    files
+  }
+}
+
+impl Drop for FakeInstall {
+  fn drop(&mut self) {
+    _ = std::fs::remove_dir_all(&self.root);
+  }
+}
@@ -0,0 +1,162 @@
+//! End to end tests of moving the addon storage and setting the game path.
+
+mod common;
+
+use app_lib::{
+  config::ConfigStore,
+  error::ErrorPayload,
+  game::{Context, DeployMethod, GameInstall},
+  migration::{MigrationConflict, MigrationConflictPolicy, MigrationOptions, MigrationOutcome},
+  storage::AddonStorage,
+};
+use common::{vpk_bytes, FakeInstall, GameInfoVariant};
+use serde_json::json;
+
+#[test]
+fn migrates_storage_with_renamed_conflicts() {
+  let install = FakeInstall::new("migrate", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+  install.install("maps.vpk", &["maps/street.vmap_c"]);
+
+  context.mount_addon("maps.vpk").unwrap();
+  context
+    .create_profile("Casual", vec!["maps.vpk".to_string()])
+    .unwrap();
+
+  let new_storage = AddonStorage::new(install.root.join("new"));
+
+  std::fs::create_dir_all(&new_storage.path).unwrap();
+  std::fs::copy(
+    install.storage.addon_path("hud.vpk"),
+    new_storage.addon_path("hud.vpk"),
+  )
+  .unwrap();
+  std::fs::write(
+    new_storage.addon_path("maps.vpk"),
+    vpk_bytes(&[("maps/other_street.vmap_c", b"other")]),
+  )
+  .unwrap();
+
+  let plan = context.plan_storage_migration(&new_storage.path).unwrap();
+
+  assert_eq!(
+    plan
+      .addons
+      .iter()
+      .map(|addon| addon.conflict)
+      .collect::<Vec<_>>(),
+    vec![
+      Some(MigrationConflict::Identical),
+      Some(MigrationConflict::Different)
+    ]
+  );
+
+  let report = context
+    .set_install_path(
+      &new_storage.path,
+      MigrationOptions {
+        on_conflict: MigrationConflictPolicy::Rename,
+        delete_old: true,
+      },
+    )
+    .unwrap();
+
+  assert!(report.completed && report.failed.is_empty() && report.old_copies_deleted);
+  assert_eq!(
+    report
+      .migrated
+      .iter()
+      .map(|addon| (addon.new_file_name.as_str(), addon.outcome))
+      .collect::<Vec<_>>(),
+    vec![
+      ("hud.vpk", MigrationOutcome::AlreadyPresent),
+      ("maps (2).vpk", MigrationOutcome::Renamed),
+    ]
+  );
+
+  let context = Context {
+    storage: Some(new_storage.clone()),
+    ..install.context()
+  };
+
+  assert_eq!(
+    new_storage.list().unwrap().len(),
+    3,
+    "the conflicting addon is kept"
+  );
+  assert!(install.storage.list().unwrap().is_empty());
+  assert_eq!(
+    install.config.get("install_path"),
+    Some(json!(new_storage.path))
+  );
+  assert_eq!(context.list_mounted_addons().unwrap(), vec!["maps (2).vpk"]);
+  assert_eq!(context.list_profiles()[0].addons, vec!["maps (2).vpk"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn relinks_symlinks_after_storage_moved() {
+  let install = FakeInstall::new("relink", GameInfoVariant::Modded);
+  let context = install.context();
+
+  install
+    .config
+    .set("deploy_method", json!(DeployMethod::Symlink));
+  install.install("hud.vpk", &["materials/hud.vmat_c"]);
+
+  context.mount_addon("hud.vpk").unwrap();
+
+  let moved_storage = AddonStorage::new(install.root.join("moved"));
+
+  std::fs::rename(&install.storage.path, &moved_storage.path).unwrap();
+
+  assert_eq!(
+    json!(context.scan_mounted_addons().unwrap()[0])["broken"],
+    true
+  );
+
+  context
+    .set_install_path(&moved_storage.path, MigrationOptions::default())
+    .unwrap();
+
+  let context = Context {
+    storage: Some(moved_storage.clone()),
+    ..install.context()
+  };
+
+  let addon = json!(context.scan_mounted_addons().unwrap()[0]);
+
+  assert_eq!(addon["broken"], false);
+  assert_eq!(
+    addon["linkTarget"],
+    json!(moved_storage.addon_path("hud.vpk"))
+  );
+}
+
+#[test]
+fn validates_and_saves_game_path() {
+  let install = FakeInstall::new("game-path", GameInfoVariant::Vanilla);
+  let context = Context {
+    game: None,
+    ..install.context()
+  };
+
+  assert_eq!(
+    context.set_game_path(&install.root).unwrap_err().payload(),
+    ErrorPayload::new(
+      "InvalidGamePath",
+      "Game path does not contain game/citadel/gameinfo.gi."
+    )
+    .param("path", &install.root)
+  );
+  assert_eq!(
+    context.set_game_path(&install.game.path).unwrap(),
+    install.game
+  );
+  assert_eq!(
+    GameInstall::resolve(&install.config),
+    Some(install.game.clone())
+  );
+}
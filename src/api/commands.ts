@@ -1,4 +1,32 @@
-import { DeployMethod, InstallAddonInfo, SearchPathsState } from "./types";
+import {
+  AddonClassification,
+  AddonConflict,
+  AdoptedAddon,
+  AddonDrift,
+  AddonContents,
+  AddonFilter,
+  AddonLocation,
+  AddonThumbnail,
+  ArchiveAddon,
+  ConflictPolicy,
+  ConflictReport,
+  DeployCapabilities,
+  DeployMethod,
+  DeployedAddon,
+  DiffLine,
+  ErrorPayload,
+  GameInfoBackup,
+  GameUpdateStatus,
+  InstallAddonInfo,
+  LinkRepair,
+  MigrationOptions,
+  MigrationPlan,
+  MigrationReport,
+  Profile,
+  ProfileApplyReport,
+  SearchPathsReport,
+  SearchPathsState,
+} from "./types";
 
 export type commands = {
   get_game_path: {
@@ -6,76 +34,196 @@ export type commands = {
     output: string | undefined;
     error: undefined;
   };
+  list_game_installs: {
+    input: undefined;
+    output: string[];
+    error: undefined;
+  };
+  set_game_path: {
+    input: {
+      gamePath: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  detect_game_path: {
+    input: undefined;
+    output: string | undefined;
+    error: ErrorPayload;
+  };
   get_search_paths_state: {
     input: undefined;
     output: SearchPathsState;
-    error: string;
+    error: ErrorPayload;
+  };
+  get_search_paths_report: {
+    input: undefined;
+    output: SearchPathsReport;
+    error: ErrorPayload;
   };
   mod_search_paths: {
     input: undefined;
     output: undefined;
-    error: string;
+    error: ErrorPayload;
   };
   reset_search_paths: {
     input: undefined;
     output: undefined;
-    error: string;
+    error: ErrorPayload;
+  };
+  list_game_info_backups: {
+    input: undefined;
+    output: GameInfoBackup[];
+    error: ErrorPayload;
+  };
+  diff_game_info_backup: {
+    input: {
+      backupId: string;
+    };
+    output: DiffLine[];
+    error: ErrorPayload;
+  };
+  restore_game_info_backup: {
+    input: {
+      backupId: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  check_game_update: {
+    input: undefined;
+    output: GameUpdateStatus | undefined;
+    error: ErrorPayload;
+  };
+  get_game_update_status: {
+    input: undefined;
+    output: GameUpdateStatus | undefined;
+    error: undefined;
+  };
+  set_auto_repatch: {
+    input: {
+      autoRepatch: boolean;
+    };
+    output: undefined;
+    error: undefined;
+  };
+  get_auto_repatch: {
+    input: undefined;
+    output: boolean;
+    error: undefined;
   };
   get_install_path: {
     input: undefined;
     output: string | undefined;
     error: undefined;
   };
+  plan_storage_migration: {
+    input: {
+      installPath: string;
+    };
+    output: MigrationPlan;
+    error: ErrorPayload;
+  };
   set_install_path: {
     input: {
       installPath: string;
+      options?: MigrationOptions;
+      jobId?: string;
     };
-    output: undefined;
-    error: string;
+    output: MigrationReport;
+    error: ErrorPayload;
   };
   list_installed_addons: {
-    input: undefined;
+    input: { filter?: AddonFilter } | undefined;
     output: string[];
-    error: string;
+    error: ErrorPayload;
   };
   list_mounted_addons: {
-    input: undefined;
+    input: { filter?: AddonFilter } | undefined;
     output: string[];
-    error: string;
+    error: ErrorPayload;
+  };
+  classify_addons: {
+    input: {
+      force: boolean;
+    };
+    output: Record<string, AddonClassification>;
+    error: ErrorPayload;
+  };
+  get_addon_thumbnail: {
+    input: {
+      addonFileName: string;
+    };
+    output: AddonThumbnail | null;
+    error: ErrorPayload;
   };
   install_addon: {
     input: {
       input: InstallAddonInfo;
+      jobId?: string;
     };
     output: undefined;
-    error: string;
+    error: ErrorPayload;
+  };
+  list_archive_addons: {
+    input: {
+      archivePath: string;
+    };
+    output: ArchiveAddon[];
+    error: ErrorPayload;
+  };
+  import_archive: {
+    input: {
+      archivePath: string;
+      /** Paths inside the archive, imports every addon when empty. */
+      addons: string[];
+      jobId?: string;
+    };
+    output: string[];
+    error: ErrorPayload;
   };
   uninstall_addon: {
     input: {
       addonFileName: string;
     };
     output: undefined;
-    error: string;
+    error: ErrorPayload;
   };
   mount_addon: {
     input: {
       addonFileName: string;
+      jobId?: string;
     };
-    output: undefined;
-    error: string;
+    output: AddonConflict[];
+    error: ErrorPayload;
   };
   unmount_addon: {
     input: {
       addonFileName: string;
+      jobId?: string;
     };
     output: undefined;
-    error: string;
+    error: ErrorPayload;
+  };
+  unmount_unmanaged_addon: {
+    input: {
+      deployedFileName: string;
+      jobId?: string;
+    };
+    /** Stored file name, absent for a broken symlink. */
+    output: string | null;
+    error: ErrorPayload;
   };
   is_symlink_available: {
     input: undefined;
     output: boolean;
     error: undefined;
   };
+  get_deploy_capabilities: {
+    input: undefined;
+    output: DeployCapabilities;
+    error: undefined;
+  };
   get_deploy_method: {
     input: undefined;
     output: DeployMethod;
@@ -84,8 +232,121 @@ export type commands = {
   set_deploy_method: {
     input: {
       deployMethod: DeployMethod;
+      jobId?: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  get_addon_contents: {
+    input: {
+      addonFileName: string;
+      location: AddonLocation;
+    };
+    output: AddonContents;
+    error: ErrorPayload;
+  };
+  detect_conflicts: {
+    input: {
+      previewAddonFileName?: string;
+    };
+    output: ConflictReport;
+    error: ErrorPayload;
+  };
+  get_conflict_policy: {
+    input: undefined;
+    output: ConflictPolicy;
+    error: undefined;
+  };
+  set_conflict_policy: {
+    input: {
+      conflictPolicy: ConflictPolicy;
+    };
+    output: undefined;
+    error: undefined;
+  };
+  get_load_order: {
+    input: undefined;
+    output: DeployedAddon[];
+    error: ErrorPayload;
+  };
+  reorder_mounted_addons: {
+    input: {
+      order: string[];
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  list_profiles: {
+    input: undefined;
+    output: Profile[];
+    error: undefined;
+  };
+  create_profile: {
+    input: {
+      name: string;
+      addons: string[];
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  update_profile: {
+    input: {
+      name: string;
+      addons: string[];
+      newName?: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  delete_profile: {
+    input: {
+      name: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  apply_profile: {
+    input: {
+      name: string;
+      jobId?: string;
+    };
+    output: ProfileApplyReport;
+    error: ErrorPayload;
+  };
+  cancel_job: {
+    input: {
+      jobId: string;
     };
     output: undefined;
-    error: string;
+    error: undefined;
+  };
+  verify_deployment: {
+    input: undefined;
+    output: AddonDrift[];
+    error: ErrorPayload;
+  };
+  resync_addon: {
+    input: {
+      addonFileName: string;
+      from: AddonLocation;
+      jobId?: string;
+    };
+    output: undefined;
+    error: ErrorPayload;
+  };
+  repair_links: {
+    input: {
+      jobId?: string;
+    };
+    output: LinkRepair[];
+    error: ErrorPayload;
+  };
+  adopt_unmanaged_addons: {
+    input: {
+      redeploy: boolean;
+      jobId?: string;
+    };
+    output: AdoptedAddon[];
+    error: ErrorPayload;
   };
 };
@@ -1,9 +1,13 @@
 import { createStore } from "@tauri-apps/plugin-store";
 import useSWR from "swr";
 import useSWRMutation from "swr/mutation";
+import { AddonCategory } from "../types";
 
 export type AddonConfig = {
   displayName: string;
+  /** Recorded by the backend from the addon contents. */
+  categories?: AddonCategory[];
+  heroes?: string[];
 };
 
 export async function getAddonConfig(addonFileName: string) {
@@ -35,7 +39,7 @@ export async function setAddonConfig(
 
   await store.set("addons", {
     ...currentConfig,
-    [addonFileName]: config,
+    [addonFileName]: { ...currentConfig[addonFileName], ...config },
   });
 
   await store.save();
@@ -1,9 +1,263 @@
 export type SearchPathsState = "Vanilla" | "Modded" | "Custom";
 
-export type DeployMethod = "Copy" | "Symlink";
+export type SearchPathsDeviation =
+  | { ExtraEntry: { key: string; value: string } }
+  | { MissingGamePath: { value: string } }
+  | "GamePathsReordered"
+  | { UnexpectedValue: { key: string; expected: string; actual: string } }
+  | "AddonsPathAfterGamePath"
+  | { MissingPatchEntry: { key: string; value: string } };
+
+export type SearchPathsReport = {
+  state: SearchPathsState;
+  deviations: SearchPathsDeviation[];
+};
+
+export type GameInfoBackup = {
+  id: string;
+  createdAt: number;
+  hash: string;
+  size: number;
+};
+
+export type DiffLineKind = "Unchanged" | "Added" | "Removed";
+
+export type DiffLine = {
+  kind: DiffLineKind;
+  text: string;
+};
+
+export type GameUpdateOutcome =
+  | "StillPatched"
+  | "Repatched"
+  | "RepatchFailed"
+  | "PatchRequired";
+
+/** Payload of the `game-updated` event. */
+export type GameUpdateStatus = {
+  buildId: string;
+  previousBuildId: string;
+  searchPathsState: SearchPathsState;
+  outcome: GameUpdateOutcome;
+  error?: ErrorPayload;
+  detectedAt: number;
+};
+
+export type DeployMethod = "Copy" | "Symlink" | "Hardlink" | "Reflink";
+
+export interface DeployCapabilities {
+  symlink: boolean;
+  hardlink: boolean;
+  reflink: boolean;
+}
 
 export type InstallAddonInfo = {
   filePath: string;
   fileName?: string;
   displayName?: string;
 };
+
+export type ArchiveAddon = {
+  path: string;
+  fileName: string;
+  chunks: string[];
+  size: number;
+  displayName: string;
+};
+
+export type AddonLocation = "Stored" | "Mounted";
+
+/** Payload of the `addon-added`, `addon-removed` and `addon-changed` events. */
+export type AddonWatchEvent = {
+  kind: "AddonAdded" | "AddonRemoved" | "AddonChanged";
+  location: AddonLocation;
+  fileName: string;
+};
+
+/** Payload of the `search-paths-state-changed` event. */
+export type SearchPathsWatchEvent = {
+  kind: "SearchPathsStateChanged";
+  state: SearchPathsState | null;
+};
+
+export type AddonFileEntry = {
+  path: string;
+  size: number;
+  crc: number;
+  archiveIndex: number;
+  preloadBytes: number;
+};
+
+export type AddonContents = {
+  version: number;
+  files: AddonFileEntry[];
+  fileCount: number;
+  totalSize: number;
+};
+
+export type ConflictPolicy = "Ignore" | "Warn" | "Refuse";
+
+export type AddonConflict = {
+  path: string;
+  addons: string[];
+  winner: string;
+};
+
+export type Profile = {
+  name: string;
+  /** Stored addon file names, highest priority first. */
+  addons: string[];
+};
+
+export type ProfileAction = "Mount" | "Unmount";
+
+export type ProfileAddonResult = {
+  addon: string;
+  action: ProfileAction;
+  error?: ErrorPayload;
+  conflicts: AddonConflict[];
+};
+
+export type ProfileApplyReport = {
+  results: ProfileAddonResult[];
+};
+
+export type ConflictReport = {
+  conflicts: AddonConflict[];
+  unreadable: string[];
+};
+
+export type DeployedAddon = {
+  fileName: string;
+  deployedFileName: string;
+  slot?: number;
+  managed: boolean;
+  /** Target of a symlinked addon. */
+  linkTarget?: string;
+  /** Symlink whose target is missing. */
+  broken: boolean;
+};
+
+export type LinkRepairOutcome = "Relinked" | "Copied" | "Missing";
+
+export type LinkRepair = {
+  deployedFileName: string;
+  linkTarget: string;
+  outcome: LinkRepairOutcome;
+};
+
+export type AddonCategory =
+  | "HeroSkin"
+  | "AbilityVfx"
+  | "Sound"
+  | "Hud"
+  | "Map"
+  | "Localization"
+  | "Misc";
+
+export type AddonClassification = {
+  /** Every kind of content found, most files first. */
+  categories: AddonCategory[];
+  /** Internal hero names as used in the game files. */
+  heroes: string[];
+};
+
+/** Every given criterion has to match. */
+export type AddonFilter = {
+  category?: AddonCategory;
+  hero?: string;
+};
+
+export type AddonThumbnail = {
+  /** Cached PNG in the addon storage. */
+  path: string;
+  dataUrl: string;
+};
+
+export type AdoptOutcome =
+  | "Stored"
+  | "Matched"
+  | "NotLoadable"
+  | "Broken"
+  | "Failed";
+
+export type AdoptedAddon = {
+  deployedFileName: string;
+  /** Stored file name of the adopted addon. */
+  fileName?: string;
+  outcome: AdoptOutcome;
+  /** The deployed copy was replaced using the current deploy method. */
+  redeployed: boolean;
+  error?: ErrorPayload;
+};
+
+/** Payload of the `copy-progress` event. */
+export type CopyProgress = {
+  jobId: string;
+  copiedBytes: number;
+  totalBytes: number;
+};
+
+export type DriftState = "InSync" | "Stale" | "Diverged" | "Orphaned";
+
+export type AddonDrift = {
+  fileName: string;
+  deployedFileName: string;
+  state: DriftState;
+  /** Copy that changed, for stale addons. */
+  changed?: AddonLocation;
+};
+
+export type MigrationConflictPolicy = "Skip" | "Overwrite" | "Rename";
+
+export type MigrationOptions = {
+  onConflict?: MigrationConflictPolicy;
+  /** Delete the addons from the old storage once all were copied. */
+  deleteOld?: boolean;
+};
+
+export type MigrationConflict = "Identical" | "Different";
+
+export type PlannedAddon = {
+  fileName: string;
+  files: string[];
+  size: number;
+  conflict?: MigrationConflict;
+};
+
+export type MigrationPlan = {
+  addons: PlannedAddon[];
+  copyBytes: number;
+  availableBytes?: number;
+  enoughSpace: boolean;
+};
+
+export type MigrationOutcome =
+  | "Copied"
+  | "AlreadyPresent"
+  | "Skipped"
+  | "Overwritten"
+  | "Renamed";
+
+export type MigrationReport = {
+  migrated: {
+    fileName: string;
+    newFileName: string;
+    outcome: MigrationOutcome;
+  }[];
+  failed: { fileName: string; error: ErrorPayload }[];
+  relinked: LinkRepair[];
+  /** `false` when the migration was rolled back. */
+  completed: boolean;
+  oldCopiesDeleted: boolean;
+};
+
+/** Error returned by commands. `code` names the error and doesn't change,
+ * `params` holds the values its message is built from, for example `addon`,
+ * `path`, `ioKind` and `osError`. */
+export type ErrorPayload = {
+  code: string;
+  params: Record<string, unknown>;
+  /** English message. */
+  message: string;
+};
@@ -0,0 +1,24 @@
+import { useMessages, useTranslations } from "next-intl";
+import { ErrorPayload } from "./types";
+
+/** Formats command errors with the `errors` messages, falling back to the
+ * English message from the backend for codes without a translation. */
+export function useErrorMessage() {
+  const t = useTranslations("errors");
+  const messages = useMessages() as { errors?: Record<string, string> };
+
+  return (error: ErrorPayload) => {
+    if (!messages.errors?.[error.code]) {
+      return error.message;
+    }
+
+    const values = Object.fromEntries(
+      Object.entries(error.params).filter(
+        (entry): entry is [string, string | number] =>
+          typeof entry[1] === "string" || typeof entry[1] === "number"
+      )
+    );
+
+    return t(error.code, values);
+  };
+}
@@ -16,6 +16,13 @@ export default function Home() {
     undefined
   );
   const { data: mountedAddons } = useInvoke("list_mounted_addons", undefined);
+  const { data: loadOrder } = useInvoke("get_load_order", undefined);
+
+  // Placed in the addons folder by hand, their names can match stored addons.
+  const unmanagedAddons = useMemo(
+    () => loadOrder?.filter((addon) => !addon.managed) ?? [],
+    [loadOrder]
+  );
 
   const { trigger: installAddon } = useInvokeMutate("install_addon");
 
@@ -69,6 +76,14 @@ export default function Home() {
             {mountedAddons?.map((item, index) => (
               <AddonEntry key={index} fileName={item} mounted />
             ))}
+            {unmanagedAddons.map((addon) => (
+              <AddonEntry
+                key={addon.deployedFileName}
+                fileName={addon.deployedFileName}
+                mounted
+                unmanaged
+              />
+            ))}
           </div>
         </div>
 
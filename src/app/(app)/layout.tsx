@@ -1,9 +1,13 @@
+import CopyProgress from "@/components/main/copy-progress";
+import FsChangeListener from "@/components/main/fs-change-listener";
 import NavRoute from "@/components/nav/route";
 import { Button } from "@/components/ui/button";
 
 export default function AppLayout({ children }: { children: React.ReactNode }) {
   return (
     <div className="flex flex-row justify-start w-[100dvw] h-[100dvh]">
+      <FsChangeListener />
+      <CopyProgress />
       <div className="bg-surface-500 flex flex-col gap-2 p-2 items-start">
         <span className="font-bold py-2 text-xl self-center">AddonManager</span>
         <div className="h-[1px] w-full bg-surface-100/30" />
@@ -1,7 +1,11 @@
 "use client";
 
-import { DeployMethod } from "@/api/types";
-import { useInvoke, useInvokeMutate } from "@/api/useInvoke";
+import invoke from "@/api/invoke";
+import { DeployMethod, ErrorPayload, MigrationPlan } from "@/api/types";
+import { useErrorMessage } from "@/api/useErrorMessage";
+import { mutateInvoke, useInvoke, useInvokeMutate } from "@/api/useInvoke";
+import StorageMigration from "@/components/main/storage-migration";
+import { Button } from "@/components/ui/button";
 import {
   Dialog,
   DialogContent,
@@ -15,26 +19,45 @@ import { open } from "@tauri-apps/plugin-dialog";
 import { useState } from "react";
 
 export default function SettingsPage() {
-  const { data: installPath, mutate: mutateInstallPath } = useInvoke(
-    "get_install_path",
+  const { data: gamePath, mutate: mutateGamePath } = useInvoke(
+    "get_game_path",
     undefined
   );
-  const { data: symlinkAvailable, mutate: mutateSymlinkAvailable } = useInvoke(
-    "is_symlink_available",
+  const { data: gameInstalls } = useInvoke("list_game_installs", undefined);
+
+  const { data: installPath, mutate: mutateInstallPath } = useInvoke(
+    "get_install_path",
     undefined
   );
+  const { data: deployCapabilities, mutate: mutateDeployCapabilities } =
+    useInvoke("get_deploy_capabilities", undefined);
 
   const { data: deployMethod, mutate: mutateDeployMethod } = useInvoke(
     "get_deploy_method",
     undefined
   );
 
+  const { trigger: setGamePath } = useInvokeMutate("set_game_path");
+  const { trigger: detectGamePath } = useInvokeMutate("detect_game_path");
   const { trigger: setInstallPath } = useInvokeMutate("set_install_path");
   const { trigger: setDeployMethod } = useInvokeMutate("set_deploy_method");
 
-  const [error, setError] = useState<string>();
+  const [error, setError] = useState<ErrorPayload>();
+  const errorMessage = useErrorMessage();
   const [errorOpen, setErrorOpen] = useState(false);
 
+  const [migration, setMigration] = useState<{
+    target: string;
+    plan: MigrationPlan;
+  }>();
+
+  const onStorageChanged = () => {
+    mutateInstallPath();
+    mutateDeployCapabilities();
+    mutateInvoke("list_installed_addons");
+    mutateInvoke("list_mounted_addons");
+  };
+
   return (
     <div className="flex flex-col justify-start p-4 gap-4">
       <div className="self-stretch flex flex-row justify-between">
@@ -44,6 +67,81 @@ export default function SettingsPage() {
       </div>
 
       <div className="grid grid-cols-2 items-center gap-6">
+        <div className="flex flex-col gap-1">
+          <span className="font-bold text-lg">Game path</span>
+          <span className="text-primary-200 text-sm">
+            The Deadlock folder, detected from your Steam libraries unless
+            selected by hand
+          </span>
+        </div>
+        <div className="flex flex-col gap-2">
+          <div className="flex flex-row gap-2">
+            <Input
+              type="text"
+              className="cursor-pointer text-lg h-10"
+              value={gamePath ?? "Select..."}
+              readOnly
+              onClick={() => {
+                open({
+                  directory: true,
+                  multiple: false,
+                  title: "Select the Deadlock folder",
+                }).then(async (result) => {
+                  if (result === null) {
+                    return;
+                  }
+
+                  const res = await setGamePath({ gamePath: result });
+
+                  if (res.error) {
+                    setError(res.error);
+                    setErrorOpen(true);
+                  }
+
+                  mutateGamePath();
+                  mutateInvoke("get_search_paths_state");
+                });
+              }}
+            />
+            <Button
+              className="h-10"
+              onClick={async () => {
+                const res = await detectGamePath(undefined);
+
+                if (res.error) {
+                  setError(res.error);
+                  setErrorOpen(true);
+                }
+
+                mutateGamePath();
+                mutateInvoke("get_search_paths_state");
+              }}
+            >
+              Detect
+            </Button>
+          </div>
+          {gameInstalls && gameInstalls.length > 1 && (
+            <div className="flex flex-col items-start">
+              {gameInstalls.map((gameInstall) => (
+                <Button
+                  key={gameInstall}
+                  variant="ghost"
+                  size="sm"
+                  disabled={gameInstall === gamePath}
+                  onClick={async () => {
+                    await setGamePath({ gamePath: gameInstall });
+
+                    mutateGamePath();
+                    mutateInvoke("get_search_paths_state");
+                  }}
+                >
+                  {gameInstall}
+                </Button>
+              ))}
+            </div>
+          )}
+        </div>
+
         <div className="flex flex-col gap-1">
           <span className="font-bold text-lg">Addon storage path</span>
           <span className="text-primary-200 text-sm">
@@ -61,24 +159,34 @@ export default function SettingsPage() {
               multiple: false,
               canCreateDirectories: true,
               title: "Select addons install path",
-            }).then((result) => {
-              if (result !== null) {
-                setInstallPath({ installPath: result }).then((d) => {
-                  if (d.error) {
-                    setError(d.error);
-                    setErrorOpen(true);
-                  }
-                });
-                mutateInstallPath(undefined, {
-                  populateCache: false,
-                  revalidate: true,
-                  optimisticData: {
-                    success: true,
-                    result,
-                  },
-                });
-                mutateSymlinkAvailable();
+            }).then(async (result) => {
+              if (result === null || result === installPath) {
+                return;
+              }
+
+              const plan = await invoke("plan_storage_migration", {
+                installPath: result,
+              });
+
+              // Without readable addons in the old folder there is nothing
+              // to move, the path is switched right away.
+              if (plan.result) {
+                setMigration({ target: result, plan: plan.result });
+
+                return;
+              }
+
+              const res = await setInstallPath({
+                installPath: result,
+                jobId: crypto.randomUUID(),
+              });
+
+              if (res.error) {
+                setError(res.error);
+                setErrorOpen(true);
               }
+
+              onStorageChanged();
             });
           }}
         />
@@ -94,6 +202,7 @@ export default function SettingsPage() {
           onValueChange={async (s) => {
             const res = await setDeployMethod({
               deployMethod: s as DeployMethod,
+              jobId: crypto.randomUUID(),
             });
 
             if (res.error) {
@@ -117,20 +226,45 @@ export default function SettingsPage() {
             </TabsTrigger>
             <TabsTrigger
               value="Symlink"
-              disabled={!symlinkAvailable}
+              disabled={!deployCapabilities?.symlink}
               className="text-xl"
             >
               Symlink
             </TabsTrigger>
+            <TabsTrigger
+              value="Hardlink"
+              disabled={!deployCapabilities?.hardlink}
+              className="text-xl"
+            >
+              Hardlink
+            </TabsTrigger>
+            <TabsTrigger
+              value="Reflink"
+              disabled={!deployCapabilities?.reflink}
+              className="text-xl"
+            >
+              Reflink
+            </TabsTrigger>
           </TabsList>
         </Tabs>
       </div>
 
+      {migration && (
+        <StorageMigration
+          target={migration.target}
+          plan={migration.plan}
+          onClose={() => setMigration(undefined)}
+          onMigrated={onStorageChanged}
+        />
+      )}
+
       <Dialog open={errorOpen} onOpenChange={setErrorOpen}>
         <DialogContent>
           <DialogHeader>
             <DialogTitle>Error</DialogTitle>
-            <DialogDescription>{error}</DialogDescription>
+            <DialogDescription>
+              {error && errorMessage(error)}
+            </DialogDescription>
           </DialogHeader>
         </DialogContent>
       </Dialog>
@@ -14,29 +14,51 @@ import {
 } from "@/api/extras/mod-config";
 import { Button } from "../ui/button";
 import { useState } from "react";
-import { mutateInvoke, useInvokeMutate } from "@/api/useInvoke";
+import {
+  mutateInvoke,
+  useInvoke,
+  useInvokeMutate,
+} from "@/api/useInvoke";
 
 export interface AddonEntryProps {
   fileName: string;
   mounted?: boolean;
+  /** Placed in the addons folder by hand, `fileName` is its deployed name. */
+  unmanaged?: boolean;
 }
 
 export default function AddonEntry({
   fileName,
   mounted = false,
+  unmanaged = false,
 }: AddonEntryProps) {
   const { data, mutate } = useAddonConfig(fileName);
   const { trigger } = useAddonConfigMutation(fileName);
 
   const { trigger: mountAddon } = useInvokeMutate("mount_addon");
   const { trigger: unmountAddon } = useInvokeMutate("unmount_addon");
+  const { trigger: unmountUnmanagedAddon } = useInvokeMutate(
+    "unmount_unmanaged_addon"
+  );
   const { trigger: deleteAddon } = useInvokeMutate("uninstall_addon");
 
+  const { data: thumbnail } = useInvoke("get_addon_thumbnail", {
+    addonFileName: fileName,
+  });
+
   const [editOpen, setEditOpen] = useState(false);
 
   return (
     <div className="rounded-xl bg-surface-500 p-4 flex flex-row items-center gap-4 border border-surface-100/30">
-      <span className="icon-[lucide--file-cog] size-10" />
+      {thumbnail ? (
+        <img
+          src={thumbnail.dataUrl}
+          alt=""
+          className="size-10 rounded-md object-cover"
+        />
+      ) : (
+        <span className="icon-[lucide--file-cog] size-10" />
+      )}
 
       <div className="flex flex-col gap-1 self-stretch">
         <span className="font-bold text-lg">{data?.displayName}</span>
@@ -110,10 +132,21 @@ export default function AddonEntry({
           <button
             className="p-2 rounded-md flex bg-primary-500/30"
             onClick={async () => {
-              await unmountAddon({ addonFileName: fileName });
+              if (unmanaged) {
+                await unmountUnmanagedAddon({
+                  deployedFileName: fileName,
+                  jobId: crypto.randomUUID(),
+                });
+              } else {
+                await unmountAddon({
+                  addonFileName: fileName,
+                  jobId: crypto.randomUUID(),
+                });
+              }
 
               mutateInvoke("list_installed_addons");
               mutateInvoke("list_mounted_addons");
+              mutateInvoke("get_load_order");
             }}
           >
             <span className="icon-[lucide--download] size-6 text-primary-500" />
@@ -122,7 +155,10 @@ export default function AddonEntry({
           <button
             className="p-2 rounded-md flex bg-primary-500/30"
             onClick={async () => {
-              await mountAddon({ addonFileName: fileName });
+              await mountAddon({
+                addonFileName: fileName,
+                jobId: crypto.randomUUID(),
+              });
 
               mutateInvoke("list_installed_addons");
               mutateInvoke("list_mounted_addons");
@@ -122,6 +122,7 @@ export default function AddonInstaller({
     await Promise.all(
       entries.map((file) =>
         installAddon({
+          jobId: crypto.randomUUID(),
           input: {
             filePath: file.filePath,
             displayName: file.displayName,
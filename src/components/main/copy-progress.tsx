@@ -0,0 +1,76 @@
+"use client";
+
+import { CopyProgress as CopyProgressPayload } from "@/api/types";
+import invoke from "@/api/invoke";
+import { Button } from "@/components/ui/button";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useRef, useState } from "react";
+
+/** How long a finished job stays visible, jobs copying several addons report
+ * each of them separately. */
+const HIDE_DELAY = 1000;
+
+function formatSize(bytes: number) {
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+/** Shows the progress of running file copies and lets them be cancelled. */
+export default function CopyProgress() {
+  const [progress, setProgress] = useState<CopyProgressPayload>();
+
+  const hideTimeout = useRef<ReturnType<typeof setTimeout>>();
+
+  useEffect(() => {
+    const unlistenFn = listen<CopyProgressPayload>("copy-progress", (e) => {
+      clearTimeout(hideTimeout.current);
+
+      setProgress(e.payload);
+
+      if (e.payload.copiedBytes >= e.payload.totalBytes) {
+        hideTimeout.current = setTimeout(
+          () => setProgress(undefined),
+          HIDE_DELAY
+        );
+      }
+    });
+
+    return () => {
+      clearTimeout(hideTimeout.current);
+      unlistenFn.then((fn) => fn());
+    };
+  }, []);
+
+  if (!progress || progress.totalBytes === 0) {
+    return null;
+  }
+
+  const percent = Math.floor(
+    (progress.copiedBytes / progress.totalBytes) * 100
+  );
+
+  return (
+    <div className="fixed bottom-4 right-4 flex flex-col gap-2 w-80 p-3 rounded-md bg-surface-500 shadow">
+      <div className="flex flex-row items-center justify-between text-sm">
+        <span>
+          Copying {formatSize(progress.copiedBytes)} of{" "}
+          {formatSize(progress.totalBytes)}
+        </span>
+        <Button
+          size="sm"
+          variant="destructive"
+          onClick={() => {
+            invoke("cancel_job", { jobId: progress.jobId });
+          }}
+        >
+          Cancel
+        </Button>
+      </div>
+      <div className="h-2 w-full rounded bg-surface-100/30">
+        <div
+          className="h-full rounded bg-primary-600"
+          style={{ width: `${percent}%` }}
+        />
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,32 @@
+"use client";
+
+import { mutateInvoke } from "@/api/useInvoke";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { useEffect } from "react";
+
+const ADDON_EVENTS = ["addon-added", "addon-removed", "addon-changed"];
+
+/** Refreshes addon lists and the gameinfo state when files change on disk. */
+export default function FsChangeListener() {
+  useEffect(() => {
+    const unlistenFns: Promise<UnlistenFn>[] = [
+      ...ADDON_EVENTS.map((event) =>
+        listen(event, () => {
+          mutateInvoke("list_installed_addons");
+          mutateInvoke("list_mounted_addons");
+          mutateInvoke("get_load_order");
+        })
+      ),
+      listen("search-paths-state-changed", () => {
+        mutateInvoke("get_search_paths_state");
+        mutateInvoke("get_search_paths_report");
+      }),
+    ];
+
+    return () => {
+      unlistenFns.forEach((unlistenFn) => unlistenFn.then((fn) => fn()));
+    };
+  }, []);
+
+  return null;
+}
@@ -0,0 +1,154 @@
+"use client";
+
+import {
+  ErrorPayload,
+  MigrationConflictPolicy,
+  MigrationPlan,
+  MigrationReport,
+} from "@/api/types";
+import { useErrorMessage } from "@/api/useErrorMessage";
+import { useInvokeMutate } from "@/api/useInvoke";
+import { Button } from "@/components/ui/button";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { Tabs, TabsList, TabsTrigger } from "@/components/ui/tabs";
+import { useState } from "react";
+
+function formatSize(bytes: number) {
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+/** Shows what moving the addon storage to `target` copies, asks how to handle
+ * addons already in the new folder and reports the result. */
+export default function StorageMigration({
+  target,
+  plan,
+  onClose,
+  onMigrated,
+}: {
+  target: string;
+  plan: MigrationPlan;
+  onClose: () => void;
+  onMigrated: () => void;
+}) {
+  const { trigger: setInstallPath, isMutating } =
+    useInvokeMutate("set_install_path");
+
+  const [onConflict, setOnConflict] = useState<MigrationConflictPolicy>("Skip");
+  const [deleteOld, setDeleteOld] = useState(false);
+
+  const [report, setReport] = useState<MigrationReport>();
+  const [error, setError] = useState<ErrorPayload>();
+  const errorMessage = useErrorMessage();
+
+  const conflicts = plan.addons.filter(
+    (addon) => addon.conflict === "Different"
+  );
+
+  return (
+    <Dialog open onOpenChange={(open) => !open && !isMutating && onClose()}>
+      <DialogContent>
+        <DialogHeader>
+          <DialogTitle>Move addon storage</DialogTitle>
+          <DialogDescription className="break-all">{target}</DialogDescription>
+        </DialogHeader>
+
+        {report ? (
+          <div className="flex flex-col gap-2 text-sm">
+            {report.completed ? (
+              <span>
+                Moved {report.migrated.length} addons
+                {report.relinked.length > 0 &&
+                  `, ${report.relinked.length} symlinks updated`}
+                {deleteOld &&
+                  !report.oldCopiesDeleted &&
+                  ". Some addons could not be deleted from the old folder"}
+              </span>
+            ) : (
+              <span>
+                Nothing was moved, the old storage folder is still used.
+              </span>
+            )}
+            {report.failed.map((addon) => (
+              <span key={addon.fileName} className="text-red-400">
+                {addon.fileName}: {errorMessage(addon.error)}
+              </span>
+            ))}
+          </div>
+        ) : (
+          <div className="flex flex-col gap-4 text-sm">
+            <span>
+              {plan.addons.length} addons, {formatSize(plan.copyBytes)} to
+              copy
+              {plan.availableBytes !== undefined &&
+                ` (${formatSize(plan.availableBytes)} free)`}
+            </span>
+            {!plan.enoughSpace && (
+              <span className="text-red-400">
+                Not enough free space in the new folder.
+              </span>
+            )}
+            {conflicts.length > 0 && (
+              <div className="flex flex-col gap-2">
+                <span>
+                  A different addon with the same name is already in the new
+                  folder: {conflicts.map((addon) => addon.fileName).join(", ")}
+                </span>
+                <Tabs
+                  value={onConflict}
+                  onValueChange={(s) =>
+                    setOnConflict(s as MigrationConflictPolicy)
+                  }
+                >
+                  <TabsList>
+                    <TabsTrigger value="Skip">Keep theirs</TabsTrigger>
+                    <TabsTrigger value="Overwrite">Overwrite</TabsTrigger>
+                    <TabsTrigger value="Rename">Keep both</TabsTrigger>
+                  </TabsList>
+                </Tabs>
+              </div>
+            )}
+            <label className="flex flex-row items-center gap-2">
+              <input
+                type="checkbox"
+                checked={deleteOld}
+                onChange={(e) => setDeleteOld(e.target.checked)}
+              />
+              Delete the addons from the old folder afterwards
+            </label>
+            {error && (
+              <span className="text-red-400">{errorMessage(error)}</span>
+            )}
+            <Button
+              disabled={!plan.enoughSpace || isMutating}
+              onClick={async () => {
+                const res = await setInstallPath({
+                  installPath: target,
+                  options: { onConflict, deleteOld },
+                  jobId: crypto.randomUUID(),
+                });
+
+                if (res.error) {
+                  setError(res.error);
+                } else if (res.result) {
+                  setReport(res.result);
+
+                  if (res.result.completed) {
+                    onMigrated();
+                  }
+                }
+              }}
+            >
+              Move addons
+            </Button>
+          </div>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}